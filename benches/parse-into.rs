@@ -0,0 +1,47 @@
+// Measures parse_cmd_into once its buffers are warm, and checks that it then stops allocating.
+//
+// Run with `cargo bench --bench parse-into`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use windows_args::{Args, ParseScratch};
+
+#[global_allocator]
+static ALLOC: common::TestAlloc = common::TestAlloc;
+
+const ITERATIONS: usize = 100_000;
+
+const LINES: &[&str] = &[
+    r#""C:\Program Files\thing.exe" --flag "some value" C:\path\to\file.txt"#,
+    r#"C:\Windows\system32\cmd.exe /c "echo hi""#,
+    r#"a.exe"#,
+    r#"b.exe a\\\"b "" """#,
+];
+
+fn main() {
+    let mut scratch = ParseScratch::new();
+    let mut out = vec![];
+    // grow the buffers beyond the size of any single line
+    Args::parse_cmd_into(&LINES.join(" "), &mut out, &mut scratch);
+
+    for line in LINES {
+        Args::parse_cmd_into(line, &mut out, &mut scratch);
+        let start = Instant::now();
+        let allocs = common::count_allocs(|| {
+            for _ in 0..ITERATIONS {
+                Args::parse_cmd_into(black_box(line), &mut out, &mut scratch);
+            }
+        });
+        let elapsed = start.elapsed();
+
+        println!(
+            "{:>8.1} ns/iter  {} allocations  {:?}",
+            elapsed.as_nanos() as f64 / ITERATIONS as f64, allocs, line,
+        );
+        assert_eq!(allocs, 0, "parse_cmd_into allocated with warm buffers on {:?}", line);
+    }
+}
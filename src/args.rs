@@ -1,20 +1,421 @@
+use std::collections::TryReserveError;
+use std::convert;
 use std::fmt;
-use std::iter;
-use crate::wtf8like::{IsWtf8Slice, IsWtf8Buf};
+use std::hash::{Hash, Hasher};
+use std::iter::FusedIterator;
+use std::ops::{Bound, Range, RangeBounds};
+use crate::config::{DelimiterPolicy, ParseError, ParserConfig};
+use crate::wildcard::DirLister;
+use crate::wtf8like::{wtf8_len, IsWtf8Slice, IsWtf8Buf};
+use wtf8::Wtf8Buf;
 
 pub(crate) struct ArgsWtf8<S> {
     inner: std::vec::IntoIter<S>,
+    /// The input that was parsed (up to the first NUL), if it was kept.
+    raw: Option<Raw<S>>,
+}
+
+#[derive(Clone)]
+struct Raw<S> {
+    input: S,
+    /// Where the remaining arguments are in `input`.  This is advanced together with
+    /// `ArgsWtf8::inner`.
+    spans: Spans,
+}
+
+/// The ranges in which arguments were written, in bytes of the WTF-8 encoding of the input.
+#[derive(Clone)]
+enum Spans {
+    /// Each argument is one token of the input, so the ranges can be found again by parsing it
+    /// in this mode, and nothing needs to be stored per argument.  `front` is the index of the
+    /// token of the next argument.
+    Tokens { mode: Mode, front: usize },
+    /// The range of each remaining argument, for parsers that may add, drop or expand them.
+    Listed(std::vec::IntoIter<Range<usize>>),
+}
+
+impl Spans {
+    fn skip_front(&mut self, n: usize) {
+        match self {
+            Spans::Tokens { front, .. } => *front += n,
+            Spans::Listed(spans) => spans.by_ref().take(n).for_each(drop),
+        }
+    }
+
+    fn skip_back(&mut self, n: usize) {
+        match self {
+            Spans::Tokens { .. } => {},
+            Spans::Listed(spans) => spans.by_ref().rev().take(n).for_each(drop),
+        }
+    }
 }
 
 impl<S: IsWtf8Buf> ArgsWtf8<S> {
     pub(crate) fn parse_cmd<I: IsWtf8Slice + ?Sized>(input: &I) -> Self {
-        let mut wide: Vec<_> = input.encode_wide();
-        wide.push(0);
+        unwrap_infallible(Self::parse_in::<Infallible, I>(input, Mode::Cmd))
+    }
+
+    pub(crate) fn parse_args<I: IsWtf8Slice + ?Sized>(input: &I) -> Self {
+        unwrap_infallible(Self::parse_in::<Infallible, I>(input, Mode::Args))
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn try_parse_cmd_reserving<I: IsWtf8Slice + ?Sized>(input: &I) -> Result<Self, TryReserveError> {
+        Self::parse_in::<Fallible, I>(input, Mode::Cmd)
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn try_parse_args_reserving<I: IsWtf8Slice + ?Sized>(input: &I) -> Result<Self, TryReserveError> {
+        Self::parse_in::<Fallible, I>(input, Mode::Args)
+    }
+
+    /// Parse UTF-16 code units without copying them.
+    pub(crate) fn parse_cmd_wide(wide: &[u16]) -> Self {
+        unwrap_infallible(collect_lp_cmd_line::<Infallible, S>(wide, Mode::Cmd))
+    }
+
+    /// Parse UTF-16 code units as they are produced by an iterator.
+    pub(crate) fn parse_cmd_units(units: impl IntoIterator<Item=u16>) -> Self {
+        let mut ret_val = Vec::new();
+        unwrap_infallible(parse_cmd_units::<Infallible, u16>(units, &mut Vec::new(), |arg, _| {
+            ret_val.push(S::from_wide(arg));
+            Ok(())
+        }));
+        ArgsWtf8::from_vec(ret_val)
+    }
+
+    /// Like `parse_cmd_wide`, for arguments only.
+    pub(crate) fn parse_args_wide(wide: &[u16]) -> Self {
+        unwrap_infallible(collect_lp_cmd_line::<Infallible, S>(wide, Mode::Args))
+    }
+
+    /// Parse UTF-16 code units according to a [`ParserConfig`].
+    pub(crate) fn parse_wide_config(
+        wide: &[u16],
+        mode: Mode,
+        config: &ParserConfig,
+        dirs: &mut DirLister<'_>,
+    ) -> Result<Self, ParseError> {
+        let mut ret_val = Vec::new();
+        let mut spans = Vec::new();
+        let mut offsets = Wtf8Offsets::default();
+        let mut last_empty = false;
+        let open_quote = config.parse_units::<Limited, u16>(wide.iter().cloned(), mode, &mut Vec::new(), |arg, span| {
+            last_empty = arg.is_empty();
+            let raw_span = match config.retains_raw() {
+                true => offsets.of(wide, span.start)..offsets.of(wide, span.end),
+                false => 0..0,
+            };
+            let quoted = wide[span].contains(&(QUOTE as u16));
+            match config.expand_wildcards_in(ret_val.is_empty(), mode, quoted, || String::from_utf16(arg).ok(), dirs) {
+                Some(matches) => ret_val.extend(matches.iter().map(|name| S::from_wide(&name.encode_utf16().collect::<Vec<_>>()))),
+                None => ret_val.push(S::from_wide(arg)),
+            }
+            if config.retains_raw() {
+                // every argument that a wildcard expands to is written as the wildcard
+                spans.resize(ret_val.len(), raw_span);
+            }
+            config.check_arg_count(ret_val.len())
+        })?;
+        let empty = wide.first().is_none_or(|&c| c == 0);
+        let ret_val = config.finish(ret_val, mode, empty, open_quote, last_empty)?;
+        match config.retains_raw() {
+            true => Ok(ArgsWtf8::with_listed_raw(ret_val, S::from_wide(until_nul(wide)), spans)),
+            false => Ok(ArgsWtf8::from_vec(ret_val)),
+        }
+    }
+
+    fn parse_in<A: Alloc, I: IsWtf8Slice + ?Sized>(input: &I, mode: Mode) -> Result<Self, A::Error> {
+        let mut wide = Vec::new();
+        A::reserve(&mut wide, input.wide_len_bound())?;
+        input.extend_wide(&mut wide);
+
+        collect_lp_cmd_line::<A, S>(&wide, mode)
+    }
+}
+
+/// Parsing of UTF-8, which runs the parser directly over the bytes.
+///
+/// This avoids the temporary UTF-16 copy of the input, and the conversions of each argument
+/// to UTF-16 and back.
+impl ArgsWtf8<Wtf8Buf> {
+    pub(crate) fn parse_cmd_str(input: &str) -> Self {
+        unwrap_infallible(Self::parse_str_in::<Infallible>(input, Mode::Cmd))
+    }
+
+    pub(crate) fn parse_args_str(input: &str) -> Self {
+        unwrap_infallible(Self::parse_str_in::<Infallible>(input, Mode::Args))
+    }
+
+    pub(crate) fn try_parse_cmd_str_reserving(input: &str) -> Result<Self, TryReserveError> {
+        Self::parse_str_in::<Fallible>(input, Mode::Cmd)
+    }
+
+    pub(crate) fn try_parse_args_str_reserving(input: &str) -> Result<Self, TryReserveError> {
+        Self::parse_str_in::<Fallible>(input, Mode::Args)
+    }
+
+    /// Like `parse_cmd_str` or `parse_args_str`, reusing a buffer for the argument in progress.
+    pub(crate) fn parse_str_with(input: &str, mode: Mode, cur: &mut Vec<u8>) -> Self {
+        unwrap_infallible(Self::parse_str_in_with::<Infallible>(input, mode, cur))
+    }
+
+    /// Like `parse_wide_config`, for UTF-8.
+    pub(crate) fn parse_str_config(
+        input: &str,
+        mode: Mode,
+        config: &ParserConfig,
+        dirs: &mut DirLister<'_>,
+    ) -> Result<Self, ParseError> {
+        let mut ret_val = Vec::new();
+        let mut spans = Vec::new();
+        let mut last_empty = false;
+        let open_quote = config.parse_units::<Limited, u8>(input.bytes(), mode, &mut Vec::new(), |arg, span| {
+            last_empty = arg.is_empty();
+            let arg = still_utf8(arg);
+            let quoted = input.as_bytes()[span.clone()].contains(&b'"');
+            match config.expand_wildcards_in(ret_val.is_empty(), mode, quoted, || Some(arg.to_string()), dirs) {
+                Some(matches) => ret_val.extend(matches.iter().map(|name| Wtf8Buf::from_str(name))),
+                None => ret_val.push(Wtf8Buf::from_str(arg)),
+            }
+            if config.retains_raw() {
+                spans.resize(ret_val.len(), span);
+            }
+            config.check_arg_count(ret_val.len())
+        })?;
+        // the offset in errors is always counted in UTF-16 code units
+        let open_quote = open_quote.map(|pos| input[..pos].encode_utf16().count());
+        let empty = input.bytes().next().is_none_or(|c| c == 0);
+        let ret_val = config.finish(ret_val, mode, empty, open_quote, last_empty)?;
+        match config.retains_raw() {
+            true => Ok(ArgsWtf8::with_listed_raw(ret_val, Wtf8Buf::from_str(still_utf8(until_nul(input.as_bytes()))), spans)),
+            false => Ok(ArgsWtf8::from_vec(ret_val)),
+        }
+    }
+
+    fn parse_str_in<A: Alloc>(input: &str, mode: Mode) -> Result<Self, A::Error> {
+        Self::parse_str_in_with::<A>(input, mode, &mut Vec::new())
+    }
+
+    fn parse_str_in_with<A: Alloc>(input: &str, mode: Mode, cur: &mut Vec<u8>) -> Result<Self, A::Error> {
+        let mut ret_val = Vec::new();
+        mode.parse::<A, u8>(input.bytes(), cur, |arg, _| {
+            push::<A, _>(&mut ret_val, Wtf8Buf::from_string(A::from_utf8(arg)?))
+        })?;
+        let raw = Wtf8Buf::from_string(A::from_utf8(until_nul(input.as_bytes()))?);
+        Ok(ArgsWtf8::with_raw(ret_val, raw, mode))
+    }
+}
+
+/// Reusable buffers for [`ArgsOs::parse_cmd_into`] and [`Args::parse_cmd_into`].
+///
+/// Holding on to one of these between calls lets the parser skip allocating its
+/// temporary buffers every time.
+///
+/// [`ArgsOs::parse_cmd_into`]: struct.ArgsOs.html#method.parse_cmd_into
+/// [`Args::parse_cmd_into`]: struct.Args.html#method.parse_cmd_into
+#[derive(Debug, Default, Clone)]
+pub struct ParseScratch {
+    #[cfg(windows)]
+    wide: Vec<u16>,
+    #[cfg(windows)]
+    cur: Vec<u16>,
+    cur_utf8: Vec<u8>,
+}
+
+impl ParseScratch {
+    /// Create empty buffers.  Nothing is allocated until the first parse.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn parse_cmd_into<S: IsWtf8Buf, I: IsWtf8Slice + ?Sized>(
+    input: &I,
+    out: &mut Vec<S>,
+    scratch: &mut ParseScratch,
+) {
+    let ParseScratch { wide, cur, .. } = scratch;
+    wide.clear();
+    wide.reserve(input.wide_len_bound());
+    input.extend_wide(wide);
+
+    // Overwrite existing elements in place to reuse their capacity.
+    let mut len = 0;
+    unwrap_infallible(parse_lp_cmd_line::<Infallible>(wide, cur, |arg, _| {
+        match out.get_mut(len) {
+            Some(existing) => existing.assign_wide(arg),
+            None => out.push(S::from_wide(arg)),
+        }
+        len += 1;
+        Ok(())
+    }));
+    out.truncate(len);
+}
+
+/// Like `parse_cmd_into`, but runs the parser directly over UTF-8.
+pub(crate) fn parse_cmd_into_str(input: &str, out: &mut Vec<String>, scratch: &mut ParseScratch) {
+    let mut len = 0;
+    unwrap_infallible(parse_cmd_units::<Infallible, u8>(input.bytes(), &mut scratch.cur_utf8, |arg, _| {
+        let arg = still_utf8(arg);
+        match out.get_mut(len) {
+            Some(existing) => {
+                existing.clear();
+                existing.push_str(arg);
+            },
+            None => out.push(arg.to_string()),
+        }
+        len += 1;
+        Ok(())
+    }));
+    out.truncate(len);
+}
+
+/// Decides how [`parse_lp_cmd_line`] grows its buffers.
+///
+/// Every allocation made while parsing goes through one of these methods, so that the
+/// same state machine can serve both the ordinary API and the `try_reserve`-based one.
+pub(crate) trait Alloc {
+    type Error;
+
+    fn reserve<T>(vec: &mut Vec<T>, additional: usize) -> Result<(), Self::Error>;
+    fn from_wide<S: IsWtf8Buf>(wide: &[u16]) -> Result<S, Self::Error>;
+    fn from_utf8(bytes: &[u8]) -> Result<String, Self::Error>;
+}
+
+/// Allocation failure aborts the process, as usual.
+pub(crate) enum Infallible {}
+
+/// Allocation failure is reported as a [`TryReserveError`].
+pub(crate) enum Fallible {}
+
+/// Allocation failure aborts the process, but the output may be rejected with a
+/// [`ParseError`] (for instance, for exceeding a limit).
+pub(crate) enum Limited {}
+
+impl Alloc for Infallible {
+    type Error = convert::Infallible;
+
+    fn reserve<T>(vec: &mut Vec<T>, additional: usize) -> Result<(), Self::Error> {
+        vec.reserve(additional);
+        Ok(())
+    }
+
+    fn from_wide<S: IsWtf8Buf>(wide: &[u16]) -> Result<S, Self::Error> {
+        Ok(S::from_wide(wide))
+    }
+
+    fn from_utf8(bytes: &[u8]) -> Result<String, Self::Error> {
+        Ok(still_utf8(bytes).to_string())
+    }
+}
+
+impl Alloc for Fallible {
+    type Error = TryReserveError;
+
+    fn reserve<T>(vec: &mut Vec<T>, additional: usize) -> Result<(), Self::Error> {
+        vec.try_reserve(additional)
+    }
+
+    fn from_wide<S: IsWtf8Buf>(wide: &[u16]) -> Result<S, Self::Error> {
+        S::try_from_wide(wide)
+    }
+
+    fn from_utf8(bytes: &[u8]) -> Result<String, Self::Error> {
+        let mut out = String::new();
+        out.try_reserve_exact(bytes.len())?;
+        out.push_str(still_utf8(bytes));
+        Ok(out)
+    }
+}
+
+impl Alloc for Limited {
+    type Error = ParseError;
+
+    fn reserve<T>(vec: &mut Vec<T>, additional: usize) -> Result<(), Self::Error> {
+        vec.reserve(additional);
+        Ok(())
+    }
+
+    fn from_wide<S: IsWtf8Buf>(wide: &[u16]) -> Result<S, Self::Error> {
+        Ok(S::from_wide(wide))
+    }
+
+    fn from_utf8(bytes: &[u8]) -> Result<String, Self::Error> {
+        Ok(still_utf8(bytes).to_string())
+    }
+}
+
+fn still_utf8(bytes: &[u8]) -> &str {
+    std::str::from_utf8(bytes).unwrap_or_else(|e| {
+        panic!("valid UTF-8 became invalid after arg splitting?! ({:?})", e)
+    })
+}
 
-        ArgsWtf8 { inner: parse_lp_cmd_line(&wide).into_iter() }
+pub(crate) fn unwrap_infallible<T>(result: Result<T, convert::Infallible>) -> T {
+    match result {
+        Ok(x) => x,
+        Err(never) => match never {},
     }
 }
 
+pub(crate) fn push<A: Alloc, T>(vec: &mut Vec<T>, value: T) -> Result<(), A::Error> {
+    A::reserve(vec, 1)?;
+    vec.push(value);
+    Ok(())
+}
+
+pub(crate) fn push_n<A: Alloc, U: CodeUnit>(vec: &mut Vec<U>, value: U, n: usize) -> Result<(), A::Error> {
+    A::reserve(vec, n)?;
+    vec.resize(vec.len() + n, value);
+    Ok(())
+}
+
+/// Whether the input begins with an executable name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// A complete command line, parsed by [`parse_lp_cmd_line`].
+    Cmd,
+    /// Only the arguments.  Every argument is parsed by the ordinary rules, producing the
+    /// same arguments that `Cmd` would produce after the executable name.  Empty input
+    /// produces none.
+    Args,
+}
+
+impl Mode {
+    pub(crate) fn parse<A: Alloc, U: CodeUnit>(
+        self,
+        units: impl IntoIterator<Item=U>,
+        cur: &mut Vec<U>,
+        emit: impl FnMut(&[U], Range<usize>) -> Result<(), A::Error>,
+    ) -> Result<Option<usize>, A::Error> {
+        match self {
+            Mode::Cmd => parse_cmd_units::<A, U>(units, cur, emit),
+            Mode::Args => {
+                let units = units.into_iter().take_while(|&c| c != U::from(0));
+                parse_args_at::<A, U>(units, 0, cur, emit)
+            },
+        }
+    }
+}
+
+/// A code unit of an encoding in which each ASCII character is a single unit, and no unit
+/// of any other character is in the ASCII range.
+///
+/// Every character that the parser treats specially is ASCII, so it can work directly on
+/// either UTF-16 or UTF-8 (as well as their ill-formed counterparts), copying everything
+/// else through untouched.
+pub(crate) trait CodeUnit: Copy + Eq + From<u8> + Into<u32> {}
+
+impl CodeUnit for u8 {}
+impl CodeUnit for u16 {}
+
+pub(crate) const BACKSLASH: u32 = '\\' as u32;
+pub(crate) const QUOTE: u32 = '"' as u32;
+pub(crate) const TAB: u32 = '\t' as u32;
+pub(crate) const SPACE: u32 = ' ' as u32;
+
 /// Implements the Windows command-line argument parsing algorithm.
 ///
 /// Microsoft's documentation for the Windows CLI argument format can be found at
@@ -24,126 +425,244 @@ impl<S: IsWtf8Buf> ArgsWtf8<S> {
 /// but linking with that DLL causes the process to be registered as a GUI application.
 /// GUI applications add a bunch of overhead, even if no windows are drawn. See
 /// <https://randomascii.wordpress.com/2018/12/03/a-not-called-function-can-cause-a-5x-slowdown/>.
-fn parse_lp_cmd_line<S: IsWtf8Buf>(
+///
+/// The input ends at the first NUL or at the end of the slice, whichever comes first, so
+/// neither a terminator nor its absence affects the result.
+///
+/// Each argument is passed to `emit` as soon as it is complete, along with the range of
+/// `lp_cmd_line` that it was parsed from.  `cur` is scratch space for the argument currently
+/// being built.
+///
+/// The range of an argument spans from its first character to just before the whitespace
+/// (or end of input) that terminated it, including any quotes.  An executable name that is
+/// empty due to leading whitespace has the empty range `0..0`.
+///
+/// If the input ended inside a quoted region, returns the offset of the quote that opened it.
+pub(crate) fn parse_lp_cmd_line<A: Alloc>(
     lp_cmd_line: &[u16],
-) -> Vec<S> {
-    const BACKSLASH: u16 = '\\' as u16;
-    const QUOTE: u16 = '"' as u16;
-    const TAB: u16 = '\t' as u16;
-    const SPACE: u16 = ' ' as u16;
+    cur: &mut Vec<u16>,
+    emit: impl FnMut(&[u16], Range<usize>) -> Result<(), A::Error>,
+) -> Result<Option<usize>, A::Error> {
+    parse_cmd_units::<A, u16>(lp_cmd_line.iter().cloned(), cur, emit)
+}
 
-    let mut ret_val = Vec::new();
-    if lp_cmd_line[0] == 0 {
-        // NOTE: Here, CommandLineToArgvW would produce the current executable name, as
-        //       given by GetModuleFileNameW.
-        //
-        //       For our purposes, it makes more sense to treat this the same way we would
-        //       treat a string consisting entirely of whitespace.
-        ret_val.push(S::from_str(""));
-        return ret_val;
-    }
-    let mut cmd_line = {
-        let mut end = 0;
-        while lp_cmd_line[end] != 0 {
-            end += 1;
-        }
-        &lp_cmd_line[..end]
-    };
+/// Like [`parse_lp_cmd_line`], but reads code units one at a time from an iterator, in any
+/// [`CodeUnit`] encoding.  Ranges are in units of that encoding.
+///
+/// Parsing stops at the first NUL or when the iterator is exhausted, and nothing is read
+/// after that point.
+pub(crate) fn parse_cmd_units<A: Alloc, U: CodeUnit>(
+    units: impl IntoIterator<Item=U>,
+    cur: &mut Vec<U>,
+    emit: impl FnMut(&[U], Range<usize>) -> Result<(), A::Error>,
+) -> Result<Option<usize>, A::Error> {
+    parse_cmd_units_with::<A, U>(units, DelimiterPolicy::ControlPlane, cur, emit)
+}
+
+/// Like [`parse_cmd_units`], with a choice of the characters that end an unquoted
+/// executable name.
+pub(crate) fn parse_cmd_units_with<A: Alloc, U: CodeUnit>(
+    units: impl IntoIterator<Item=U>,
+    delimiters: DelimiterPolicy,
+    cur: &mut Vec<U>,
+    mut emit: impl FnMut(&[U], Range<usize>) -> Result<(), A::Error>,
+) -> Result<Option<usize>, A::Error> {
+    let mut units = units.into_iter().take_while(|&c| c != U::from(0)).map(|c| (c, c.into()));
+    cur.clear();
     // The executable name at the beginning is special.
-    cmd_line = match cmd_line[0] {
+    let base = match units.next() {
+        None => {
+            // NOTE: Here, CommandLineToArgvW would produce the current executable name, as
+            //       given by GetModuleFileNameW.
+            //
+            //       For our purposes, it makes more sense to treat this the same way we would
+            //       treat a string consisting entirely of whitespace.
+            emit(&[], 0..0)?;
+            return Ok(None);
+        },
         // The executable name ends at the next quote mark,
         // no matter what.
-        QUOTE => {
-            let args = {
-                let mut cut = cmd_line[1..].splitn(2, |&c| c == QUOTE);
-                if let Some(exe) = cut.next() {
-                    ret_val.push(S::from_wide(exe));
+        Some((_, QUOTE)) => {
+            let mut pos = 1;
+            loop {
+                match units.next() {
+                    Some((_, QUOTE)) => break,
+                    Some((c, _)) => push::<A, _>(cur, c)?,
+                    None => {
+                        // the closing quote is missing
+                        emit(&cur[..], 0..pos)?;
+                        return Ok(Some(0));
+                    },
                 }
-                cut.next()
-            };
-            if let Some(args) = args {
-                args
-            } else {
-                return ret_val;
+                pos += 1;
             }
-        }
+            emit(&cur[..], 0..pos + 1)?;
+            pos + 1
+        },
         // Implement quirk: when they say whitespace here,
         // they include the entire ASCII control plane:
         // "However, if lpCmdLine starts with any amount of whitespace, CommandLineToArgvW
         // will consider the first argument to be an empty string. Excess whitespace at the
         // end of lpCmdLine is ignored."
-        0..=SPACE => {
-            ret_val.push(S::from_str(""));
-            &cmd_line[1..]
+        Some((_, class)) if delimiters.ends_exe(class) => {
+            emit(&[], 0..0)?;
+            1
         },
         // The executable name ends at the next whitespace,
         // no matter what.
-        _ => {
-            let args = {
-                let mut cut = cmd_line.splitn(2, |&c| c > 0 && c <= SPACE);
-                if let Some(exe) = cut.next() {
-                    ret_val.push(S::from_wide(exe));
+        Some((first, _)) => {
+            push::<A, _>(cur, first)?;
+            let mut pos = 1;
+            loop {
+                match units.next() {
+                    Some((_, class)) if delimiters.ends_exe(class) => break,
+                    Some((c, _)) => push::<A, _>(cur, c)?,
+                    None => {
+                        emit(&cur[..], 0..pos)?;
+                        return Ok(None);
+                    },
                 }
-                cut.next()
-            };
-            if let Some(args) = args {
-                args
-            } else {
-                return ret_val;
+                pos += 1;
             }
-        }
+            emit(&cur[..], 0..pos)?;
+            // skip the whitespace
+            pos + 1
+        },
     };
-    let mut cur = Vec::new();
+    parse_args_at::<A, U>(units.map(|(c, _)| c), base, cur, emit)
+}
+
+/// Parse arguments by the ordinary rules, until the iterator is exhausted.  `base` is the
+/// offset of the first unit in the input, for the purpose of reporting ranges.
+fn parse_args_at<A: Alloc, U: CodeUnit>(
+    units: impl Iterator<Item=U>,
+    base: usize,
+    cur: &mut Vec<U>,
+    mut emit: impl FnMut(&[U], Range<usize>) -> Result<(), A::Error>,
+) -> Result<Option<usize>, A::Error> {
+    let backslash = U::from(BACKSLASH as u8);
+    let quote = U::from(QUOTE as u8);
+    let mut end = base;
+    cur.clear();
     let mut in_quotes = false;
+    let mut quote_start = 0;
     let mut was_in_quotes = false;
     let mut backslash_count: usize = 0;
-    for &c in cmd_line {
-        match c {
+    let mut start = None;
+    for (pos, c) in (base..).zip(units) {
+        end = pos + 1;
+        let class: u32 = c.into();
+        if start.is_none() && (in_quotes || (class != SPACE && class != TAB)) {
+            start = Some(pos);
+        }
+        match class {
             // backslash
             BACKSLASH => {
                 backslash_count += 1;
                 was_in_quotes = false;
             },
-            QUOTE if backslash_count % 2 == 0 => {
-                cur.extend(iter::repeat(b'\\' as u16).take(backslash_count / 2));
+            QUOTE if backslash_count.is_multiple_of(2) => {
+                push_n::<A, U>(cur, backslash, backslash_count / 2)?;
                 backslash_count = 0;
                 if was_in_quotes {
-                    cur.push('"' as u16);
+                    push::<A, _>(cur, quote)?;
                     was_in_quotes = false;
                 } else {
                     was_in_quotes = in_quotes;
                     in_quotes = !in_quotes;
+                    quote_start = pos;
                 }
             }
-            QUOTE if backslash_count % 2 != 0 => {
-                cur.extend(iter::repeat(b'\\' as u16).take(backslash_count / 2));
+            QUOTE if !backslash_count.is_multiple_of(2) => {
+                push_n::<A, U>(cur, backslash, backslash_count / 2)?;
                 backslash_count = 0;
                 was_in_quotes = false;
-                cur.push(b'"' as u16);
+                push::<A, _>(cur, quote)?;
             }
             SPACE | TAB if !in_quotes => {
-                cur.extend(iter::repeat(b'\\' as u16).take(backslash_count));
+                push_n::<A, U>(cur, backslash, backslash_count)?;
                 if !cur.is_empty() || was_in_quotes {
-                    ret_val.push(S::from_wide(&cur[..]));
+                    emit(&cur[..], start.unwrap_or(pos)..pos)?;
                     cur.truncate(0);
                 }
+                start = None;
                 backslash_count = 0;
                 was_in_quotes = false;
             }
             _ => {
-                cur.extend(iter::repeat(b'\\' as u16).take(backslash_count));
+                push_n::<A, U>(cur, backslash, backslash_count)?;
                 backslash_count = 0;
                 was_in_quotes = false;
-                cur.push(c);
+                push::<A, _>(cur, c)?;
             }
         }
     }
-    cur.extend(iter::repeat(b'\\' as u16).take(backslash_count));
+    push_n::<A, U>(cur, backslash, backslash_count)?;
     // include empty quoted strings at the end of the arguments list
     if !cur.is_empty() || was_in_quotes || in_quotes {
-        ret_val.push(S::from_wide(&cur[..]));
+        emit(&cur[..], start.unwrap_or(end)..end)?;
+    }
+    Ok(in_quotes.then_some(quote_start))
+}
+
+/// The part of the input that the parser reads: everything before the first NUL.
+pub(crate) fn until_nul<U: CodeUnit>(units: &[U]) -> &[U] {
+    let len = units.iter().position(|&c| c == U::from(0)).unwrap_or(units.len());
+    &units[..len]
+}
+
+/// Converts the offsets that the parser reports in UTF-16 code units into offsets in the WTF-8
+/// encoding of the same input.  They must be asked for in increasing order.
+#[derive(Default)]
+struct Wtf8Offsets {
+    units: usize,
+    bytes: usize,
+}
+
+impl Wtf8Offsets {
+    fn of(&mut self, wide: &[u16], pos: usize) -> usize {
+        // arguments never begin inside of a surrogate pair, so the pieces can be measured apart
+        self.bytes += wtf8_len(&wide[self.units..pos]);
+        self.units = pos;
+        self.bytes
+    }
+}
+
+fn collect_lp_cmd_line<A: Alloc, S: IsWtf8Buf>(
+    lp_cmd_line: &[u16],
+    mode: Mode,
+) -> Result<ArgsWtf8<S>, A::Error> {
+    let mut ret_val = Vec::new();
+    mode.parse::<A, u16>(lp_cmd_line.iter().cloned(), &mut Vec::new(), |arg, _| {
+        push::<A, _>(&mut ret_val, A::from_wide(arg)?)
+    })?;
+    let raw = A::from_wide(until_nul(lp_cmd_line))?;
+    Ok(ArgsWtf8::with_raw(ret_val, raw, mode))
+}
+
+fn resolve_range(range: &impl RangeBounds<usize>, len: usize) -> Option<Range<usize>> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.checked_add(1)?,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.checked_add(1)?,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    if start <= end && end <= len {
+        Some(start..end)
+    } else {
+        None
     }
-    ret_val
+}
+
+pub(crate) fn slice_range_panic(range: &impl RangeBounds<usize>, len: usize) -> ! {
+    panic!(
+        "range ({:?}, {:?}) out of bounds for {} remaining arguments",
+        range.start_bound(), range.end_bound(), len,
+    )
 }
 
 pub(crate) struct ArgsInnerDebug<'a, S> {
@@ -157,38 +676,208 @@ impl<'a, S: fmt::Debug> fmt::Debug for ArgsInnerDebug<'a, S> {
 }
 
 impl<S> ArgsWtf8<S> {
+    pub(crate) fn from_vec(vec: Vec<S>) -> Self {
+        ArgsWtf8 { inner: vec.into_iter(), raw: None }
+    }
+
+    /// For arguments that are exactly the tokens of `input` in `mode`.
+    pub(crate) fn with_raw(vec: Vec<S>, input: S, mode: Mode) -> Self {
+        let spans = Spans::Tokens { mode, front: 0 };
+        ArgsWtf8 { inner: vec.into_iter(), raw: Some(Raw { input, spans }) }
+    }
+
+    /// `spans` may be longer than `vec`, by any arguments that were dropped from the end.
+    fn with_listed_raw(vec: Vec<S>, input: S, mut spans: Vec<Range<usize>>) -> Self {
+        spans.truncate(vec.len());
+        let spans = Spans::Listed(spans.into_iter());
+        ArgsWtf8 { inner: vec.into_iter(), raw: Some(Raw { input, spans }) }
+    }
+
+    /// Convert the remaining arguments and the input alike, keeping track of where the
+    /// arguments are in it.  `f` must not change the WTF-8 encoding of what it converts.
+    #[cfg(windows)]
+    pub(crate) fn convert<T>(self, mut f: impl FnMut(S) -> T) -> ArgsWtf8<T> {
+        let raw = self.raw.map(|raw| Raw { input: f(raw.input), spans: raw.spans });
+        ArgsWtf8 { inner: self.inner.map(f).collect::<Vec<_>>().into_iter(), raw }
+    }
+
+    /// The input that was parsed, unless it was not kept.
+    pub(crate) fn raw(&self) -> Option<&S> {
+        self.raw.as_ref().map(|raw| &raw.input)
+    }
+
     pub(crate) fn inner_debug(&self) -> ArgsInnerDebug<'_, S> {
         ArgsInnerDebug {
             args: self
         }
     }
+
+    pub(crate) fn into_vec(self) -> Vec<S> {
+        self.inner.collect()
+    }
+
+    pub(crate) fn as_slice(&self) -> &[S] {
+        self.inner.as_slice()
+    }
+
+    pub(crate) fn get_slice(&self, range: impl RangeBounds<usize>) -> Option<Self>
+    where
+        S: Clone,
+    {
+        let range = resolve_range(&range, self.inner.len())?;
+        let vec = self.inner.as_slice()[range].to_vec();
+        Some(ArgsWtf8::from_vec(vec))
+    }
+
+    pub(crate) fn into_slice(self, range: impl RangeBounds<usize>) -> Self {
+        let range = resolve_range(&range, self.inner.len()).unwrap_or_else(|| {
+            slice_range_panic(&range, self.inner.len())
+        });
+        let mut vec = self.into_vec();
+        vec.truncate(range.end);
+        vec.drain(..range.start);
+        ArgsWtf8::from_vec(vec)
+    }
+
+    pub(crate) fn append(&mut self, other: Self) {
+        let mut vec = Vec::with_capacity(self.inner.len() + other.inner.len());
+        vec.extend(&mut self.inner);
+        vec.extend(other.inner);
+        // the arguments no longer come from a single input
+        *self = ArgsWtf8::from_vec(vec);
+    }
+}
+
+impl<S: IsWtf8Buf> ArgsWtf8<S> {
+    /// The input that was parsed, and the range in it of each remaining argument as written,
+    /// in bytes of its WTF-8 encoding.  There may be fewer ranges than arguments, if some were
+    /// added after parsing.
+    pub(crate) fn raw_spans(&self) -> Option<(&S, Vec<Range<usize>>)> {
+        let raw = self.raw.as_ref()?;
+        let spans = match &raw.spans {
+            Spans::Tokens { mode, front } => {
+                let mut spans = token_spans(&raw.input, *mode);
+                spans.truncate(front + self.inner.len());
+                spans.drain(..usize::min(*front, spans.len()));
+                spans
+            },
+            Spans::Listed(spans) => spans.as_slice().to_vec(),
+        };
+        Some((&raw.input, spans))
+    }
+
+    /// The input that was parsed, and the offset in it of the next argument (or `None` if there
+    /// are no arguments left).
+    pub(crate) fn rest_raw(&self) -> Option<(&S, Option<usize>)> {
+        self.raw_spans().map(|(input, spans)| (input, spans.first().map(|span| span.start)))
+    }
+}
+
+/// The range of every token of `input` in `mode`, in bytes of its WTF-8 encoding.
+fn token_spans<S: IsWtf8Buf>(input: &S, mode: Mode) -> Vec<Range<usize>> {
+    let mut spans = vec![];
+    // the parser only looks at ASCII, so the other bytes can be anything outside of it
+    let bytes = (0..input.wtf8_byte_len()).map(|i| input.ascii_byte_at(i));
+    unwrap_infallible(mode.parse::<Infallible, u8>(bytes, &mut Vec::new(), |_, span| {
+        spans.push(span);
+        Ok(())
+    }));
+    spans
+}
+
+impl<S: Clone> Clone for ArgsWtf8<S> {
+    fn clone(&self) -> Self {
+        ArgsWtf8 { inner: self.inner.clone(), raw: self.raw.clone() }
+    }
+}
+
+/// Compares the remaining arguments.
+impl<S: PartialEq> PartialEq for ArgsWtf8<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<S: Eq> Eq for ArgsWtf8<S> {}
+
+impl<S: Hash> Hash for ArgsWtf8<S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state)
+    }
 }
 
 impl<S> Iterator for ArgsWtf8<S> {
     type Item = S;
-    fn next(&mut self) -> Option<S> { self.inner.next() }
+
+    fn next(&mut self) -> Option<S> {
+        self.nth(0)
+    }
+
     fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+
+    fn nth(&mut self, n: usize) -> Option<S> {
+        if let Some(raw) = &mut self.raw {
+            raw.spans.skip_front(usize::min(n + 1, self.inner.len()));
+        }
+        self.inner.nth(n)
+    }
+
+    fn count(self) -> usize { self.inner.len() }
+    fn last(mut self) -> Option<S> { self.inner.next_back() }
 }
 
 impl<S> DoubleEndedIterator for ArgsWtf8<S> {
-    fn next_back(&mut self) -> Option<S> { self.inner.next_back() }
+    fn next_back(&mut self) -> Option<S> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<S> {
+        if let Some(raw) = &mut self.raw {
+            raw.spans.skip_back(usize::min(n + 1, self.inner.len()));
+        }
+        self.inner.nth_back(n)
+    }
 }
 
 impl<S> ExactSizeIterator for ArgsWtf8<S> {
     fn len(&self) -> usize { self.inner.len() }
 }
 
+impl<S> FusedIterator for ArgsWtf8<S> {}
+
+impl<S> Extend<S> for ArgsWtf8<S> {
+    fn extend<I: IntoIterator<Item=S>>(&mut self, iter: I) {
+        let mut vec = std::mem::replace(self, ArgsWtf8::from_vec(Vec::new())).into_vec();
+        vec.extend(iter);
+        // the arguments no longer come from a single input
+        *self = ArgsWtf8::from_vec(vec);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use wtf8::Wtf8Buf;
+    use crate::short_strings::{short_strings, short_strs};
+    use wtf8::{Wtf8, Wtf8Buf};
 
     fn chk(string: &str, parts: &[&str]) {
         let mut wide: Vec<u16> = Wtf8Buf::from_str(string).to_ill_formed_utf16().collect();
         wide.push(0);
-        let parsed = parse_lp_cmd_line::<Wtf8Buf>(&wide);
+        let parsed = unwrap_infallible(collect_lp_cmd_line::<Infallible, Wtf8Buf>(&wide, Mode::Cmd));
         let expected: Vec<Wtf8Buf> = parts.iter().map(|k| Wtf8Buf::from_str(k)).collect();
         assert_eq!(parsed.as_slice(), expected.as_slice());
+
+        let parsed = collect_lp_cmd_line::<Fallible, Wtf8Buf>(&wide, Mode::Cmd).unwrap();
+        assert_eq!(parsed.as_slice(), expected.as_slice());
+
+        // the UTF-8 implementation must agree
+        assert_eq!(ArgsWtf8::parse_cmd_str(string).into_vec(), expected);
+        assert_eq!(ArgsWtf8::try_parse_cmd_str_reserving(string).unwrap().into_vec(), expected);
+
+        // and so must the default configuration
+        let config = ParserConfig::default();
+        assert_eq!(config.parse_cmd(string).unwrap().inner.into_vec(), expected);
+        assert_eq!(config.parse_cmd(&wide).unwrap().inner.into_vec(), expected);
     }
 
     #[test]
@@ -206,6 +895,157 @@ mod tests {
         chk("EXE 😅🤦", &["EXE", "😅🤦"]);
     }
 
+    fn decode_fixture_field(field: &str) -> Vec<u16> {
+        assert!(field.len().is_multiple_of(4), "bad fixture field: {:?}", field);
+        (0..field.len()).step_by(4)
+            .map(|i| u16::from_str_radix(&field[i..i + 4], 16).expect("bad fixture field"))
+            .collect()
+    }
+
+    /// Expected output of `CommandLineToArgvW`, which `tests/command-line-to-argv-w.rs` can
+    /// replace with a recording.
+    #[test]
+    fn shell32_fixture() {
+        let fixture = include_str!("../tests/fixture/shell32.txt");
+        let mut lines = fixture.lines().filter(|line| !line.starts_with('#'));
+        assert_eq!(lines.next(), Some("version: 1"));
+        assert!(lines.next().unwrap().starts_with("source: "));
+
+        let mut count = 0;
+        for line in lines {
+            let mut fields = line.split('\t').map(decode_fixture_field);
+            let mut input = fields.next().unwrap();
+            let expected = fields.collect::<Vec<_>>();
+            input.push(0);
+
+            let mut parsed = vec![];
+            let mut cur = vec![];
+            unwrap_infallible(parse_lp_cmd_line::<Infallible>(&input, &mut cur, |arg, _| {
+                parsed.push(arg.to_vec());
+                Ok(())
+            }));
+            assert_eq!(parsed, expected, "input: {:x?}", input);
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn spans() {
+        let chk = |string: &str, expected: &[(usize, usize)]| {
+            let mut wide = string.encode_utf16().collect::<Vec<_>>();
+            wide.push(0);
+            let mut spans = vec![];
+            unwrap_infallible(parse_lp_cmd_line::<Infallible>(&wide, &mut vec![], |_, span| {
+                spans.push((span.start, span.end));
+                Ok(())
+            }));
+            assert_eq!(spans, expected, "{:?}", string);
+        };
+        chk("", &[(0, 0)]);
+        chk("  a", &[(0, 0), (2, 3)]);
+        chk(r#"EXE  a"b c"d  "" "#, &[(0, 3), (5, 12), (14, 16)]);
+        chk(r#""E X"a "b"#, &[(0, 5), (5, 6), (7, 9)]);
+        chk(r#""E X"#, &[(0, 4)]);
+        // the argument loop begins right after a closing quote, or after one whitespace
+        chk(r#""a""b c"#, &[(0, 3), (3, 7)]);
+        chk(r#"a"b c"d e"#, &[(0, 3), (4, 9)]);
+        chk("\t\t\"a\"", &[(0, 0), (2, 5)]);
+        chk("EXE a\0b", &[(0, 3), (4, 5)]);
+    }
+
+    #[test]
+    fn exe_token_boundaries() {
+        // an unquoted exe name ends at whitespace, even inside quotes
+        chk(r#"a"b c"d e"#, &[r#"a"b"#, "cd e"]);
+        // a quoted one ends at the next quote, and arguments begin immediately after it
+        chk(r#""unterminated exe"#, &["unterminated exe"]);
+        chk(r#""a"b c"#, &["a", "b", "c"]);
+        chk(r#""a""b c"#, &["a", "b c"]);
+        chk(r#"" a b"#, &[" a b"]);
+        chk(r#"" a" b"#, &[" a", "b"]);
+        chk(r#""" a"#, &["", "a"]);
+        chk(r#"a"" b"#, &[r#"a"""#, "b"]);
+        chk(r#"a" b"#, &[r#"a""#, "b"]);
+        // only a single whitespace character is consumed after an empty exe name
+        chk("\t\"a\"", &["", "a"]);
+    }
+
+    #[test]
+    fn units_match_slices() {
+        let alphabet = [b'a' as u16, b'"' as u16, b'\\' as u16, b' ' as u16, b'\t' as u16, 1, 0, 0xd800];
+        for input in short_strings(&alphabet, 5) {
+            let expected = ArgsWtf8::<Wtf8Buf>::parse_cmd_wide(&input).into_vec();
+            // an iterator that hides its length, and must not be read past a NUL
+            let mut iter = input.iter().cloned();
+            let mut read = 0;
+            let units = std::iter::from_fn(|| {
+                read += 1;
+                iter.next()
+            });
+            assert_eq!(ArgsWtf8::<Wtf8Buf>::parse_cmd_units(units).into_vec(), expected, "{:?}", input);
+            let nul = input.iter().position(|&c| c == 0);
+            assert_eq!(read, nul.map_or(input.len() + 1, |nul| nul + 1), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn utf8_matches_utf16() {
+        let alphabet = ['a', '"', '\\', ' ', '\t', '\u{1}', '\0', 'é', '😅'];
+        let mut scratch = ParseScratch::new();
+        let mut into = vec![];
+        for input in short_strs(&alphabet, 5) {
+            let wtf8 = Wtf8::from_str(&input);
+            let cmd = ArgsWtf8::<Wtf8Buf>::parse_cmd(wtf8).into_vec();
+            assert_eq!(ArgsWtf8::parse_cmd_str(&input).into_vec(), cmd, "{:?}", input);
+            let args = ArgsWtf8::<Wtf8Buf>::parse_args(wtf8).into_vec();
+            assert_eq!(ArgsWtf8::parse_args_str(&input).into_vec(), args, "{:?}", input);
+            assert_eq!(ArgsWtf8::try_parse_args_str_reserving(&input).unwrap().into_vec(), args, "{:?}", input);
+
+            parse_cmd_into_str(&input, &mut into, &mut scratch);
+            assert_eq!(into.iter().map(|s| Wtf8Buf::from_str(s)).collect::<Vec<_>>(), cmd, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn args_mode_matches_prepended_exe() {
+        // parse_args used to be implemented by prepending an exe name, so check exhaustively
+        // that the arguments are the same
+        let alphabet = [b'a' as u16, b'"' as u16, b'\\' as u16, b' ' as u16, b'\t' as u16, 0];
+        for mut input in short_strings(&alphabet, 5) {
+            input.push(0);
+
+            let args = unwrap_infallible(collect_lp_cmd_line::<Infallible, Wtf8Buf>(&input, Mode::Args));
+            let prepended = [&[b'a' as u16, b' ' as u16][..], &input].concat();
+            let cmd = unwrap_infallible(collect_lp_cmd_line::<Infallible, Wtf8Buf>(&prepended, Mode::Cmd));
+            assert_eq!(args.as_slice(), &cmd.as_slice()[1..], "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn explicit_length() {
+        let chk = |wide: &[u16], parts: &[&str]| {
+            let parsed = unwrap_infallible(collect_lp_cmd_line::<Infallible, Wtf8Buf>(wide, Mode::Cmd));
+            let expected = parts.iter().map(|k| Wtf8Buf::from_str(k)).collect::<Vec<_>>();
+            assert_eq!(parsed.into_vec(), expected);
+        };
+        let w = |s: &str| s.encode_utf16().collect::<Vec<_>>();
+
+        chk(&[], &[""]);
+        chk(&w("\""), &[""]);
+        chk(&w("EXE \""), &["EXE", ""]);
+        chk(&w("EXE a"), &["EXE", "a"]);
+        // NUL padding, as left by fixed-size reads
+        chk(&[&w("EXE a")[..], &[0; 8]].concat(), &["EXE", "a"]);
+        chk(&[&w("EXE \"a")[..], &[0; 3]].concat(), &["EXE", "a"]);
+        chk(&[0; 4], &[""]);
+
+        let args = unwrap_infallible(collect_lp_cmd_line::<Infallible, Wtf8Buf>(&[], Mode::Args));
+        assert_eq!(args.len(), 0);
+        let args = unwrap_infallible(collect_lp_cmd_line::<Infallible, Wtf8Buf>(&w("\""), Mode::Args));
+        assert_eq!(args.as_slice(), [Wtf8Buf::from_str("")]);
+    }
+
     #[test]
     fn official_examples() {
         chk(r#"EXE "abc" d e"#, &["EXE", "abc", "d", "e"]);
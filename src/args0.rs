@@ -0,0 +1,337 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use crate::wtf8like::IsWtf8Buf;
+
+/// Encoding of NUL-separated records, as used by [`Args::write_args0`] and [`Args::from_args0`].
+///
+/// [`Args::write_args0`]: struct.Args.html#method.write_args0
+/// [`Args::from_args0`]: struct.Args.html#method.from_args0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Args0Encoding {
+    /// [WTF-8](https://simonsapin.github.io/wtf-8/), separated by single NUL bytes.
+    ///
+    /// This is UTF-8, extended to losslessly represent unpaired surrogates.
+    Wtf8,
+    /// UTF-16LE (possibly containing unpaired surrogates), separated by NUL code units.
+    Utf16Le,
+}
+
+/// Options for writing arguments as NUL-separated records.
+///
+/// The default is WTF-8, with a NUL after every record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Args0Options {
+    /// How each argument is encoded.
+    pub encoding: Args0Encoding,
+    /// Whether the final record is followed by a NUL, like the output of `find -print0`.
+    ///
+    /// When this is `false`, NULs appear only *between* records.  Because a final NUL is taken
+    /// as a terminator when reading, a trailing empty argument is then lost (so e.g. the output
+    /// for an empty list is indistinguishable from the output for a single empty argument).
+    pub trailing_separator: bool,
+}
+
+impl Default for Args0Options {
+    fn default() -> Self {
+        Args0Options {
+            encoding: Args0Encoding::Wtf8,
+            trailing_separator: true,
+        }
+    }
+}
+
+/// An argument could not be written as a NUL-separated record because it contains a NUL.
+///
+/// This is the inner error of the `io::Error` returned by `write_args0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NulInArgError {
+    index: usize,
+}
+
+impl NulInArgError {
+    /// The index of the offending argument, among those that were being written.
+    pub fn index(&self) -> usize { self.index }
+}
+
+impl fmt::Display for NulInArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "argument {} contains a NUL and cannot be NUL-separated", self.index)
+    }
+}
+
+impl Error for NulInArgError {}
+
+/// A NUL-separated record could not be decoded.
+///
+/// This is returned by [`Args::from_args0`] and [`ArgsOs::from_args0`].
+///
+/// [`Args::from_args0`]: struct.Args.html#method.from_args0
+/// [`ArgsOs::from_args0`]: struct.ArgsOs.html#method.from_args0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args0Error {
+    index: usize,
+    offset: usize,
+}
+
+impl Args0Error {
+    /// The index of the malformed record.
+    pub fn index(&self) -> usize { self.index }
+
+    /// The byte offset into the entire input at which decoding failed.
+    pub fn offset(&self) -> usize { self.offset }
+}
+
+impl fmt::Display for Args0Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "record {} is malformed at byte offset {}", self.index, self.offset)
+    }
+}
+
+impl Error for Args0Error {}
+
+/// Write each record, given as UTF-16 code units.
+///
+/// Nothing is written if any record contains a NUL.
+pub(crate) fn write_args0<I, R>(mut w: impl io::Write, records: I, opts: &Args0Options) -> io::Result<()>
+where
+    I: Iterator<Item=R> + Clone,
+    R: Iterator<Item=u16>,
+{
+    for (index, mut record) in records.clone().enumerate() {
+        if record.any(|unit| unit == 0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, NulInArgError { index }));
+        }
+    }
+
+    let separator: &[u8] = match opts.encoding {
+        Args0Encoding::Wtf8 => &[0],
+        Args0Encoding::Utf16Le => &[0, 0],
+    };
+
+    let mut buf = vec![];
+    for (index, record) in records.enumerate() {
+        buf.clear();
+        if index > 0 && !opts.trailing_separator {
+            buf.extend_from_slice(separator);
+        }
+        match opts.encoding {
+            Args0Encoding::Wtf8 => encode_wtf8(&mut buf, record),
+            Args0Encoding::Utf16Le => buf.extend(record.flat_map(u16::to_le_bytes)),
+        }
+        if opts.trailing_separator {
+            buf.extend_from_slice(separator);
+        }
+        w.write_all(&buf)?;
+    }
+    Ok(())
+}
+
+fn encode_wtf8(out: &mut Vec<u8>, wide: impl Iterator<Item=u16>) {
+    for c in std::char::decode_utf16(wide) {
+        match c {
+            Ok(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            Err(e) => {
+                // the generalized UTF-8 encoding of a surrogate code point
+                let unit = e.unpaired_surrogate();
+                out.extend_from_slice(&[
+                    0xe0 | (unit >> 12) as u8,
+                    0x80 | ((unit >> 6) & 0x3f) as u8,
+                    0x80 | (unit & 0x3f) as u8,
+                ]);
+            },
+        }
+    }
+}
+
+/// Split NUL-separated records and decode each one.
+///
+/// A single NUL at the very end of the input terminates the final record rather than
+/// beginning a new one, so both the output with and without trailing separators are accepted.
+/// Unpaired surrogates are rejected unless `allow_surrogates` is set.
+pub(crate) fn read_args0<S: IsWtf8Buf>(
+    bytes: &[u8],
+    encoding: Args0Encoding,
+    allow_surrogates: bool,
+) -> Result<Vec<S>, Args0Error> {
+    let unit_size = match encoding {
+        Args0Encoding::Wtf8 => 1,
+        Args0Encoding::Utf16Le => 2,
+    };
+
+    let mut out = vec![];
+    let mut wide = vec![];
+    let mut start = 0;
+    while start < bytes.len() {
+        let len = bytes[start..].chunks(unit_size)
+            .position(|unit| unit.len() == unit_size && unit.iter().all(|&b| b == 0))
+            .map_or(bytes.len() - start, |units| units * unit_size);
+        let record = &bytes[start..start + len];
+
+        wide.clear();
+        let decoded = match encoding {
+            Args0Encoding::Wtf8 => decode_wtf8(record, &mut wide, allow_surrogates),
+            Args0Encoding::Utf16Le => decode_utf16le(record, &mut wide, allow_surrogates),
+        };
+        decoded.map_err(|offset| Args0Error { index: out.len(), offset: start + offset })?;
+        out.push(S::from_wide(&wide));
+
+        start += len + unit_size;
+    }
+    Ok(out)
+}
+
+/// Decode WTF-8, returning the offset of the first error.
+fn decode_wtf8(bytes: &[u8], out: &mut Vec<u16>, allow_surrogates: bool) -> Result<(), usize> {
+    let mut pos = 0;
+    loop {
+        let (valid, error) = match std::str::from_utf8(&bytes[pos..]) {
+            Ok(valid) => (valid, None),
+            Err(e) => (std::str::from_utf8(&bytes[pos..pos + e.valid_up_to()]).unwrap(), Some(e)),
+        };
+        out.extend(valid.encode_utf16());
+        pos += valid.len();
+        if error.is_none() {
+            return Ok(());
+        }
+
+        // UTF-8 forbids encoded surrogates, but WTF-8 allows them when unpaired
+        let unit = match bytes[pos..] {
+            [0xed, b1 @ 0xa0..=0xbf, b2 @ 0x80..=0xbf, ..] if allow_surrogates => {
+                0xd000 | (u16::from(b1) & 0x3f) << 6 | (u16::from(b2) & 0x3f)
+            },
+            _ => return Err(pos),
+        };
+        let follows_lead = matches!(out.last(), Some(0xd800..=0xdbff));
+        if follows_lead && (0xdc00..=0xdfff).contains(&unit) {
+            // a pair must be encoded as a single four-byte sequence
+            return Err(pos);
+        }
+        out.push(unit);
+        pos += 3;
+    }
+}
+
+/// Decode UTF-16LE, returning the offset of the first error.
+fn decode_utf16le(bytes: &[u8], out: &mut Vec<u16>, allow_surrogates: bool) -> Result<(), usize> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(bytes.len() - 1);
+    }
+    out.extend(bytes.chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])));
+    if !allow_surrogates {
+        let mut pos = 0;
+        for c in std::char::decode_utf16(out.iter().cloned()) {
+            match c {
+                Ok(c) => pos += 2 * c.len_utf16(),
+                Err(_) => return Err(pos),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(records: &[&[u16]], opts: &Args0Options) -> io::Result<Vec<u8>> {
+        let mut out = vec![];
+        write_args0(&mut out, records.iter().map(|r| r.iter().cloned()), opts)?;
+        Ok(out)
+    }
+
+    const NO_TRAILING: Args0Options = Args0Options {
+        encoding: Args0Encoding::Wtf8,
+        trailing_separator: false,
+    };
+
+    const UTF16: Args0Options = Args0Options {
+        encoding: Args0Encoding::Utf16Le,
+        trailing_separator: true,
+    };
+
+    #[test]
+    fn separators() {
+        let a = &[b'a' as u16][..];
+        let opts = Args0Options::default();
+        assert_eq!(write(&[], &opts).unwrap(), b"");
+        assert_eq!(write(&[&[]], &opts).unwrap(), b"\0");
+        assert_eq!(write(&[a, &[], a], &opts).unwrap(), b"a\0\0a\0");
+        assert_eq!(write(&[], &NO_TRAILING).unwrap(), b"");
+        assert_eq!(write(&[&[]], &NO_TRAILING).unwrap(), b"");
+        assert_eq!(write(&[a, &[], a], &NO_TRAILING).unwrap(), b"a\0\0a");
+        assert_eq!(write(&[a, &[]], &UTF16).unwrap(), b"a\0\0\0\0\0");
+    }
+
+    #[test]
+    fn surrogates() {
+        let opts = Args0Options::default();
+        assert_eq!(write(&[&[0xd83d, 0xde05]], &opts).unwrap(), "😅\0".as_bytes());
+        assert_eq!(write(&[&[0xd800]], &opts).unwrap(), b"\xed\xa0\x80\0");
+        assert_eq!(write(&[&[0xdfff]], &opts).unwrap(), b"\xed\xbf\xbf\0");
+        assert_eq!(write(&[&[0xd800]], &UTF16).unwrap(), b"\x00\xd8\0\0");
+    }
+
+    #[test]
+    fn interior_nul() {
+        let err = write(&[&[1], &[1, 0, 1]], &Args0Options::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let inner = err.into_inner().unwrap().downcast::<NulInArgError>().unwrap();
+        assert_eq!(inner.index(), 1);
+    }
+
+    fn read(bytes: &[u8], encoding: Args0Encoding) -> Result<Vec<Vec<u16>>, Args0Error> {
+        let records = read_args0::<wtf8::Wtf8Buf>(bytes, encoding, true)?;
+        Ok(records.iter().map(|r| r.to_ill_formed_utf16().collect()).collect())
+    }
+
+    fn read_str(bytes: &[u8], encoding: Args0Encoding) -> Result<Vec<String>, Args0Error> {
+        read_args0::<String>(bytes, encoding, false)
+    }
+
+    #[test]
+    fn reading() {
+        let wtf8 = Args0Encoding::Wtf8;
+        let empty: &[&str] = &[];
+        assert_eq!(read_str(b"", wtf8).unwrap(), empty);
+        assert_eq!(read_str(b"\0", wtf8).unwrap(), &[""]);
+        assert_eq!(read_str(b"\0\0", wtf8).unwrap(), &["", ""]);
+        assert_eq!(read_str(b"a\0\0b", wtf8).unwrap(), &["a", "", "b"]);
+        assert_eq!(read_str(b"a\0\0b\0", wtf8).unwrap(), &["a", "", "b"]);
+        assert_eq!(read_str(b"a\0b\0\0", wtf8).unwrap(), &["a", "b", ""]);
+
+        let utf16 = Args0Encoding::Utf16Le;
+        assert_eq!(read_str(b"", utf16).unwrap(), empty);
+        assert_eq!(read_str(b"a\0\0\0", utf16).unwrap(), &["a"]);
+        // a zero byte that is half of a code unit is not a separator
+        assert_eq!(read_str(b"\0\x01\0\0\x01\0", utf16).unwrap(), &["\u{100}", "\u{1}"]);
+    }
+
+    #[test]
+    fn reading_surrogates() {
+        let wtf8 = Args0Encoding::Wtf8;
+        assert_eq!(read(b"\xed\xa0\x80\0x\xed\xbf\xbf", wtf8).unwrap(), &[vec![0xd800], vec![0x78, 0xdfff]]);
+        assert_eq!(read(b"\xed\xb0\x80\xed\xa0\x80", wtf8).unwrap(), &[vec![0xdc00, 0xd800]]);
+        assert_eq!(read("😅\0".as_bytes(), wtf8).unwrap(), &[vec![0xd83d, 0xde05]]);
+        assert_eq!(read(b"\x00\xd8", Args0Encoding::Utf16Le).unwrap(), &[vec![0xd800]]);
+    }
+
+    #[test]
+    fn reading_errors() {
+        let chk = |bytes: &[u8], encoding, index, offset| {
+            assert_eq!(read(bytes, encoding), Err(Args0Error { index, offset }));
+        };
+        let wtf8 = Args0Encoding::Wtf8;
+        chk(b"a\0bc\xff", wtf8, 1, 4);
+        chk(b"\0\0\xe2\x82", wtf8, 2, 2);
+        chk(b"a\0\xc0\x80", wtf8, 1, 2);
+        // an encoded surrogate pair
+        chk(b"\xed\xa0\xbd\xed\xb8\x85", wtf8, 0, 3);
+        chk(b"a\0\0\0b\0\0", Args0Encoding::Utf16Le, 1, 6);
+
+        let err = read_str(b"ab\0c\xed\xa0\x80d", wtf8).unwrap_err();
+        assert_eq!((err.index(), err.offset()), (1, 4));
+        let err = read_str(b"a\0\0\xd8", Args0Encoding::Utf16Le).unwrap_err();
+        assert_eq!((err.index(), err.offset()), (0, 2));
+    }
+}
@@ -0,0 +1,439 @@
+//! Evaluation of batch file parameter references, like `%1`, `%~dp0` and `%*`.
+//!
+//! ```
+//! use windows_args::Args;
+//! use windows_args::batch::{expand_parameters, BatchExpandOptions};
+//!
+//! let args = Args::parse_cmd(r#"C:\scripts\build.bat "C:\My Files\in.txt" -v"#);
+//! let opts = BatchExpandOptions::default();
+//! assert_eq!(
+//!     expand_parameters(r#"copy %1 "%~dp0out\%~nx1" %%2"#, &args, &opts),
+//!     r#"copy "C:\My Files\in.txt" "C:\scripts\out\in.txt" %2"#,
+//! );
+//! ```
+//!
+//! The modifiers supported after `%~` are:
+//!
+//! | modifier | meaning |
+//! |----------|---------|
+//! | (none)   | the argument with surrounding quotes removed |
+//! | `f`      | fully qualified path |
+//! | `d`      | drive letter only |
+//! | `p`      | path only, with a trailing backslash |
+//! | `n`      | file name only |
+//! | `x`      | extension only |
+//! | `s`      | use short names in the path |
+//! | `a`      | file attributes |
+//! | `t`      | date/time of the file |
+//! | `z`      | size of the file |
+//!
+//! Like in `cmd.exe`, `d`, `p`, `n` and `x` always appear in that order no matter how they
+//! are written, and `a`, `t` and `z` produce a `dir`-like line (`attributes time size path`).
+//! The `$PATH:` search modifier is not supported, and such references are left untouched.
+//!
+//! Anything that requires looking at the filesystem is routed through a [`BatchMetadata`]
+//! implementation, so that expansion can be done against a virtual filesystem (or none at
+//! all).
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+use crate::{expect_still_utf8_ref, Args};
+
+/// Source of filesystem information for [`expand_parameters`].
+///
+/// All paths given to these methods are fully qualified, except for `full_path` itself.
+/// The default implementations do not touch the filesystem.
+pub trait BatchMetadata {
+    /// Resolve a path relative to the current directory.
+    ///
+    /// By default, the path is returned unchanged.
+    fn full_path(&self, path: &str) -> String {
+        path.to_string()
+    }
+
+    /// The short (8.3) form of a path, if it has one.
+    fn short_path(&self, _full_path: &str) -> Option<String> {
+        None
+    }
+
+    /// Attributes of an existing file, in the format used by `cmd.exe` (e.g. `--a--------`).
+    fn attributes(&self, _full_path: &str) -> Option<String> {
+        None
+    }
+
+    /// Last-modified time of an existing file, already formatted for display.
+    fn modified(&self, _full_path: &str) -> Option<String> {
+        None
+    }
+
+    /// Size in bytes of an existing file.
+    fn size(&self, _full_path: &str) -> Option<u64> {
+        None
+    }
+}
+
+/// A [`BatchMetadata`] that knows nothing about any filesystem.
+///
+/// Relative paths stay relative, and attributes, times and sizes expand to nothing as
+/// though the file did not exist.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoMetadata;
+
+impl BatchMetadata for NoMetadata {}
+
+/// Options for [`expand_parameters`].
+#[derive(Clone, Copy)]
+pub struct BatchExpandOptions<'a> {
+    /// Used by the modifiers that depend on the filesystem.  Defaults to [`NoMetadata`].
+    pub metadata: &'a dyn BatchMetadata,
+}
+
+impl Default for BatchExpandOptions<'_> {
+    fn default() -> Self {
+        BatchExpandOptions { metadata: &NoMetadata }
+    }
+}
+
+impl fmt::Debug for BatchExpandOptions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchExpandOptions").finish_non_exhaustive()
+    }
+}
+
+/// Expand the parameter references in one line of a batch file.
+///
+/// `args` is the command line that invoked the batch file, as parsed by [`Args::parse_cmd`],
+/// so that `%0` is the name of the batch file itself.  Only the arguments that have not yet
+/// been consumed are visible.
+///
+/// A parameter past the end of the arguments expands to nothing, and `%%` produces a single
+/// `%`.  Any other `%` (for instance, one belonging to an environment variable like
+/// `%PATH%`) is left alone.
+///
+/// As in `cmd.exe`, `%1` and `%*` are taken from the command line exactly as it was written
+/// (see [`Args::raw`]), and `%~1` is `%1` with the quotes at either end removed.  For `Args`
+/// that do not have the original text, `%1` is reconstructed instead: an argument that is
+/// empty or contains whitespace comes back wrapped in double quotes, while `%~1` produces
+/// the argument as-is.
+///
+/// [`Args::raw`]: ../struct.Args.html#method.raw
+pub fn expand_parameters(template: &str, args: &Args, opts: &BatchExpandOptions<'_>) -> String {
+    let params = Params::of(args);
+
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find('%') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        let (consumed, expansion) = match parse_reference(rest) {
+            Some((consumed, reference)) => (consumed, expand_reference(reference, &params, opts)),
+            None => (1, "%".to_string()),
+        };
+        out.push_str(&expansion);
+        rest = &rest[consumed..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reference<'a> {
+    Percent,
+    All,
+    Param(usize),
+    Modified(&'a str, usize),
+}
+
+/// Parse a reference at the beginning of a string that starts with `%`.
+fn parse_reference(s: &str) -> Option<(usize, Reference<'_>)> {
+    let bytes = s.as_bytes();
+    match bytes.get(1)? {
+        b'%' => Some((2, Reference::Percent)),
+        b'*' => Some((2, Reference::All)),
+        &c @ b'0'..=b'9' => Some((2, Reference::Param(usize::from(c - b'0')))),
+        b'~' => {
+            let len = bytes[2..].iter().take_while(|c| b"fdpnxsatzFDPNXSATZ".contains(c)).count();
+            let digit = *bytes.get(2 + len)?;
+            if digit.is_ascii_digit() {
+                Some((3 + len, Reference::Modified(&s[2..2 + len], usize::from(digit - b'0'))))
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+/// The arguments visible to a batch file.
+struct Params<'a> {
+    args: Vec<&'a str>,
+    /// The command line, and the range of each argument in it, if they are known.
+    raw: Option<(&'a str, Vec<Range<usize>>)>,
+}
+
+impl<'a> Params<'a> {
+    fn of(args: &'a Args) -> Self {
+        let raw = args.inner.raw_spans().map(|(raw, spans)| (expect_still_utf8_ref(raw), spans));
+        Params {
+            args: args.inner.as_slice().iter().map(|arg| expect_still_utf8_ref(arg)).collect(),
+            raw: raw.filter(|(_, spans)| spans.len() == args.len()),
+        }
+    }
+
+    /// `%n`: an argument as it was written.
+    fn written(&self, index: usize) -> Option<Cow<'a, str>> {
+        let arg = self.args.get(index)?;
+        Some(match &self.raw {
+            Some((raw, spans)) => Cow::Borrowed(&raw[spans[index].clone()]),
+            None => Cow::Owned(requote(arg)),
+        })
+    }
+
+    /// `%~n`: an argument as it was written, without the quotes at either end.
+    fn unquoted(&self, index: usize) -> Option<&'a str> {
+        let arg = self.args.get(index)?;
+        Some(match &self.raw {
+            Some((raw, spans)) => {
+                let written = &raw[spans[index].clone()];
+                let written = written.strip_prefix('"').unwrap_or(written);
+                written.strip_suffix('"').unwrap_or(written)
+            },
+            None => *arg,
+        })
+    }
+
+    /// `%*`: the arguments after `%0`, as they were written.
+    fn all(&self) -> String {
+        match &self.raw {
+            Some((raw, spans)) if spans.len() > 1 => raw[spans[1].start..spans[spans.len() - 1].end].to_string(),
+            Some(_) => String::new(),
+            None => self.args.iter().skip(1).map(|arg| requote(arg)).collect::<Vec<_>>().join(" "),
+        }
+    }
+}
+
+fn expand_reference(reference: Reference<'_>, params: &Params<'_>, opts: &BatchExpandOptions<'_>) -> String {
+    match reference {
+        Reference::Percent => "%".to_string(),
+        Reference::All => params.all(),
+        Reference::Param(index) => params.written(index).map_or(String::new(), Cow::into_owned),
+        Reference::Modified(modifiers, index) => match params.unquoted(index) {
+            Some(param) => apply_modifiers(modifiers, param, opts.metadata),
+            None => String::new(),
+        },
+    }
+}
+
+fn requote(param: &str) -> String {
+    if param.is_empty() || param.contains([' ', '\t']) {
+        format!("\"{}\"", param)
+    } else {
+        param.to_string()
+    }
+}
+
+fn apply_modifiers(modifiers: &str, param: &str, metadata: &dyn BatchMetadata) -> String {
+    let has = |c: char| modifiers.contains(c) || modifiers.contains(c.to_ascii_uppercase());
+    if modifiers.is_empty() {
+        return param.to_string();
+    }
+
+    let mut full_path = metadata.full_path(param);
+    if has('s') {
+        if let Some(short) = metadata.short_path(&full_path) {
+            full_path = short;
+        }
+    }
+
+    let mut parts = vec![];
+    if has('a') {
+        parts.extend(metadata.attributes(&full_path));
+    }
+    if has('t') {
+        parts.extend(metadata.modified(&full_path));
+    }
+    if has('z') {
+        parts.extend(metadata.size(&full_path).map(|size| size.to_string()));
+    }
+
+    let components = PathComponents::split(&full_path);
+    let path = match (has('d'), has('p'), has('n'), has('x')) {
+        (false, false, false, false) if has('f') || has('s') => full_path.clone(),
+        (false, false, false, false) => String::new(),
+        (d, p, n, x) => {
+            let mut path = String::new();
+            let selected = [(d, components.drive), (p, components.dir), (n, components.name), (x, components.ext)];
+            for &(wanted, component) in &selected {
+                if wanted {
+                    path.push_str(component);
+                }
+            }
+            path
+        },
+    };
+    if !path.is_empty() {
+        parts.push(path);
+    }
+    parts.join(" ")
+}
+
+/// The pieces of a path selected by the `d`, `p`, `n` and `x` modifiers.
+///
+/// Concatenating all four gives back the original path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PathComponents<'a> {
+    drive: &'a str,
+    dir: &'a str,
+    name: &'a str,
+    ext: &'a str,
+}
+
+impl<'a> PathComponents<'a> {
+    fn split(path: &'a str) -> Self {
+        let bytes = path.as_bytes();
+        let drive_len = if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' { 2 } else { 0 };
+        let (drive, rest) = path.split_at(drive_len);
+
+        let dir_len = rest.rfind(['\\', '/']).map_or(0, |pos| pos + 1);
+        let (dir, file) = rest.split_at(dir_len);
+
+        let name_len = file.rfind('.').unwrap_or(file.len());
+        let (name, ext) = file.split_at(name_len);
+        PathComponents { drive, dir, name, ext }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockMetadata;
+
+    impl BatchMetadata for MockMetadata {
+        fn full_path(&self, path: &str) -> String {
+            if path.starts_with("C:") {
+                path.to_string()
+            } else {
+                format!("C:\\cwd\\{}", path)
+            }
+        }
+
+        fn short_path(&self, full_path: &str) -> Option<String> {
+            Some(full_path.replace("Program Files", "PROGRA~1"))
+        }
+
+        fn attributes(&self, full_path: &str) -> Option<String> {
+            self.size(full_path).map(|_| "--a--------".to_string())
+        }
+
+        fn modified(&self, full_path: &str) -> Option<String> {
+            self.size(full_path).map(|_| "2019-06-18 12:00".to_string())
+        }
+
+        fn size(&self, full_path: &str) -> Option<u64> {
+            match full_path {
+                "C:\\cwd\\exists.txt" => Some(1234),
+                _ => None,
+            }
+        }
+    }
+
+    fn chk(template: &str, cmd: &str, expected: &str) {
+        let args = Args::parse_cmd(cmd);
+        let opts = BatchExpandOptions { metadata: &MockMetadata };
+        assert_eq!(expand_parameters(template, &args, &opts), expected);
+    }
+
+    #[test]
+    fn plain_params() {
+        chk("%0 %1 %2", "s.bat a b", "s.bat a b");
+        chk("[%1]", r#"s.bat "a b""#, r#"["a b"]"#);
+        chk("[%1]", r#"s.bat """#, r#"[""]"#);
+        chk("[%~1]", r#"s.bat "a b""#, "[a b]");
+        chk("[%~1]", r#"s.bat """#, "[]");
+        chk("%*", r#"s.bat a "b c"  d"#, r#"a "b c"  d"#);
+        chk("[%*]", "s.bat", "[]");
+        chk("[%*]", "s.bat  ", "[]");
+    }
+
+    #[test]
+    fn params_as_written() {
+        chk("[%1]", r#"s.bat a"b c"d"#, r#"[a"b c"d]"#);
+        chk("[%1]", r#"s.bat "a"b"#, r#"["a"b]"#);
+        chk("[%~1]", r#"s.bat "a"b"#, r#"[a"b]"#);
+        chk("[%~1]", r#"s.bat a"b c""#, r#"[a"b c]"#);
+        chk("[%1]", r#"s.bat a\"b"#, r#"[a\"b]"#);
+        chk("[%~nx1]", r#"s.bat "C:\a b"\c.txt"#, "[c.txt]");
+        chk("[%*]", "s.bat \t a\t\"b\"  ", "[a\t\"b\"]");
+
+        // shifted arguments are still taken from the command line
+        let mut args = Args::parse_cmd(r#"s.bat "a"b "c  d""#);
+        args.next();
+        let opts = BatchExpandOptions::default();
+        assert_eq!(expand_parameters("[%0] [%*]", &args, &opts), r#"["a"b] ["c  d"]"#);
+    }
+
+    #[test]
+    fn params_without_raw() {
+        let args = vec!["s.bat", "a b", "", "c"].into_iter().map(String::from).collect::<Args>();
+        let opts = BatchExpandOptions::default();
+        assert_eq!(expand_parameters("[%1] [%~1] [%*]", &args, &opts), r#"["a b"] [a b] ["a b" "" c]"#);
+    }
+
+    #[test]
+    fn out_of_range() {
+        chk("[%3][%~3][%~dp9]", "s.bat a b", "[][][]");
+    }
+
+    #[test]
+    fn literal_percent() {
+        chk("100%% %%1 %PATH% 50%", "s.bat a", "100% %1 %PATH% 50%");
+        chk("%~q1 %~", "s.bat a", "%~q1 %~");
+    }
+
+    #[test]
+    fn path_modifiers() {
+        let cmd = r#"C:\scripts\s.bat "C:\Program Files\app.tar.gz" rel\file.txt"#;
+        chk("%~d1|%~p1|%~n1|%~x1", cmd, r#"C:|\Program Files\|app.tar|.gz"#);
+        chk("%~dpnx2", cmd, r#"C:\cwd\rel\file.txt"#);
+        chk("%~xnpd2", cmd, r#"C:\cwd\rel\file.txt"#);
+        chk("%~nx1", cmd, "app.tar.gz");
+        chk("%~dp0", cmd, r#"C:\scripts\"#);
+        chk("%~f2", cmd, r#"C:\cwd\rel\file.txt"#);
+        chk("%~s1", cmd, r#"C:\PROGRA~1\app.tar.gz"#);
+        chk("%~sdp1", cmd, r#"C:\PROGRA~1\"#);
+        chk("%~DPNX2", cmd, r#"C:\cwd\rel\file.txt"#);
+    }
+
+    #[test]
+    fn metadata_modifiers() {
+        chk("%~z1", "s.bat exists.txt", "1234");
+        chk("%~z1", "s.bat missing.txt", "");
+        chk("%~ftza1", "s.bat exists.txt", r#"--a-------- 2019-06-18 12:00 1234 C:\cwd\exists.txt"#);
+        chk("%~ftza1", "s.bat missing.txt", r#"C:\cwd\missing.txt"#);
+        chk("%~za1", "s.bat exists.txt", "--a-------- 1234");
+    }
+
+    #[test]
+    fn no_metadata() {
+        let args = Args::parse_cmd(r#"s.bat rel\file.txt"#);
+        let opts = BatchExpandOptions::default();
+        assert_eq!(expand_parameters("%~dp1|%~nx1|%~z1|%~f1", &args, &opts), r#"rel\|file.txt||rel\file.txt"#);
+    }
+
+    #[test]
+    fn path_components() {
+        let split = |path| {
+            let c = PathComponents::split(path);
+            (c.drive, c.dir, c.name, c.ext)
+        };
+        assert_eq!(split(r#"C:\a\b.c"#), ("C:", r#"\a\"#, "b", ".c"));
+        assert_eq!(split("b"), ("", "", "b", ""));
+        assert_eq!(split(".gitignore"), ("", "", "", ".gitignore"));
+        assert_eq!(split(r#"\\server\share\x.y"#), ("", r#"\\server\share\"#, "x", ".y"));
+        assert_eq!(split("C:"), ("C:", "", "", ""));
+    }
+}
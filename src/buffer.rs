@@ -0,0 +1,124 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use crate::ArgsOs;
+use crate::nul::{InteriorNulError, check_no_nul};
+
+/// The number of NUL code units that [`CmdLineBuffer`] places after the terminator.
+const HEADROOM: usize = 8;
+
+/// **Windows only.**
+/// An owned, mutable, NUL-terminated command line, suitable for the `lpCommandLine`
+/// argument of `CreateProcessW`.
+///
+/// `CreateProcessW` is documented to possibly modify this buffer, so it must not be given
+/// a pointer into the storage of an immutable string.  The terminator is followed by a
+/// few additional NUL code units of headroom.
+///
+/// ```no_run
+/// # use std::ffi::OsStr;
+/// use windows_args::CmdLineBuffer;
+///
+/// let mut buf = CmdLineBuffer::new(OsStr::new(r#"app.exe "some file.txt""#)).unwrap();
+/// let ptr = buf.as_mut_ptr(); // pass this to CreateProcessW
+/// # let _ = ptr;
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct CmdLineBuffer {
+    // content, then the terminator, then HEADROOM more NULs
+    wide: Vec<u16>,
+}
+
+impl CmdLineBuffer {
+    /// Copy a complete command line into a new buffer.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the command line contains a NUL.
+    pub fn new(cmd_line: &OsStr) -> Result<CmdLineBuffer, InteriorNulError> {
+        let len = cmd_line.encode_wide().count();
+        let mut wide = Vec::with_capacity(len + 1 + HEADROOM);
+        wide.extend(cmd_line.encode_wide());
+        check_no_nul(&wide)?;
+        wide.resize(len + 1 + HEADROOM, 0);
+        Ok(CmdLineBuffer { wide })
+    }
+
+    /// Get a pointer to the NUL-terminated command line.
+    ///
+    /// The pointer is only valid for as long as this buffer is borrowed; do not use it after
+    /// the buffer is moved, dropped, or otherwise used.  Writes through it are permitted,
+    /// up to and including the terminator and headroom.
+    pub fn as_mut_ptr(&mut self) -> *mut u16 {
+        self.wide.as_mut_ptr()
+    }
+
+    /// The length of the command line in UTF-16 code units, not counting the terminator.
+    pub fn len(&self) -> usize {
+        self.wide.len() - 1 - HEADROOM
+    }
+
+    /// Whether the command line is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The current contents of the buffer, up to its first NUL.
+    ///
+    /// This is normally the original command line, but may differ if it was modified
+    /// through [`CmdLineBuffer::as_mut_ptr`].
+    pub fn as_wide(&self) -> &[u16] {
+        let end = self.wide.iter().position(|&unit| unit == 0).unwrap_or(self.len());
+        &self.wide[..end]
+    }
+
+    /// Parse the current contents of the buffer, as the child process would.
+    pub fn reparse(&self) -> ArgsOs {
+        ArgsOs::parse_cmd_wide(self.as_wide())
+    }
+}
+
+impl fmt::Debug for CmdLineBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CmdLineBuffer")
+            .field(&OsString::from_wide(self.as_wide()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invariants() {
+        let mut buf = CmdLineBuffer::new(OsStr::new("app.exe a")).unwrap();
+        assert_eq!(buf.len(), 9);
+        assert_eq!(buf.as_wide(), &"app.exe a".encode_utf16().collect::<Vec<_>>()[..]);
+        unsafe {
+            let ptr = buf.as_mut_ptr();
+            assert_eq!(*ptr.add(buf.len()), 0);
+            for i in 1..=HEADROOM {
+                assert_eq!(*ptr.add(buf.len() + i), 0);
+            }
+        }
+        assert_eq!(buf.reparse().collect::<Vec<_>>(), ["app.exe", "a"]);
+
+        let buf = CmdLineBuffer::new(OsStr::new("")).unwrap();
+        assert!(buf.is_empty());
+        assert_eq!(buf.reparse().collect::<Vec<_>>(), [""]);
+    }
+
+    #[test]
+    fn interior_nul() {
+        let err = CmdLineBuffer::new(OsStr::new("app.exe\0 hidden")).unwrap_err();
+        assert_eq!(err.position(), 7);
+    }
+
+    #[test]
+    fn reparse_sees_modifications() {
+        let mut buf = CmdLineBuffer::new(OsStr::new("app.exe a b")).unwrap();
+        unsafe { *buf.as_mut_ptr().add(9) = 0 };
+        assert_eq!(buf.reparse().collect::<Vec<_>>(), ["app.exe", "a"]);
+    }
+}
@@ -0,0 +1,189 @@
+use std::error::Error;
+use std::fmt;
+
+/// A UTF-16 byte buffer could not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Utf16BytesError {
+    /// The buffer is not a whole number of code units.
+    OddLength { len: usize },
+    /// There is an unpaired surrogate at this byte offset (which includes any BOM).
+    ///
+    /// This is only produced when decoding to `String`s.
+    UnpairedSurrogate { offset: usize },
+}
+
+impl fmt::Display for Utf16BytesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Utf16BytesError::OddLength { len } => {
+                write!(f, "UTF-16 buffer has an odd length of {} bytes", len)
+            },
+            Utf16BytesError::UnpairedSurrogate { offset } => {
+                write!(f, "unpaired surrogate at byte offset {}", offset)
+            },
+        }
+    }
+}
+
+impl Error for Utf16BytesError {}
+
+/// A UTF-8 command line was not valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utf8CmdError {
+    offset: usize,
+}
+
+impl Utf8CmdError {
+    /// The byte offset of the first invalid sequence, counting any BOM.
+    pub fn offset(&self) -> usize { self.offset }
+}
+
+impl fmt::Display for Utf8CmdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid UTF-8 at byte offset {}", self.offset)
+    }
+}
+
+impl Error for Utf8CmdError {}
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Validate UTF-8 bytes, removing a leading BOM.
+pub(crate) fn decode_utf8_bytes(bytes: &[u8]) -> Result<&str, Utf8CmdError> {
+    let (bom_len, content) = match bytes.strip_prefix(UTF8_BOM) {
+        Some(rest) => (UTF8_BOM.len(), rest),
+        None => (0, bytes),
+    };
+    std::str::from_utf8(content).map_err(|e| Utf8CmdError { offset: bom_len + e.valid_up_to() })
+}
+
+/// The byte order of UTF-16 data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+    /// Guess the byte order of UTF-16 data.
+    ///
+    /// A byte order mark is used if present.  Otherwise, this counts the zero bytes in
+    /// each position, as text that is mostly ASCII has a zero high byte in every code unit.
+    /// Ties (such as empty input) are resolved as little-endian, the order used by Windows.
+    pub fn sniff(bytes: &[u8]) -> Endianness {
+        match bytes {
+            [0xff, 0xfe, ..] => return Endianness::Little,
+            [0xfe, 0xff, ..] => return Endianness::Big,
+            _ => {},
+        }
+        let count_zeros = |start| bytes.iter().skip(start).step_by(2).filter(|&&b| b == 0).count();
+        match count_zeros(0) > count_zeros(1) {
+            true => Endianness::Big,
+            false => Endianness::Little,
+        }
+    }
+
+    fn decode(self, pair: [u8; 2]) -> u16 {
+        match self {
+            Endianness::Little => u16::from_le_bytes(pair),
+            Endianness::Big => u16::from_be_bytes(pair),
+        }
+    }
+}
+
+const BOM: u16 = 0xfeff;
+
+/// Decode UTF-16 bytes into code units, removing any BOM.
+///
+/// The bytes are copied, so the buffer need not be aligned.
+pub(crate) fn decode_utf16_bytes(
+    bytes: &[u8],
+    endianness: Endianness,
+    allow_surrogates: bool,
+) -> Result<Vec<u16>, Utf16BytesError> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(Utf16BytesError::OddLength { len: bytes.len() });
+    }
+    let mut wide = Vec::with_capacity(bytes.len() / 2);
+    wide.extend(bytes.chunks_exact(2).map(|pair| endianness.decode([pair[0], pair[1]])));
+
+    let bom_len = match wide.first() {
+        Some(&BOM) => 1,
+        _ => 0,
+    };
+    if !allow_surrogates {
+        let mut units = bom_len;
+        for c in std::char::decode_utf16(wide[bom_len..].iter().cloned()) {
+            match c {
+                Ok(c) => units += c.len_utf16(),
+                Err(_) => return Err(Utf16BytesError::UnpairedSurrogate { offset: 2 * units }),
+            }
+        }
+    }
+    wide.drain(..bom_len);
+    Ok(wide)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_utf16le(bytes: &[u8], allow_surrogates: bool) -> Result<Vec<u16>, Utf16BytesError> {
+        decode_utf16_bytes(bytes, Endianness::Little, allow_surrogates)
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(decode_utf16le(b"", false), Ok(vec![]));
+        assert_eq!(decode_utf16le(b"\xff\xfe", false), Ok(vec![]));
+        assert_eq!(decode_utf16le(b"\xff\xfea\0", false), Ok(vec![0x61]));
+        // only a leading BOM is removed
+        assert_eq!(decode_utf16le(b"a\0\xff\xfe", false), Ok(vec![0x61, BOM]));
+        assert_eq!(decode_utf16le(b"\xfe\xffa\0", false), Ok(vec![0xfffe, 0x61]));
+
+        // unaligned input
+        let buf = b"xa\0b\0";
+        assert_eq!(decode_utf16le(&buf[1..], false), Ok(vec![0x61, 0x62]));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(decode_utf16le(b"a\0b", false), Err(Utf16BytesError::OddLength { len: 3 }));
+        assert_eq!(decode_utf16le(b"\xff", true), Err(Utf16BytesError::OddLength { len: 1 }));
+
+        let lone = b"\xff\xfea\0\x3d\xd8\x05\xde\x00\xd8";
+        assert_eq!(decode_utf16le(lone, false), Err(Utf16BytesError::UnpairedSurrogate { offset: 8 }));
+        assert_eq!(decode_utf16le(lone, true), Ok(vec![0x61, 0xd83d, 0xde05, 0xd800]));
+    }
+
+    #[test]
+    fn utf8() {
+        assert_eq!(decode_utf8_bytes(b""), Ok(""));
+        assert_eq!(decode_utf8_bytes(b"\xef\xbb\xbf"), Ok(""));
+        assert_eq!(decode_utf8_bytes(b"\xef\xbb\xbf\xef\xbb\xbfa"), Ok("\u{feff}a"));
+        assert_eq!(decode_utf8_bytes("a é".as_bytes()), Ok("a é"));
+        assert_eq!(decode_utf8_bytes(b"ab\xc3"), Err(Utf8CmdError { offset: 2 }));
+        assert_eq!(decode_utf8_bytes(b"\xef\xbb\xbfab\xff"), Err(Utf8CmdError { offset: 5 }));
+        // a truncated BOM is just invalid
+        assert_eq!(decode_utf8_bytes(b"\xef\xbba"), Err(Utf8CmdError { offset: 0 }));
+    }
+
+    #[test]
+    fn big_endian() {
+        let decode = |bytes| decode_utf16_bytes(bytes, Endianness::Big, false);
+        assert_eq!(decode(b"\xfe\xff\0a\xd8\x3d\xde\x05"), Ok(vec![0x61, 0xd83d, 0xde05]));
+        assert_eq!(decode(b"\xff\xfe"), Ok(vec![0xfffe]));
+        assert_eq!(decode(b"\0a\xd8\x00"), Err(Utf16BytesError::UnpairedSurrogate { offset: 2 }));
+    }
+
+    #[test]
+    fn sniffing() {
+        assert_eq!(Endianness::sniff(b""), Endianness::Little);
+        assert_eq!(Endianness::sniff(b"\xff\xfe\0a"), Endianness::Little);
+        assert_eq!(Endianness::sniff(b"\xfe\xff\x61\0"), Endianness::Big);
+        assert_eq!(Endianness::sniff(b"a\0b\0"), Endianness::Little);
+        assert_eq!(Endianness::sniff(b"\0a\0b"), Endianness::Big);
+        // a few non-ASCII characters don't throw it off
+        assert_eq!(Endianness::sniff(b"\0a\x30\x42\0b"), Endianness::Big);
+    }
+}
@@ -0,0 +1,170 @@
+//! The transformation that `cmd.exe` applies to a command typed at its prompt, before the
+//! program is started.
+//!
+//! ```
+//! use windows_args::cmd::{self, CmdOptions};
+//!
+//! let line = cmd::parse(r#"app.exe ^"a b^" "x ^& y" & echo done"#, CmdOptions::default());
+//! assert_eq!(line.command, r#"app.exe "a b" "x ^& y" "#);
+//! assert_eq!(line.args, ["app.exe", "a b", "x ^& y"]);
+//! assert_eq!(line.rest.as_deref(), Some("& echo done"));
+//! ```
+//!
+//! Only the rules for an interactive command line are modelled, in this order:
+//!
+//! * `%NAME%` is replaced by the value of the environment variable `NAME`.  A variable that
+//!   is not defined is left as written.
+//! * Outside of double quotes, `^` escapes the character after it (and is removed), and an
+//!   unescaped `&`, `|`, `<` or `>` ends the command.  An escaped quote does not begin or end
+//!   a quoted region.  A `^` at the very end of the line is removed.
+//! * Inside double quotes, `^` and the metacharacters are ordinary characters.
+//!
+//! What follows the end of the command (another command, or a redirection) is not
+//! interpreted.  Batch file semantics, such as `%%` or `%1`, are not modelled either; see
+//! [`batch`](../batch/index.html) for the latter.
+
+use crate::expand::{expand_refs, Piece};
+use crate::Args;
+
+/// Options for [`parse`].
+///
+/// [`parse`]: fn.parse.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CmdOptions {
+    /// Whether to expand `%NAME%` from the environment of the current process.
+    /// Defaults to `true`.
+    pub expand_vars: bool,
+}
+
+impl Default for CmdOptions {
+    fn default() -> Self {
+        CmdOptions { expand_vars: true }
+    }
+}
+
+/// A command as `cmd.exe` would run it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CmdLine {
+    /// The command line that the program receives.
+    pub command: String,
+    /// The arguments of the program, as parsed from `command` by [`Args::parse_cmd`].
+    ///
+    /// [`Args::parse_cmd`]: ../struct.Args.html#method.parse_cmd
+    pub args: Vec<String>,
+    /// Everything from the metacharacter that ended the command onwards, if any.
+    pub rest: Option<String>,
+}
+
+/// Apply the transformations of an interactive `cmd.exe` to a line of input, and split the
+/// resulting command into arguments.
+///
+/// See the [module documentation](index.html) for the rules.
+pub fn parse(input: &str, opts: CmdOptions) -> CmdLine {
+    parse_with(input, opts, |name| std::env::var(name).ok())
+}
+
+fn parse_with(input: &str, opts: CmdOptions, lookup: impl FnMut(&str) -> Option<String>) -> CmdLine {
+    let expanded;
+    let input = match opts.expand_vars {
+        true => {
+            expanded = expand_vars(input, lookup);
+            &expanded[..]
+        },
+        false => input,
+    };
+
+    let mut command = String::with_capacity(input.len());
+    let mut rest = None;
+    let mut in_quotes = false;
+    let mut chars = input.char_indices();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                command.push(c);
+            },
+            '^' if !in_quotes => {
+                // a trailing caret escapes the end of the line, and produces nothing
+                if let Some((_, escaped)) = chars.next() {
+                    command.push(escaped);
+                }
+            },
+            '&' | '|' | '<' | '>' if !in_quotes => {
+                rest = Some(input[pos..].to_string());
+                break;
+            },
+            _ => command.push(c),
+        }
+    }
+
+    let args = Args::parse_cmd(&command).collect();
+    CmdLine { command, args, rest }
+}
+
+/// Expand `%NAME%` references by the same rules as [`expand_env_vars`].
+///
+/// [`expand_env_vars`]: ../fn.expand_env_vars.html
+fn expand_vars(input: &str, mut lookup: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(input.len());
+    // the pieces are split next to `%`, so they are valid slices of the `str`
+    expand_refs(input.as_bytes(), |piece| match piece {
+        Piece::Text(range) => {
+            out.push_str(&input[range]);
+            true
+        },
+        Piece::Var(range) => lookup(&input[range]).map(|value| out.push_str(&value)).is_some(),
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chk(input: &str, command: &str, args: &[&str], rest: Option<&str>) {
+        let vars = |name: &str| match name {
+            "X" => Some("1".to_string()),
+            "AMP" => Some("^&".to_string()),
+            "Q" => Some("\"".to_string()),
+            _ => None,
+        };
+        let line = parse_with(input, CmdOptions::default(), vars);
+        assert_eq!(line.command, command, "{:?}", input);
+        assert_eq!(line.args, args, "{:?}", input);
+        assert_eq!(line.rest.as_deref(), rest, "{:?}", input);
+    }
+
+    #[test]
+    fn carets() {
+        chk(r#"app.exe ^"a b^" c"#, r#"app.exe "a b" c"#, &["app.exe", "a b", "c"], None);
+        chk(r"app.exe ^^x ^a", r"app.exe ^x a", &["app.exe", "^x", "a"], None);
+        chk(r#"app.exe "a ^& ^^b""#, r#"app.exe "a ^& ^^b""#, &["app.exe", "a ^& ^^b"], None);
+        chk(r"app.exe a^", r"app.exe a", &["app.exe", "a"], None);
+        chk(r"app.exe a^ b^^^", r"app.exe a b^", &["app.exe", "a", "b^"], None);
+    }
+
+    #[test]
+    fn metacharacters() {
+        chk(r"app.exe a ^& b | more", r"app.exe a & b ", &["app.exe", "a", "&", "b"], Some("| more"));
+        chk(r"app.exe a>out.txt", r"app.exe a", &["app.exe", "a"], Some(">out.txt"));
+        chk(r"app.exe <in.txt", r"app.exe ", &["app.exe"], Some("<in.txt"));
+        chk(r#"app.exe "a | b" c&&d"#, r#"app.exe "a | b" c"#, &["app.exe", "a | b", "c"], Some("&&d"));
+        // an escaped quote does not protect what follows it
+        chk(r#"app.exe ^"a & b^""#, r#"app.exe "a "#, &["app.exe", "a "], Some(r#"& b^""#));
+        chk("& app.exe", "", &[""], Some("& app.exe"));
+    }
+
+    #[test]
+    fn variables() {
+        chk(r#"app.exe %X% "%X%" %Y% 100%"#, r#"app.exe 1 "1" %Y% 100%"#, &["app.exe", "1", "1", "%Y%", "100%"], None);
+        chk("app.exe %Y%X%", "app.exe %Y1", &["app.exe", "%Y1"], None);
+        chk("app.exe %%X%", "app.exe %1", &["app.exe", "%1"], None);
+        // expansion comes first, so the expanded text is subject to the other rules
+        chk("app.exe %AMP% x", "app.exe & x", &["app.exe", "&", "x"], None);
+        chk("app.exe %Q%a & b%Q%", r#"app.exe "a & b""#, &["app.exe", "a & b"], None);
+
+        let opts = CmdOptions { expand_vars: false };
+        let line = parse_with("app.exe %X%", opts, |_| panic!("lookup with expansion disabled"));
+        assert_eq!(line.args, ["app.exe", "%X%"]);
+    }
+}
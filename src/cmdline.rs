@@ -0,0 +1,189 @@
+#[cfg(windows)]
+use std::ffi::{OsStr, OsString};
+use wtf8::{Wtf8, Wtf8Buf};
+
+use crate::args::{ArgsWtf8, Mode};
+use crate::config::{ParseError, ParserConfig};
+use crate::wildcard::DirLister;
+use crate::wtf8like::IsWtf8Buf;
+#[cfg(test)]
+use crate::Args;
+#[cfg(all(test, windows))]
+use crate::ArgsOs;
+
+/// Types that can be parsed as a complete command line by [`Args::parse`] and
+/// [`ArgsOs::parse`].
+///
+/// This is implemented for references to strings (`str`, `String`), to UTF-16 code units
+/// (`[u16]`, `Vec<u16>`), to [`Wtf8`] and [`Wtf8Buf`], and on Windows to `OsStr` and
+/// `OsString`.  Each is parsed in its own encoding where possible; in particular, UTF-16
+/// input is parsed in place, and only copied into the string kept for [`Args::raw`].
+///
+/// This trait is sealed, and cannot be implemented outside of this crate.
+///
+/// [`Args::parse`]: struct.Args.html#method.parse
+/// [`ArgsOs::parse`]: struct.ArgsOs.html#method.parse
+/// [`Args::raw`]: struct.Args.html#method.raw
+/// [`Wtf8`]: wtf8/struct.Wtf8.html
+/// [`Wtf8Buf`]: wtf8/struct.Wtf8Buf.html
+pub trait AsCmdLine: private::Sealed {}
+
+mod private {
+    use super::*;
+
+    pub trait Sealed {
+        fn cmd_line(&self) -> CmdLine<'_>;
+    }
+
+    /// The input, in whichever encoding it came in.
+    #[derive(Clone, Copy)]
+    pub enum CmdLine<'a> {
+        Str(&'a str),
+        Wide(&'a [u16]),
+        Wtf8(&'a Wtf8),
+        #[cfg(windows)]
+        Os(&'a OsStr),
+    }
+}
+
+pub(crate) use private::CmdLine;
+
+impl<T: private::Sealed> AsCmdLine for T {}
+
+impl CmdLine<'_> {
+    /// Parse the input as a complete command line, in the way that is cheapest for its
+    /// encoding.
+    pub(crate) fn parse_cmd<S: IsWtf8Buf>(self) -> ArgsWtf8<S> {
+        match self {
+            CmdLine::Str(input) => ArgsWtf8::parse_cmd(Wtf8::from_str(input)),
+            CmdLine::Wide(input) => ArgsWtf8::parse_cmd_wide(input),
+            CmdLine::Wtf8(input) => ArgsWtf8::parse_cmd(input),
+            #[cfg(windows)]
+            CmdLine::Os(input) => ArgsWtf8::parse_cmd(input),
+        }
+    }
+
+    /// Like `parse_cmd`, but takes the UTF-8 path for `str`.
+    pub(crate) fn parse_cmd_wtf8(self) -> ArgsWtf8<Wtf8Buf> {
+        match self {
+            CmdLine::Str(input) => ArgsWtf8::parse_cmd_str(input),
+            _ => self.parse_cmd(),
+        }
+    }
+
+    /// The length of the input in bytes: those of UTF-8 (or WTF-8) for strings, and two per
+    /// code unit for UTF-16.
+    fn byte_len(self) -> usize {
+        match self {
+            CmdLine::Str(input) => input.len(),
+            CmdLine::Wide(input) => input.len() * 2,
+            CmdLine::Wtf8(input) => input.len(),
+            #[cfg(windows)]
+            CmdLine::Os(input) => input.len(),
+        }
+    }
+
+    /// Parse the input according to a [`ParserConfig`].
+    ///
+    /// `dirs` lists directories, if the config asks for wildcards to be expanded.
+    pub(crate) fn parse_config<S: IsWtf8Buf>(
+        self,
+        mode: Mode,
+        config: &ParserConfig,
+        dirs: &mut DirLister<'_>,
+    ) -> Result<ArgsWtf8<S>, ParseError> {
+        config.check_input_len(self.byte_len())?;
+        match self {
+            CmdLine::Str(input) => ArgsWtf8::parse_wide_config(&input.encode_utf16().collect::<Vec<_>>(), mode, config, dirs),
+            CmdLine::Wide(input) => ArgsWtf8::parse_wide_config(input, mode, config, dirs),
+            CmdLine::Wtf8(input) => ArgsWtf8::parse_wide_config(&input.to_ill_formed_utf16().collect::<Vec<_>>(), mode, config, dirs),
+            #[cfg(windows)]
+            CmdLine::Os(input) => {
+                use std::os::windows::ffi::OsStrExt;
+                ArgsWtf8::parse_wide_config(&input.encode_wide().collect::<Vec<_>>(), mode, config, dirs)
+            },
+        }
+    }
+
+    /// Like `parse_config`, but takes the UTF-8 path for `str`.
+    pub(crate) fn parse_config_wtf8(
+        self,
+        mode: Mode,
+        config: &ParserConfig,
+        dirs: &mut DirLister<'_>,
+    ) -> Result<ArgsWtf8<Wtf8Buf>, ParseError> {
+        match self {
+            CmdLine::Str(input) => {
+                config.check_input_len(self.byte_len())?;
+                ArgsWtf8::parse_str_config(input, mode, config, dirs)
+            },
+            _ => self.parse_config(mode, config, dirs),
+        }
+    }
+}
+
+impl private::Sealed for &str {
+    fn cmd_line(&self) -> CmdLine<'_> { CmdLine::Str(self) }
+}
+
+impl private::Sealed for &String {
+    fn cmd_line(&self) -> CmdLine<'_> { CmdLine::Str(self) }
+}
+
+impl private::Sealed for &[u16] {
+    fn cmd_line(&self) -> CmdLine<'_> { CmdLine::Wide(self) }
+}
+
+impl private::Sealed for &Vec<u16> {
+    fn cmd_line(&self) -> CmdLine<'_> { CmdLine::Wide(self) }
+}
+
+impl private::Sealed for &Wtf8 {
+    fn cmd_line(&self) -> CmdLine<'_> { CmdLine::Wtf8(self) }
+}
+
+impl private::Sealed for &Wtf8Buf {
+    fn cmd_line(&self) -> CmdLine<'_> { CmdLine::Wtf8(self) }
+}
+
+#[cfg(windows)]
+impl private::Sealed for &OsStr {
+    fn cmd_line(&self) -> CmdLine<'_> { CmdLine::Os(self) }
+}
+
+#[cfg(windows)]
+impl private::Sealed for &OsString {
+    fn cmd_line(&self) -> CmdLine<'_> { CmdLine::Os(self) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_input_type_agrees() {
+        for &input in &["", "app.exe", r#""C:\a b\app.exe" x "y \" z" \\"#, "é 😅\0hidden"] {
+            let expected = Args::parse_cmd(input).collect::<Vec<_>>();
+            let wide = input.encode_utf16().collect::<Vec<u16>>();
+            let wtf8 = Wtf8Buf::from_str(input);
+
+            assert_eq!(Args::parse(input).collect::<Vec<_>>(), expected);
+            assert_eq!(Args::parse(&input.to_string()).collect::<Vec<_>>(), expected);
+            assert_eq!(Args::parse(&wide[..]).collect::<Vec<_>>(), expected);
+            assert_eq!(Args::parse(&wide).collect::<Vec<_>>(), expected);
+            assert_eq!(Args::parse(&*wtf8).collect::<Vec<_>>(), expected);
+            assert_eq!(Args::parse(&wtf8).collect::<Vec<_>>(), expected);
+
+            #[cfg(windows)]
+            {
+                let expected = expected.iter().map(OsString::from).collect::<Vec<_>>();
+                let os = OsString::from(input);
+                assert_eq!(ArgsOs::parse(input).collect::<Vec<_>>(), expected);
+                assert_eq!(ArgsOs::parse(&wide).collect::<Vec<_>>(), expected);
+                assert_eq!(ArgsOs::parse(&*wtf8).collect::<Vec<_>>(), expected);
+                assert_eq!(ArgsOs::parse(os.as_os_str()).collect::<Vec<_>>(), expected);
+                assert_eq!(ArgsOs::parse(&os).collect::<Vec<_>>(), expected);
+            }
+        }
+    }
+}
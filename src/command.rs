@@ -0,0 +1,172 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::{ParseError, ParserConfig};
+use crate::nul::check_no_nul_in;
+use crate::Args;
+
+/// A complete command line, split into the executable name and its arguments.
+///
+/// This is mostly useful through its [`FromStr`] impl, which parses like [`Args::parse_cmd`],
+/// but strictly:
+///
+/// ```
+/// use windows_args::{Command, FromStrError};
+///
+/// let command: Command = r#""C:\Program Files\app.exe" -v "a b""#.parse()?;
+/// assert_eq!(command.program(), r"C:\Program Files\app.exe");
+/// assert_eq!(command.args().iter().collect::<Vec<_>>(), ["-v", "a b"]);
+///
+/// assert_eq!("".parse::<Command>(), Err(FromStrError::Empty));
+/// assert_eq!(r#"app.exe "a"#.parse::<Command>(), Err(FromStrError::UnterminatedQuote { offset: 8 }));
+/// # Ok::<(), FromStrError>(())
+/// ```
+///
+/// [`FromStr`]: #impl-FromStr-for-Command
+/// [`Args::parse_cmd`]: struct.Args.html#method.parse_cmd
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Command {
+    program: String,
+    args: Args,
+}
+
+impl Command {
+    /// The executable name.
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// The arguments that follow the executable name.
+    pub fn args(&self) -> &Args {
+        &self.args
+    }
+
+    /// Take the executable name and the arguments.
+    pub fn into_parts(self) -> (String, Args) {
+        (self.program, self.args)
+    }
+}
+
+/// Parse a complete command line, like [`Args::parse_cmd`].
+///
+/// Unlike that function, this fails on input that is empty (or contains nothing but spaces
+/// and tabs) instead of inventing a placeholder executable name, on input that contains a
+/// NUL, and on input that ends inside a quoted region.
+///
+/// [`Args::parse_cmd`]: struct.Args.html#method.parse_cmd
+impl FromStr for Command {
+    type Err = FromStrError;
+
+    fn from_str(input: &str) -> Result<Command, FromStrError> {
+        check_no_nul_in(input.encode_utf16()).map_err(|err| FromStrError::InteriorNul { offset: err.position() })?;
+        if input.bytes().all(|c| c == b' ' || c == b'\t') {
+            return Err(FromStrError::Empty);
+        }
+        let mut args = ParserConfig::new().strict_quotes(true).parse_cmd(input).map_err(FromStrError::from_parse)?;
+        let program = args.next().expect("a command line always has an executable name");
+        Ok(Command { program, args })
+    }
+}
+
+/// Parse whitespace-separated arguments, like [`Args::parse_args`].
+///
+/// Unlike that function, this fails on input that contains a NUL, and on input that ends
+/// inside a quoted region.  Empty input gives no arguments.
+///
+/// ```
+/// use windows_args::Args;
+///
+/// let args: Args = r#"foo "a b""#.parse()?;
+/// assert_eq!(args.collect::<Vec<_>>(), ["foo", "a b"]);
+/// # Ok::<(), windows_args::FromStrError>(())
+/// ```
+///
+/// [`Args::parse_args`]: struct.Args.html#method.parse_args
+impl FromStr for Args {
+    type Err = FromStrError;
+
+    fn from_str(input: &str) -> Result<Args, FromStrError> {
+        check_no_nul_in(input.encode_utf16()).map_err(|err| FromStrError::InteriorNul { offset: err.position() })?;
+        ParserConfig::new().strict_quotes(true).parse_args(input).map_err(FromStrError::from_parse)
+    }
+}
+
+/// A string could not be parsed into [`Args`] or a [`Command`].
+///
+/// Offsets are in UTF-16 code units, as elsewhere in this crate.
+///
+/// [`Args`]: struct.Args.html
+/// [`Command`]: struct.Command.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FromStrError {
+    /// The input contains a NUL at this offset.
+    InteriorNul { offset: usize },
+    /// The quote at this offset is never closed.
+    UnterminatedQuote { offset: usize },
+    /// The input has no executable name.  This is only produced for a [`Command`].
+    ///
+    /// [`Command`]: struct.Command.html
+    Empty,
+}
+
+impl FromStrError {
+    fn from_parse(err: ParseError) -> FromStrError {
+        match err {
+            ParseError::UnterminatedQuote { offset } => FromStrError::UnterminatedQuote { offset },
+            ParseError::LimitExceeded { .. } => unreachable!("no limits were set"),
+        }
+    }
+}
+
+impl fmt::Display for FromStrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FromStrError::InteriorNul { offset } => write!(f, "command line contains a NUL at offset {}", offset),
+            FromStrError::UnterminatedQuote { offset } => write!(f, "quote at offset {} is never closed", offset),
+            FromStrError::Empty => write!(f, "command line is empty"),
+        }
+    }
+}
+
+impl Error for FromStrError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args() {
+        assert_eq!("".parse::<Args>().unwrap().len(), 0);
+        assert_eq!(r#"a  "b c" """#.parse::<Args>().unwrap().collect::<Vec<_>>(), ["a", "b c", ""]);
+        assert_eq!("a\0b".parse::<Args>(), Err(FromStrError::InteriorNul { offset: 1 }));
+        assert_eq!(r#"a "b"#.parse::<Args>(), Err(FromStrError::UnterminatedQuote { offset: 2 }));
+        // offsets are in UTF-16
+        assert_eq!("\u{1F600} \"".parse::<Args>(), Err(FromStrError::UnterminatedQuote { offset: 3 }));
+        assert_eq!("é\0".parse::<Args>(), Err(FromStrError::InteriorNul { offset: 1 }));
+    }
+
+    #[test]
+    fn command() {
+        let command = r#"app.exe  a "b c""#.parse::<Command>().unwrap();
+        assert_eq!(command.program(), "app.exe");
+        assert_eq!(command.args().raw(), Some(r#"app.exe  a "b c""#));
+        assert_eq!(command.args().rest_raw(), Some(r#"a "b c""#));
+        let (program, args) = command.into_parts();
+        assert_eq!(program, "app.exe");
+        assert_eq!(args.collect::<Vec<_>>(), ["a", "b c"]);
+
+        assert_eq!(" \t ".parse::<Command>(), Err(FromStrError::Empty));
+        assert_eq!("\0".parse::<Command>(), Err(FromStrError::InteriorNul { offset: 0 }));
+        // leading whitespace gives an empty name, as in `Args::parse_cmd`
+        assert_eq!(" a".parse::<Command>().unwrap().program(), "");
+        assert_eq!(r#""app.exe"#.parse::<Command>(), Err(FromStrError::UnterminatedQuote { offset: 0 }));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(FromStrError::InteriorNul { offset: 3 }.to_string(), "command line contains a NUL at offset 3");
+        assert_eq!(FromStrError::UnterminatedQuote { offset: 0 }.to_string(), "quote at offset 0 is never closed");
+        assert_eq!(FromStrError::Empty.to_string(), "command line is empty");
+    }
+}
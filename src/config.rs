@@ -0,0 +1,831 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use crate::args::{self, Alloc, CodeUnit, Mode};
+use crate::cmdline::AsCmdLine;
+use crate::crt;
+use crate::rust_std;
+use crate::wildcard::{self, DirLister};
+use crate::wtf8like::IsWtf8Buf;
+#[cfg(windows)]
+use crate::DetectedQuirks;
+use crate::Args;
+#[cfg(windows)]
+use crate::ArgsOs;
+
+/// Options that change how command lines are parsed.
+///
+/// The default configuration parses exactly like [`Args::parse_cmd`] and [`Args::parse_args`].
+/// Options are set with builder methods:
+///
+/// ```
+/// use windows_args::ParserConfig;
+///
+/// let config = ParserConfig::new().placeholder_exe("app.exe").strict_quotes(true);
+/// assert_eq!(config.parse_cmd("")?.collect::<Vec<_>>(), ["app.exe"]);
+/// assert!(config.parse_cmd(r#"app.exe "unterminated"#).is_err());
+/// # Ok::<(), windows_args::ParseError>(())
+/// ```
+///
+/// [`Args::parse_cmd`]: struct.Args.html#method.parse_cmd
+/// [`Args::parse_args`]: struct.Args.html#method.parse_args
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct ParserConfig {
+    placeholder_exe: Option<String>,
+    #[cfg(windows)]
+    current_exe_for_empty: bool,
+    strict_quotes: bool,
+    compat: Compat,
+    delimiters: Option<DelimiterPolicy>,
+    trailing_empty: TrailingEmpty,
+    expand_wildcards: bool,
+    max_args: Option<usize>,
+    max_input_len: Option<usize>,
+    discard_raw: bool,
+}
+
+/// The program whose splitting rules to follow, selected with [`ParserConfig::compat`].
+///
+/// Different programs disagree about what arguments a command line contains, mostly in how
+/// they treat `""` inside a quoted region and in how they find the end of the executable name.
+/// Which rules apply depends on the program that *receives* the command line.
+///
+/// [`ParserConfig::compat`]: struct.ParserConfig.html#method.compat
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compat {
+    /// `CommandLineToArgvW` from shell32.dll.  This is what the rest of this crate implements.
+    #[default]
+    Shell32,
+    /// The Microsoft C runtime since Visual C++ 2008, including the Universal CRT.  This
+    /// decides the arguments of `main` and `wmain` in most programs written in C or C++.
+    ///
+    /// Inside a quoted region, `""` produces a quote and the region continues (so
+    /// `"a"" b"` is the single argument `a" b`).  Quotes anywhere in the executable name
+    /// protect spaces, and only a space or tab ends it.
+    CrtModern,
+    /// The Microsoft C runtime up to Visual C++ 2005, for programs linked against an older
+    /// `msvcrt`.
+    ///
+    /// Inside a quoted region, `""` produces a quote and *ends* the region (so `"a"" b"` is
+    /// the two arguments `a"` and `b`).  The executable name is found as by `Shell32`.
+    ///
+    /// Although the CRT states these rules differently, they produce the same arguments as
+    /// `Shell32` for every input, so this is parsed by the same code.
+    CrtLegacy,
+    /// The parser behind `std::env::args` in current versions of the Rust standard library.
+    ///
+    /// This was rewritten to follow the modern C runtime, and it differs from `Shell32` in the
+    /// same places as `CrtModern`: `""` inside a quoted region produces a quote without ending
+    /// the region, quotes anywhere in the executable name protect spaces, and only a space or
+    /// tab ends the executable name.  For instance, `"C:\a b"c d` is `C:\a bc` and `d`, where
+    /// `Shell32` gives `C:\a b`, `c` and `d`.
+    ///
+    /// It is implemented separately, so that it can follow std if the two ever part ways.
+    /// For empty input, std produces the name of the current executable; see
+    /// [`ParserConfig::current_exe_for_empty`].
+    ///
+    /// [`ParserConfig::current_exe_for_empty`]: struct.ParserConfig.html#method.current_exe_for_empty
+    RustStd,
+}
+
+/// The characters that end an unquoted executable name, selected with
+/// [`ParserConfig::delimiters`].
+///
+/// After the executable name, arguments are always separated by spaces and tabs.
+///
+/// [`ParserConfig::delimiters`]: struct.ParserConfig.html#method.delimiters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DelimiterPolicy {
+    /// Any ASCII control character or space, as in `CommandLineToArgvW`.
+    ControlPlane,
+    /// Only a space or tab.  Other control characters are part of the name.
+    SpaceTabOnly,
+}
+
+/// What becomes of an empty argument at the end of the input, selected with
+/// [`ParserConfig::trailing_empty`].
+///
+/// Such an argument can only be written with quotes, as in `app.exe ""`, or with a quote that
+/// is never closed, as in `app.exe "`.  An empty argument elsewhere (as in `app.exe "" x`) is
+/// always kept, and so is an empty executable name.
+///
+/// [`ParserConfig::trailing_empty`]: struct.ParserConfig.html#method.trailing_empty
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrailingEmpty {
+    /// Keep it.  This is what `CommandLineToArgvW` does, and every [`Compat`] mode agrees:
+    /// the C runtime (of any version) also begins an argument at the first character that is
+    /// not a space or tab, whether or not the argument turns out to be empty.
+    ///
+    /// [`Compat`]: enum.Compat.html
+    #[default]
+    Keep,
+    /// Drop it if the input ended inside a quoted region (`app.exe "`), but keep it if the
+    /// quotes were closed (`app.exe ""`).  This treats an unterminated quote as a typo.
+    DropUnterminated,
+    /// Drop it whenever it is empty.  None of the Microsoft parsers do this; it is offered for
+    /// comparison with tokenizers that never produce an empty argument at the end of a line.
+    Drop,
+}
+
+impl Compat {
+    fn default_delimiters(self) -> DelimiterPolicy {
+        match self {
+            Compat::CrtModern | Compat::RustStd => DelimiterPolicy::SpaceTabOnly,
+            _ => DelimiterPolicy::ControlPlane,
+        }
+    }
+}
+
+impl DelimiterPolicy {
+    pub(crate) fn ends_exe(self, class: u32) -> bool {
+        match self {
+            DelimiterPolicy::ControlPlane => class <= ' ' as u32,
+            DelimiterPolicy::SpaceTabOnly => class == ' ' as u32 || class == '\t' as u32,
+        }
+    }
+}
+
+impl ParserConfig {
+    /// The default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use this executable name when parsing an empty command line.
+    ///
+    /// By default, that produces a single empty argument.  (`CommandLineToArgvW` would instead
+    /// produce the path of the current executable.)  A command line that merely begins with
+    /// whitespace is not affected.
+    pub fn placeholder_exe(mut self, name: &str) -> Self {
+        self.placeholder_exe = Some(name.to_string());
+        self
+    }
+
+    /// **Windows only.**
+    /// Whether to produce the path of the current executable when parsing an empty command
+    /// line, exactly as `CommandLineToArgvW` does.
+    ///
+    /// The path is obtained from `GetModuleFileNameW`.  If that fails, the
+    /// [`placeholder_exe`] (or an empty argument) is used instead.
+    ///
+    /// ```
+    /// use windows_args::ParserConfig;
+    ///
+    /// let config = ParserConfig::new().current_exe_for_empty(true);
+    /// let args = config.parse_cmd_os("")?.collect::<Vec<_>>();
+    /// assert_eq!(args, [std::env::current_exe().unwrap().into_os_string()]);
+    /// # Ok::<(), windows_args::ParseError>(())
+    /// ```
+    ///
+    /// [`placeholder_exe`]: #method.placeholder_exe
+    #[cfg(windows)]
+    pub fn current_exe_for_empty(mut self, enable: bool) -> Self {
+        self.current_exe_for_empty = enable;
+        self
+    }
+
+    /// **Windows only.**
+    /// A configuration that splits like the `CommandLineToArgvW` of the running system.
+    ///
+    /// The system is probed once, on the first call (or the first call to
+    /// [`current_windows_quirks`]), by splitting a handful of command lines on which the known
+    /// quirks differ.  If its behavior matches none of them, or `shell32.dll` cannot be
+    /// loaded, this is the same as [`ParserConfig::new`].
+    ///
+    /// [`current_windows_quirks`]: #method.current_windows_quirks
+    /// [`ParserConfig::new`]: #method.new
+    #[cfg(windows)]
+    pub fn for_current_windows() -> Self {
+        match Self::current_windows_quirks() {
+            DetectedQuirks::Matched { compat, delimiters } => ParserConfig::new().compat(compat).delimiters(delimiters),
+            DetectedQuirks::Unrecognized | DetectedQuirks::Unavailable => ParserConfig::new(),
+        }
+    }
+
+    /// **Windows only.**
+    /// What [`for_current_windows`] found about the running system.
+    ///
+    /// ```
+    /// use windows_args::{DetectedQuirks, ParserConfig};
+    ///
+    /// println!("{:?}", ParserConfig::current_windows_quirks());
+    /// if let DetectedQuirks::Matched { compat, delimiters } = ParserConfig::current_windows_quirks() {
+    ///     let expected = ParserConfig::new().compat(compat).delimiters(delimiters);
+    ///     assert_eq!(ParserConfig::for_current_windows(), expected);
+    /// }
+    /// ```
+    ///
+    /// [`for_current_windows`]: #method.for_current_windows
+    #[cfg(windows)]
+    pub fn current_windows_quirks() -> DetectedQuirks {
+        crate::detect::current_quirks()
+    }
+
+    /// Whether to fail when the input ends inside a quoted region.
+    ///
+    /// By default, a missing closing quote is silently supplied.
+    pub fn strict_quotes(mut self, strict: bool) -> Self {
+        self.strict_quotes = strict;
+        self
+    }
+
+    /// Follow the rules of a program other than `CommandLineToArgvW`.
+    ///
+    /// ```
+    /// use windows_args::{Compat, ParserConfig};
+    ///
+    /// let input = r#"app.exe "a"" b""#;
+    /// let shell32 = ParserConfig::new().parse_cmd(input)?;
+    /// assert_eq!(shell32.collect::<Vec<_>>(), ["app.exe", "a\"", "b"]);
+    /// let crt = ParserConfig::new().compat(Compat::CrtModern).parse_cmd(input)?;
+    /// assert_eq!(crt.collect::<Vec<_>>(), ["app.exe", "a\" b"]);
+    /// # Ok::<(), windows_args::ParseError>(())
+    /// ```
+    pub fn compat(mut self, compat: Compat) -> Self {
+        self.compat = compat;
+        self
+    }
+
+    /// Choose the characters that end an unquoted executable name.
+    ///
+    /// By default, this follows the [`Compat`] mode: [`DelimiterPolicy::SpaceTabOnly`] for
+    /// `CrtModern` and `RustStd`, and [`DelimiterPolicy::ControlPlane`] otherwise.
+    ///
+    /// ```
+    /// use windows_args::{DelimiterPolicy, ParserConfig};
+    ///
+    /// let input = "app.exe\x01x y";
+    /// assert_eq!(ParserConfig::new().parse_cmd(input)?.collect::<Vec<_>>(), ["app.exe", "x", "y"]);
+    /// let config = ParserConfig::new().delimiters(DelimiterPolicy::SpaceTabOnly);
+    /// assert_eq!(config.parse_cmd(input)?.collect::<Vec<_>>(), ["app.exe\x01x", "y"]);
+    /// # Ok::<(), windows_args::ParseError>(())
+    /// ```
+    ///
+    /// [`Compat`]: enum.Compat.html
+    /// [`DelimiterPolicy::SpaceTabOnly`]: enum.DelimiterPolicy.html#variant.SpaceTabOnly
+    /// [`DelimiterPolicy::ControlPlane`]: enum.DelimiterPolicy.html#variant.ControlPlane
+    pub fn delimiters(mut self, delimiters: DelimiterPolicy) -> Self {
+        self.delimiters = Some(delimiters);
+        self
+    }
+
+    /// Choose what becomes of an empty argument at the end of the input.
+    ///
+    /// By default, it is kept, as in every [`Compat`] mode.
+    ///
+    /// ```
+    /// use windows_args::{ParserConfig, TrailingEmpty};
+    ///
+    /// let parse = |trailing, input| -> Vec<String> {
+    ///     ParserConfig::new().trailing_empty(trailing).parse_cmd(input).unwrap().collect()
+    /// };
+    /// assert_eq!(parse(TrailingEmpty::Keep, r#"app.exe ""#), ["app.exe", ""]);
+    /// assert_eq!(parse(TrailingEmpty::DropUnterminated, r#"app.exe ""#), ["app.exe"]);
+    /// assert_eq!(parse(TrailingEmpty::DropUnterminated, r#"app.exe """#), ["app.exe", ""]);
+    /// assert_eq!(parse(TrailingEmpty::Drop, r#"app.exe """#), ["app.exe"]);
+    /// ```
+    ///
+    /// [`Compat`]: enum.Compat.html
+    pub fn trailing_empty(mut self, trailing: TrailingEmpty) -> Self {
+        self.trailing_empty = trailing;
+        self
+    }
+
+    /// Expand wildcards in the arguments, as a program linked with `setargv.obj` does.
+    ///
+    /// Each argument (other than the executable name) whose last path component contains `*`
+    /// or `?` is replaced by the paths of the matching files, in the order that the directory
+    /// lists them.  As in the CRT:
+    ///
+    /// * An argument with a quote anywhere in its text (even an escaped one) is never
+    ///   expanded.
+    /// * A pattern that matches nothing is left as it is.
+    /// * Wildcards are only supported in the last component, so a pattern like `src\*\x.rs`
+    ///   is left as it is.
+    /// * Matching is case-insensitive, and `.` and `..` never match.
+    ///
+    /// [`ParserConfig::parse_cmd`] expands against the real filesystem.  Use
+    /// [`ParserConfig::parse_cmd_with_dirs`] to supply the directory listings instead.
+    ///
+    /// [`ParserConfig::parse_cmd`]: #method.parse_cmd
+    /// [`ParserConfig::parse_cmd_with_dirs`]: #method.parse_cmd_with_dirs
+    pub fn expand_wildcards(mut self, enable: bool) -> Self {
+        self.expand_wildcards = enable;
+        self
+    }
+
+    /// Fail with [`ParseError::LimitExceeded`] if the input has more than `limit` arguments.
+    ///
+    /// The count is checked as each argument is found, so parsing stops as soon as the limit
+    /// is exceeded, and no more than `limit` arguments are ever stored.  (With
+    /// [`ParserConfig::expand_wildcards`], the matches of one pattern may briefly exceed it.)
+    /// By default, there is no limit.
+    ///
+    /// ```
+    /// use windows_args::{LimitKind, ParseError, ParserConfig};
+    ///
+    /// let config = ParserConfig::new().max_args(3);
+    /// assert!(config.parse_cmd("app.exe a b").is_ok());
+    /// assert_eq!(
+    ///     config.parse_cmd("app.exe a b c").unwrap_err(),
+    ///     ParseError::LimitExceeded { kind: LimitKind::Args, limit: 3 },
+    /// );
+    /// ```
+    ///
+    /// [`ParseError::LimitExceeded`]: enum.ParseError.html#variant.LimitExceeded
+    /// [`ParserConfig::expand_wildcards`]: #method.expand_wildcards
+    pub fn max_args(mut self, limit: usize) -> Self {
+        self.max_args = Some(limit);
+        self
+    }
+
+    /// Fail with [`ParseError::LimitExceeded`] if the input is longer than `limit` bytes.
+    ///
+    /// The length is checked before anything else is done.  It is that of the whole input as
+    /// given (including anything after a NUL), in UTF-8 for a `str`, or at two bytes per code
+    /// unit for UTF-16.  By default, there is no limit.
+    ///
+    /// Together with [`ParserConfig::max_args`], this bounds the memory used to parse untrusted
+    /// input.
+    ///
+    /// [`ParseError::LimitExceeded`]: enum.ParseError.html#variant.LimitExceeded
+    /// [`ParserConfig::max_args`]: #method.max_args
+    pub fn max_input_len(mut self, limit: usize) -> Self {
+        self.max_input_len = Some(limit);
+        self
+    }
+
+    /// Whether the parsed arguments keep a copy of the input, for [`Args::raw`].
+    ///
+    /// This is on by default, and costs one copy of the input (up to the first NUL) for as
+    /// long as the arguments are alive.  With `false`, `raw` returns `None`.
+    ///
+    /// ```
+    /// use windows_args::ParserConfig;
+    ///
+    /// let args = ParserConfig::new().parse_cmd(r#"app.exe "a b""#)?;
+    /// assert_eq!(args.raw(), Some(r#"app.exe "a b""#));
+    /// let args = ParserConfig::new().retain_raw(false).parse_cmd(r#"app.exe "a b""#)?;
+    /// assert_eq!(args.raw(), None);
+    /// # Ok::<(), windows_args::ParseError>(())
+    /// ```
+    ///
+    /// [`Args::raw`]: struct.Args.html#method.raw
+    pub fn retain_raw(mut self, retain: bool) -> Self {
+        self.discard_raw = !retain;
+        self
+    }
+
+    /// Parse a complete command line, like [`Args::parse`].
+    ///
+    /// [`Args::parse`]: struct.Args.html#method.parse
+    pub fn parse_cmd(&self, input: impl AsCmdLine) -> Result<Args, ParseError> {
+        self.parse_cmd_with_dirs(input, wildcard::read_dir)
+    }
+
+    /// Parse arguments to an executable, like [`Args::parse_args`].
+    ///
+    /// [`Args::parse_args`]: struct.Args.html#method.parse_args
+    pub fn parse_args(&self, input: impl AsCmdLine) -> Result<Args, ParseError> {
+        self.parse_args_with_dirs(input, wildcard::read_dir)
+    }
+
+    /// Like [`ParserConfig::parse_cmd`], but with the directory listings used by
+    /// [`ParserConfig::expand_wildcards`] supplied by `dirs`.
+    ///
+    /// `dirs` is given a directory as written in the argument (for instance `src\` or `C:`,
+    /// or the empty string for the current directory) and returns the names of its entries,
+    /// or nothing if it does not exist.
+    ///
+    /// ```
+    /// use windows_args::ParserConfig;
+    ///
+    /// let dirs = |dir: &str| match dir {
+    ///     "" => vec!["b.txt".to_string(), "a.TXT".to_string(), "c.rs".to_string()],
+    ///     _ => vec![],
+    /// };
+    /// let config = ParserConfig::new().expand_wildcards(true);
+    /// let args = config.parse_cmd_with_dirs(r#"app.exe *.txt "*.txt" *.md"#, dirs)?;
+    /// assert_eq!(args.collect::<Vec<_>>(), ["app.exe", "b.txt", "a.TXT", "*.txt", "*.md"]);
+    /// # Ok::<(), windows_args::ParseError>(())
+    /// ```
+    ///
+    /// [`ParserConfig::parse_cmd`]: #method.parse_cmd
+    /// [`ParserConfig::expand_wildcards`]: #method.expand_wildcards
+    pub fn parse_cmd_with_dirs(
+        &self,
+        input: impl AsCmdLine,
+        mut dirs: impl FnMut(&str) -> Vec<String>,
+    ) -> Result<Args, ParseError> {
+        let inner = input.cmd_line().parse_config_wtf8(Mode::Cmd, self, &mut dirs)?;
+        Ok(Args { inner })
+    }
+
+    /// Like [`ParserConfig::parse_args`], but with directory listings supplied by `dirs`.
+    ///
+    /// See [`ParserConfig::parse_cmd_with_dirs`].
+    ///
+    /// [`ParserConfig::parse_args`]: #method.parse_args
+    /// [`ParserConfig::parse_cmd_with_dirs`]: #method.parse_cmd_with_dirs
+    pub fn parse_args_with_dirs(
+        &self,
+        input: impl AsCmdLine,
+        mut dirs: impl FnMut(&str) -> Vec<String>,
+    ) -> Result<Args, ParseError> {
+        let inner = input.cmd_line().parse_config_wtf8(Mode::Args, self, &mut dirs)?;
+        Ok(Args { inner })
+    }
+
+    /// **Windows only.**
+    /// Like [`ParserConfig::parse_cmd`], but produces an [`ArgsOs`].
+    ///
+    /// [`ParserConfig::parse_cmd`]: struct.ParserConfig.html#method.parse_cmd
+    /// [`ArgsOs`]: struct.ArgsOs.html
+    #[cfg(windows)]
+    pub fn parse_cmd_os(&self, input: impl AsCmdLine) -> Result<ArgsOs, ParseError> {
+        let inner = input.cmd_line().parse_config(Mode::Cmd, self, &mut wildcard::read_dir)?;
+        Ok(ArgsOs { inner })
+    }
+
+    /// **Windows only.**
+    /// Like [`ParserConfig::parse_args`], but produces an [`ArgsOs`].
+    ///
+    /// [`ParserConfig::parse_args`]: struct.ParserConfig.html#method.parse_args
+    /// [`ArgsOs`]: struct.ArgsOs.html
+    #[cfg(windows)]
+    pub fn parse_args_os(&self, input: impl AsCmdLine) -> Result<ArgsOs, ParseError> {
+        let inner = input.cmd_line().parse_config(Mode::Args, self, &mut wildcard::read_dir)?;
+        Ok(ArgsOs { inner })
+    }
+
+    /// Run the parser selected by the options.  If the input ended inside a quoted region,
+    /// returns the offset of the quote that opened it.
+    pub(crate) fn parse_units<A: Alloc, U: CodeUnit>(
+        &self,
+        units: impl IntoIterator<Item=U>,
+        mode: Mode,
+        cur: &mut Vec<U>,
+        emit: impl FnMut(&[U], Range<usize>) -> Result<(), A::Error>,
+    ) -> Result<Option<usize>, A::Error> {
+        let delimiters = self.delimiters.unwrap_or_else(|| self.compat.default_delimiters());
+        match (self.compat, mode) {
+            (Compat::Shell32, Mode::Cmd) | (Compat::CrtLegacy, Mode::Cmd) => {
+                args::parse_cmd_units_with::<A, U>(units, delimiters, cur, emit)
+            },
+            (Compat::Shell32, Mode::Args) | (Compat::CrtLegacy, Mode::Args) => mode.parse::<A, U>(units, cur, emit),
+            (Compat::CrtModern, _) => crt::parse_crt::<A, U>(units, mode, delimiters, cur, emit),
+            (Compat::RustStd, _) => rust_std::parse_rust_std::<A, U>(units, mode, delimiters, cur, emit),
+        }
+    }
+
+    pub(crate) fn retains_raw(&self) -> bool {
+        !self.discard_raw
+    }
+
+    pub(crate) fn check_input_len(&self, len: usize) -> Result<(), ParseError> {
+        match self.max_input_len {
+            Some(limit) if len > limit => Err(ParseError::LimitExceeded { kind: LimitKind::InputLen, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the number of arguments found so far.
+    pub(crate) fn check_arg_count(&self, count: usize) -> Result<(), ParseError> {
+        match self.max_args {
+            Some(limit) if count > limit => Err(ParseError::LimitExceeded { kind: LimitKind::Args, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The expansion of an argument under [`ParserConfig::expand_wildcards`], if it is to be
+    /// expanded.
+    ///
+    /// `first` is whether this is the first argument, and `quoted` is whether its text in the
+    /// input contains a quote.
+    pub(crate) fn expand_wildcards_in(
+        &self,
+        first: bool,
+        mode: Mode,
+        quoted: bool,
+        arg: impl FnOnce() -> Option<String>,
+        dirs: &mut DirLister<'_>,
+    ) -> Option<Vec<String>> {
+        if !self.expand_wildcards || quoted || (first && mode == Mode::Cmd) {
+            return None;
+        }
+        wildcard::expand(&arg()?, dirs)
+    }
+
+    /// Apply the options to the output of the parser.
+    ///
+    /// `empty` is whether the input was empty (up to the first NUL), `open_quote` is the
+    /// offset of the unclosed quote (in UTF-16 code units) if it ended inside a quoted region,
+    /// and `last_empty` is whether the last argument is empty.
+    pub(crate) fn finish<S: IsWtf8Buf>(
+        &self,
+        mut args: Vec<S>,
+        mode: Mode,
+        empty: bool,
+        open_quote: Option<usize>,
+        last_empty: bool,
+    ) -> Result<Vec<S>, ParseError> {
+        if let (true, Some(offset)) = (self.strict_quotes, open_quote) {
+            return Err(ParseError::UnterminatedQuote { offset });
+        }
+        let drop_last = match self.trailing_empty {
+            TrailingEmpty::Keep => false,
+            TrailingEmpty::DropUnterminated => last_empty && open_quote.is_some(),
+            TrailingEmpty::Drop => last_empty,
+        };
+        // the executable name is never dropped
+        if drop_last && !(mode == Mode::Cmd && args.len() == 1) {
+            args.pop();
+        }
+        if let (Mode::Cmd, true) = (mode, empty) {
+            #[cfg(windows)]
+            if self.current_exe_for_empty {
+                if let Some(exe) = crate::process::module_file_name() {
+                    args[0] = S::from_wide(&exe);
+                    return Ok(args);
+                }
+            }
+            if let Some(name) = &self.placeholder_exe {
+                args[0] = S::from_wide(&name.encode_utf16().collect::<Vec<_>>());
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// A command line was rejected under a [`ParserConfig`].
+///
+/// [`ParserConfig`]: struct.ParserConfig.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input ended inside a quoted region, and [`ParserConfig::strict_quotes`] is set.
+    ///
+    /// `offset` is the position of the quote that opened the region, in UTF-16 code units.
+    ///
+    /// [`ParserConfig::strict_quotes`]: struct.ParserConfig.html#method.strict_quotes
+    UnterminatedQuote { offset: usize },
+    /// The input exceeds a limit set by [`ParserConfig::max_args`] or
+    /// [`ParserConfig::max_input_len`].
+    ///
+    /// [`ParserConfig::max_args`]: struct.ParserConfig.html#method.max_args
+    /// [`ParserConfig::max_input_len`]: struct.ParserConfig.html#method.max_input_len
+    LimitExceeded { kind: LimitKind, limit: usize },
+}
+
+/// Which limit was exceeded, in a [`ParseError::LimitExceeded`].
+///
+/// [`ParseError::LimitExceeded`]: enum.ParseError.html#variant.LimitExceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+    /// The number of arguments, set by [`ParserConfig::max_args`].
+    ///
+    /// [`ParserConfig::max_args`]: struct.ParserConfig.html#method.max_args
+    Args,
+    /// The length of the input in bytes, set by [`ParserConfig::max_input_len`].
+    ///
+    /// [`ParserConfig::max_input_len`]: struct.ParserConfig.html#method.max_input_len
+    InputLen,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote { offset } => {
+                write!(f, "quote at offset {} is never closed", offset)
+            },
+            ParseError::LimitExceeded { kind: LimitKind::Args, limit } => {
+                write!(f, "command line has more than {} arguments", limit)
+            },
+            ParseError::LimitExceeded { kind: LimitKind::InputLen, limit } => {
+                write!(f, "command line is longer than {} bytes", limit)
+            },
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::short_strings::short_strs;
+
+    fn cmd(config: &ParserConfig, input: &str) -> Result<Vec<String>, ParseError> {
+        let args = config.parse_cmd(input).map(Iterator::collect::<Vec<_>>);
+        let wide = input.encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(config.parse_cmd(&wide).map(Iterator::collect::<Vec<_>>), args);
+        #[cfg(windows)]
+        assert_eq!(
+            config.parse_cmd_os(input).map(|args| args.map(|arg| arg.into_string().unwrap()).collect::<Vec<_>>()),
+            args,
+        );
+        args
+    }
+
+    fn args(config: &ParserConfig, input: &str) -> Result<Vec<String>, ParseError> {
+        let args = config.parse_args(input).map(Iterator::collect::<Vec<_>>);
+        let wide = input.encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(config.parse_args(&wide).map(Iterator::collect::<Vec<_>>), args);
+        args
+    }
+
+    #[test]
+    fn placeholder_exe() {
+        let config = ParserConfig::new().placeholder_exe("app.exe");
+        assert_eq!(cmd(&config, ""), Ok(vec!["app.exe".to_string()]));
+        assert_eq!(cmd(&config, "\0x"), Ok(vec!["app.exe".to_string()]));
+        assert_eq!(cmd(&config, " x"), Ok(vec!["".to_string(), "x".to_string()]));
+        assert_eq!(cmd(&config, "\"\""), Ok(vec!["".to_string()]));
+        assert_eq!(args(&config, ""), Ok(vec![]));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn current_exe_for_empty() {
+        let exe = std::env::current_exe().unwrap().into_os_string().into_string().unwrap();
+        let config = ParserConfig::new().current_exe_for_empty(true).placeholder_exe("app.exe");
+        assert_eq!(cmd(&config, ""), Ok(vec![exe.clone()]));
+        assert_eq!(cmd(&config, "\0x"), Ok(vec![exe]));
+        assert_eq!(cmd(&config, " x"), Ok(vec!["".to_string(), "x".to_string()]));
+        assert_eq!(args(&config, ""), Ok(vec![]));
+
+        let config = config.current_exe_for_empty(false);
+        assert_eq!(cmd(&config, ""), Ok(vec!["app.exe".to_string()]));
+    }
+
+    #[test]
+    fn expand_wildcards() {
+        let dirs = |dir: &str| -> Vec<String> {
+            let names: &[&str] = match dir {
+                "" => &[".", "..", "b.txt", "a.TXT", "notes", ".hidden.txt"],
+                r"src\" | "src/" => &["main.rs", "lib.rs", "README.md"],
+                "C:" => &["a.txt"],
+                _ => &[],
+            };
+            names.iter().map(|name| name.to_string()).collect()
+        };
+        let config = ParserConfig::new().expand_wildcards(true);
+        let expand = |input: &str| config.parse_cmd_with_dirs(input, dirs).unwrap().collect::<Vec<_>>();
+        assert_eq!(expand("app.exe *.txt"), ["app.exe", "b.txt", "a.TXT", ".hidden.txt"]);
+        assert_eq!(expand("app.exe ?.txt n?tes"), ["app.exe", "b.txt", "a.TXT", "notes"]);
+        assert_eq!(expand("app.exe *"), ["app.exe", "b.txt", "a.TXT", "notes", ".hidden.txt"]);
+        // directories in the pattern
+        assert_eq!(expand(r"app.exe src\*.rs src/*.MD C:*.txt"), [
+            "app.exe", r"src\main.rs", r"src\lib.rs", "src/README.md", "C:a.txt",
+        ]);
+        assert_eq!(expand(r"app.exe *\main.rs other\*.rs"), ["app.exe", r"*\main.rs", r"other\*.rs"]);
+        // no matches
+        assert_eq!(expand("app.exe *.md x"), ["app.exe", "*.md", "x"]);
+        // quoted arguments and the executable name
+        assert_eq!(expand(r#"*.txt "*.txt" a"b"*.txt \"*.txt"#), ["*.txt", "*.txt", "ab*.txt", r#""*.txt"#]);
+        assert_eq!(
+            config.parse_args_with_dirs("*.txt", dirs).unwrap().collect::<Vec<_>>(),
+            ["b.txt", "a.TXT", ".hidden.txt"],
+        );
+
+        // the input is unaffected by default
+        let lenient = ParserConfig::new();
+        assert_eq!(lenient.parse_cmd_with_dirs("app.exe *.txt", dirs).unwrap().collect::<Vec<_>>(), ["app.exe", "*.txt"]);
+
+        // the UTF-16 path agrees
+        let wide = "app.exe src/*.rs \"*\"".encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(config.parse_cmd_with_dirs(&wide, dirs).unwrap().collect::<Vec<_>>(), ["app.exe", "src/main.rs", "src/lib.rs", "*"]);
+    }
+
+    #[test]
+    fn trailing_empty() {
+        let chk = |trailing, input: &str, expected: &[&str]| {
+            for compat in [Compat::Shell32, Compat::CrtModern, Compat::CrtLegacy, Compat::RustStd] {
+                let config = ParserConfig::new().compat(compat).trailing_empty(trailing);
+                let expected = Ok(expected.iter().map(|s| s.to_string()).collect());
+                assert_eq!(cmd(&config, input), expected, "{:?} {:?} {:?}", trailing, compat, input);
+            }
+        };
+        chk(TrailingEmpty::Keep, r#"foo """#, &["foo", ""]);
+        chk(TrailingEmpty::Keep, r#"foo ""#, &["foo", ""]);
+        chk(TrailingEmpty::Keep, r#"foo "bar"#, &["foo", "bar"]);
+        chk(TrailingEmpty::DropUnterminated, r#"foo """#, &["foo", ""]);
+        chk(TrailingEmpty::DropUnterminated, r#"foo ""#, &["foo"]);
+        chk(TrailingEmpty::DropUnterminated, r#"foo "bar"#, &["foo", "bar"]);
+        chk(TrailingEmpty::Drop, r#"foo """#, &["foo"]);
+        chk(TrailingEmpty::Drop, r#"foo ""#, &["foo"]);
+        chk(TrailingEmpty::Drop, r#"foo "bar"#, &["foo", "bar"]);
+
+        // only the last argument is affected, and never the executable name
+        chk(TrailingEmpty::Drop, r#"foo "" """#, &["foo", ""]);
+        chk(TrailingEmpty::Drop, r#"foo "" x"#, &["foo", "", "x"]);
+        chk(TrailingEmpty::Drop, r#"foo "" "#, &["foo"]);
+        chk(TrailingEmpty::Drop, r#"""#, &[""]);
+        chk(TrailingEmpty::Drop, r#""" "#, &[""]);
+
+        let config = ParserConfig::new().trailing_empty(TrailingEmpty::DropUnterminated);
+        assert_eq!(args(&config, r#"x ""#), Ok(vec!["x".to_string()]));
+        assert_eq!(args(&config, r#"""#), Ok(vec![]));
+        let config = config.strict_quotes(true);
+        assert_eq!(args(&config, r#"x ""#), Err(ParseError::UnterminatedQuote { offset: 2 }));
+    }
+
+    #[test]
+    fn limits() {
+        let too_many = |limit| Err(ParseError::LimitExceeded { kind: LimitKind::Args, limit });
+        let too_long = |limit| Err(ParseError::LimitExceeded { kind: LimitKind::InputLen, limit });
+
+        let config = ParserConfig::new().max_args(2);
+        assert_eq!(cmd(&config, "a.exe x"), Ok(vec!["a.exe".to_string(), "x".to_string()]));
+        assert_eq!(cmd(&config, "a.exe x y"), too_many(2));
+        assert_eq!(cmd(&config, "a.exe x\0y z"), Ok(vec!["a.exe".to_string(), "x".to_string()]));
+        assert_eq!(args(&config, "x y"), Ok(vec!["x".to_string(), "y".to_string()]));
+        assert_eq!(args(&config, "x y z"), too_many(2));
+        assert_eq!(cmd(&ParserConfig::new().max_args(0), ""), too_many(0));
+        assert_eq!(args(&ParserConfig::new().max_args(0), "  "), Ok(vec![]));
+
+        // the UTF-8 path counts bytes, and the UTF-16 path two bytes per code unit
+        let config = ParserConfig::new().max_input_len(4);
+        assert!(config.parse_cmd("a bc").is_ok());
+        assert_eq!(config.parse_cmd("a bcd").map(|_| ()), too_long(4));
+        assert!(config.parse_cmd("a é").is_ok());
+        assert_eq!(config.parse_cmd("aé é").map(|_| ()), too_long(4));
+        let wide = "a b".encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(config.parse_cmd(&wide).map(|_| ()), too_long(4));
+        assert!(config.parse_cmd(&wide[..2]).is_ok());
+        #[cfg(windows)]
+        assert_eq!(config.parse_cmd_os("a bcd").map(|_| ()), too_long(4));
+    }
+
+    /// The argument limit stops the parser without storing every argument first.
+    #[test]
+    fn limits_stop_early() {
+        let mut seen = 0;
+        let config = ParserConfig::new().max_args(3);
+        let input = "x ".repeat(1000);
+        let result = config.parse_units::<crate::args::Limited, u8>(input.bytes(), Mode::Args, &mut Vec::new(), |_, _| {
+            seen += 1;
+            config.check_arg_count(seen)
+        });
+        assert!(result.is_err());
+        assert_eq!(seen, 4);
+    }
+
+    #[test]
+    fn delimiter_policies() {
+        // The policies differ exactly when an unquoted executable name contains a control
+        // character other than tab.
+        let alphabet = ['a', '"', ' ', '\t', '\u{1}', '\u{1f}', '\0'];
+        let control_plane = ParserConfig::new().delimiters(DelimiterPolicy::ControlPlane);
+        let space_tab = ParserConfig::new().delimiters(DelimiterPolicy::SpaceTabOnly);
+        for input in short_strs(&alphabet, 5) {
+            let exe = input.split([' ', '\t', '\0']).next().unwrap();
+            let should_differ = !input.starts_with('"') && exe.contains(|c| c < ' ' && c != '\t');
+            for compat in [Compat::Shell32, Compat::CrtLegacy] {
+                let control_plane = cmd(&control_plane.clone().compat(compat), &input).unwrap();
+                let space_tab = cmd(&space_tab.clone().compat(compat), &input).unwrap();
+                assert_eq!(control_plane != space_tab, should_differ, "{:?} {:?}", compat, input);
+            }
+        }
+    }
+
+    #[test]
+    fn strict_quotes() {
+        let config = ParserConfig::new().strict_quotes(true);
+        assert_eq!(cmd(&config, "\"app.exe"), Err(ParseError::UnterminatedQuote { offset: 0 }));
+        assert_eq!(cmd(&config, "app.exe \"a b"), Err(ParseError::UnterminatedQuote { offset: 8 }));
+        assert_eq!(cmd(&config, "app.exe \"a\" \"b"), Err(ParseError::UnterminatedQuote { offset: 12 }));
+        assert_eq!(cmd(&config, "é.exe 😅 \"a b"), Err(ParseError::UnterminatedQuote { offset: 9 }));
+        assert_eq!(args(&config, "\"a b"), Err(ParseError::UnterminatedQuote { offset: 0 }));
+        assert_eq!(cmd(&config, "app.exe \"a b\" \\\""), Ok(vec!["app.exe".to_string(), "a b".to_string(), "\"".to_string()]));
+        assert_eq!(cmd(&config, "app.exe \"a b\0c\"").ok(), None);
+
+        let lenient = ParserConfig::new();
+        assert_eq!(cmd(&lenient, "app.exe \"a b"), Ok(vec!["app.exe".to_string(), "a b".to_string()]));
+    }
+
+    #[test]
+    fn strict_agrees_with_lenient() {
+        let alphabet = ['a', '"', '\\', ' ', '\t', 'é', '😅', '\0'];
+        for input in short_strs(&alphabet, 5) {
+            let wide = input.encode_utf16().collect::<Vec<u16>>();
+            for compat in [Compat::Shell32, Compat::CrtModern, Compat::CrtLegacy, Compat::RustStd] {
+                let lenient = ParserConfig::new().compat(compat);
+                let strict = lenient.clone().strict_quotes(true);
+                let results = [
+                    (cmd(&lenient, &input).unwrap(), cmd(&strict, &input)),
+                    (args(&lenient, &input).unwrap(), args(&strict, &input)),
+                ];
+                for (lenient, strict) in results {
+                    match strict {
+                        Ok(strict) => assert_eq!(strict, lenient, "{:?} {:?}", compat, input),
+                        Err(ParseError::UnterminatedQuote { offset }) => {
+                            assert_eq!(wide[offset], u16::from(b'"'), "{:?} {:?}", compat, input);
+                        },
+                        Err(err) => panic!("{:?}", err),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::ops::Range;
+
+use crate::args::{push, push_n, Alloc, CodeUnit, Mode, BACKSLASH, QUOTE, SPACE, TAB};
+use crate::config::DelimiterPolicy;
+
+/// Implements the argument splitting of the Microsoft C runtime since Visual C++ 2008
+/// (`parse_cmdline` in the CRT sources), which is what a program's `main` or `wmain` sees.
+///
+/// This differs from [`parse_lp_cmd_line`] in two places:
+///
+/// * Quotes may appear anywhere in the executable name, and only toggle whether spaces and
+///   tabs end it.  Other control characters are ordinary characters.
+/// * Inside a quoted region, `""` produces a literal quote *without* ending the region.
+///
+/// The rules of earlier versions are stated differently, but produce the same arguments as
+/// [`parse_lp_cmd_line`] for every input, so they need no parser of their own.
+///
+/// Otherwise, the interface is the same as that of [`parse_cmd_units`] (or of
+/// [`Mode::parse`], for `Mode::Args`).  If the input ended inside a quoted region, returns the
+/// offset of the quote that opened it.
+///
+/// [`parse_lp_cmd_line`]: crate::args::parse_lp_cmd_line
+/// [`parse_cmd_units`]: crate::args::parse_cmd_units
+pub(crate) fn parse_crt<A: Alloc, U: CodeUnit>(
+    units: impl IntoIterator<Item=U>,
+    mode: Mode,
+    delimiters: DelimiterPolicy,
+    cur: &mut Vec<U>,
+    mut emit: impl FnMut(&[U], Range<usize>) -> Result<(), A::Error>,
+) -> Result<Option<usize>, A::Error> {
+    let mut units = units.into_iter()
+        .take_while(|&c| c != U::from(0))
+        .enumerate()
+        .map(|(pos, c)| (pos, c, c.into()))
+        .peekable();
+    let backslash = U::from(BACKSLASH as u8);
+    let quote = U::from(QUOTE as u8);
+
+    let mut in_quotes = false;
+    let mut quote_start = 0;
+    if mode == Mode::Cmd {
+        cur.clear();
+        let mut end = 0;
+        loop {
+            match units.next() {
+                None => break,
+                Some((_, _, class)) if !in_quotes && delimiters.ends_exe(class) => break,
+                Some((pos, _, QUOTE)) => {
+                    in_quotes = !in_quotes;
+                    quote_start = pos;
+                    end = pos + 1;
+                },
+                Some((pos, c, _)) => {
+                    push::<A, _>(cur, c)?;
+                    end = pos + 1;
+                },
+            }
+        }
+        emit(&cur[..], 0..end)?;
+        if units.peek().is_none() {
+            return Ok(in_quotes.then_some(quote_start));
+        }
+        in_quotes = false;
+    }
+
+    loop {
+        while let Some((_, _, SPACE)) | Some((_, _, TAB)) = units.peek() {
+            units.next();
+        }
+        let start = match units.peek() {
+            Some(&(pos, _, _)) => pos,
+            None => return Ok(in_quotes.then_some(quote_start)),
+        };
+
+        cur.clear();
+        let mut end = start;
+        loop {
+            let mut backslash_count = 0;
+            while let Some(&(pos, _, BACKSLASH)) = units.peek() {
+                units.next();
+                backslash_count += 1;
+                end = pos + 1;
+            }
+
+            match units.next() {
+                Some((pos, _, QUOTE)) => {
+                    end = pos + 1;
+                    push_n::<A, U>(cur, backslash, backslash_count / 2)?;
+                    if !backslash_count.is_multiple_of(2) {
+                        push::<A, _>(cur, quote)?;
+                    } else if in_quotes && matches!(units.peek(), Some((_, _, QUOTE))) {
+                        // a doubled quote inside a quoted region
+                        let (pos, _, _) = units.next().unwrap();
+                        end = pos + 1;
+                        push::<A, _>(cur, quote)?;
+                    } else {
+                        in_quotes = !in_quotes;
+                        quote_start = pos;
+                    }
+                },
+                Some((_, _, SPACE)) | Some((_, _, TAB)) if !in_quotes => {
+                    push_n::<A, U>(cur, backslash, backslash_count)?;
+                    break;
+                },
+                Some((pos, c, _)) => {
+                    end = pos + 1;
+                    push_n::<A, U>(cur, backslash, backslash_count)?;
+                    push::<A, _>(cur, c)?;
+                },
+                None => {
+                    push_n::<A, U>(cur, backslash, backslash_count)?;
+                    break;
+                },
+            }
+        }
+        emit(&cur[..], start..end)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Compat, ParserConfig};
+
+    fn parse(compat: Compat, input: &str) -> Vec<String> {
+        let config = ParserConfig::new().compat(compat);
+        let args = config.parse_cmd(input).unwrap().collect::<Vec<_>>();
+        let wide = input.encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(config.parse_cmd(&wide).unwrap().collect::<Vec<_>>(), args, "{:?}", input);
+        args
+    }
+
+    /// Inputs on which the modes disagree, with the output of each.
+    #[test]
+    fn divergences() {
+        type Expected = &'static [&'static str];
+        let table: &[(&str, Expected, Expected, Expected)] = &[
+            // (input, Shell32, CrtModern, CrtLegacy)
+            (
+                r#"app.exe "a"" b""#,
+                &["app.exe", r#"a""#, "b"],
+                &["app.exe", r#"a" b"#],
+                &["app.exe", r#"a""#, "b"],
+            ),
+            (
+                r#"app.exe a"""b c"#,
+                &["app.exe", r#"a"b"#, "c"],
+                &["app.exe", r#"a"b c"#],
+                &["app.exe", r#"a"b"#, "c"],
+            ),
+            (
+                r#"app.exe """" x"#,
+                &["app.exe", r#"" x"#],
+                &["app.exe", r#"""#, "x"],
+                &["app.exe", r#"" x"#],
+            ),
+            (
+                r#""C:\a b"c d"#,
+                &[r"C:\a b", "c", "d"],
+                &[r"C:\a bc", "d"],
+                &[r"C:\a b", "c", "d"],
+            ),
+            (
+                r#"C:\a"b c"\x.exe y"#,
+                &[r#"C:\a"b"#, r"c\x.exe y"],
+                &[r"C:\ab c\x.exe", "y"],
+                &[r#"C:\a"b"#, r"c\x.exe y"],
+            ),
+            (
+                "\x01app.exe x",
+                &["", "app.exe", "x"],
+                &["\x01app.exe", "x"],
+                &["", "app.exe", "x"],
+            ),
+            (
+                "app.exe\x01x y",
+                &["app.exe", "x", "y"],
+                &["app.exe\x01x", "y"],
+                &["app.exe", "x", "y"],
+            ),
+        ];
+        for &(input, shell32, crt_modern, crt_legacy) in table {
+            assert_eq!(parse(Compat::Shell32, input), shell32, "Shell32 {:?}", input);
+            assert_eq!(parse(Compat::CrtModern, input), crt_modern, "CrtModern {:?}", input);
+            assert_eq!(parse(Compat::CrtLegacy, input), crt_legacy, "CrtLegacy {:?}", input);
+        }
+    }
+
+    /// Inputs on which the modes agree.
+    #[test]
+    fn agreements() {
+        let table: &[(&str, &[&str])] = &[
+            ("", &[""]),
+            ("  a", &["", "a"]),
+            (r#""C:\a b\app.exe" x"#, &[r"C:\a b\app.exe", "x"]),
+            (r#""unterminated exe"#, &["unterminated exe"]),
+            (r#"app.exe a\\\"b "c d" e\\ "f\\" """#, &["app.exe", r#"a\"b"#, "c d", r"e\\", r"f\", ""]),
+            (r#"app.exe "a b"#, &["app.exe", "a b"]),
+            ("app.exe \t a\0b", &["app.exe", "a"]),
+        ];
+        for &(input, expected) in table {
+            assert_eq!(parse(Compat::Shell32, input), expected, "Shell32 {:?}", input);
+            assert_eq!(parse(Compat::CrtModern, input), expected, "CrtModern {:?}", input);
+            assert_eq!(parse(Compat::CrtLegacy, input), expected, "CrtLegacy {:?}", input);
+        }
+    }
+
+    #[test]
+    fn args_mode() {
+        let config = ParserConfig::new().compat(Compat::CrtModern);
+        let parse = |input: &str| config.parse_args(input).unwrap().collect::<Vec<_>>();
+        assert_eq!(parse(""), Vec::<String>::new());
+        assert_eq!(parse(r#"  "a"" b" c"#), [r#"a" b"#, "c"]);
+        assert_eq!(parse(r#""C:\a b"c"#), [r"C:\a bc"]);
+    }
+
+    #[test]
+    fn strict_quotes() {
+        let config = ParserConfig::new().compat(Compat::CrtModern).strict_quotes(true);
+        assert!(config.parse_cmd(r#""app.exe"#).is_err());
+        assert!(config.parse_cmd(r#"app.exe a"""b"#).is_err());
+        assert!(config.parse_cmd(r#"app.exe "a"" b""#).is_ok());
+    }
+}
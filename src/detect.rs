@@ -0,0 +1,122 @@
+use std::ffi::{c_void, OsString};
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::sync::OnceLock;
+use std::{mem, slice};
+
+use crate::{Compat, DelimiterPolicy, ParserConfig};
+
+type CommandLineToArgvW = unsafe extern "system" fn(*const u16, *mut i32) -> *mut *mut u16;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn LoadLibraryW(lpLibFileName: *const u16) -> *mut c_void;
+    fn GetProcAddress(hModule: *mut c_void, lpProcName: *const u8) -> *mut c_void;
+    fn LocalFree(hMem: *mut c_void) -> *mut c_void;
+}
+
+/// **Windows only.**
+/// How the `CommandLineToArgvW` of the running system splits command lines, as found by
+/// [`ParserConfig::current_windows_quirks`].
+///
+/// [`ParserConfig::current_windows_quirks`]: struct.ParserConfig.html#method.current_windows_quirks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DetectedQuirks {
+    /// It agrees with this configuration on every probe.
+    Matched { compat: Compat, delimiters: DelimiterPolicy },
+    /// It agrees with none of the configurations that were tried.
+    Unrecognized,
+    /// `shell32.dll` or `CommandLineToArgvW` could not be loaded.
+    Unavailable,
+}
+
+/// Command lines on which the known quirks differ.
+const PROBES: &[&str] = &[
+    "a\u{1}b c",
+    "a\u{7f}b c",
+    r#"app.exe "a"" b" c"#,
+    r#"app.exe """" x"#,
+    r#""C:\a b"c d"#,
+    r#"a"b c"\x.exe y"#,
+    r#"app.exe a\\\"b "c\\" "d"#,
+    r#"app.exe "" ""#,
+    " app.exe a",
+];
+
+/// The candidates, in order of preference.  The other `Compat`s describe the C runtime's
+/// parser, which no version of `CommandLineToArgvW` uses.
+const CANDIDATES: &[(Compat, DelimiterPolicy)] = &[
+    (Compat::Shell32, DelimiterPolicy::ControlPlane),
+    (Compat::Shell32, DelimiterPolicy::SpaceTabOnly),
+];
+
+/// Probe the system once, and remember the result.
+pub(crate) fn current_quirks() -> DetectedQuirks {
+    static QUIRKS: OnceLock<DetectedQuirks> = OnceLock::new();
+    *QUIRKS.get_or_init(detect)
+}
+
+fn detect() -> DetectedQuirks {
+    let command_line_to_argv = match load() {
+        Some(func) => func,
+        None => return DetectedQuirks::Unavailable,
+    };
+    let mut actual = Vec::with_capacity(PROBES.len());
+    for probe in PROBES {
+        match unsafe { split(command_line_to_argv, probe) } {
+            Some(args) => actual.push(args),
+            None => return DetectedQuirks::Unavailable,
+        }
+    }
+
+    CANDIDATES.iter()
+        .find(|&&(compat, delimiters)| {
+            let config = ParserConfig::new().compat(compat).delimiters(delimiters);
+            PROBES.iter().zip(&actual).all(|(probe, actual)| {
+                config.parse_cmd_os(*probe).is_ok_and(|args| args.collect::<Vec<_>>() == *actual)
+            })
+        })
+        .map_or(DetectedQuirks::Unrecognized, |&(compat, delimiters)| DetectedQuirks::Matched { compat, delimiters })
+}
+
+fn load() -> Option<CommandLineToArgvW> {
+    let name = "shell32.dll".encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    unsafe {
+        let module = LoadLibraryW(name.as_ptr());
+        if module.is_null() {
+            return None;
+        }
+        let func = GetProcAddress(module, b"CommandLineToArgvW\0".as_ptr());
+        match func.is_null() {
+            true => None,
+            // SAFETY: this is the documented signature of CommandLineToArgvW
+            false => Some(mem::transmute::<*mut c_void, CommandLineToArgvW>(func)),
+        }
+    }
+}
+
+/// Split a command line with the system's `CommandLineToArgvW`.
+unsafe fn split(func: CommandLineToArgvW, input: &str) -> Option<Vec<OsString>> {
+    let wide = std::ffi::OsStr::new(input).encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let mut num_args = 0;
+    let argv = func(wide.as_ptr(), &mut num_args);
+    if argv.is_null() {
+        return None;
+    }
+    let args = slice::from_raw_parts(argv, num_args as usize).iter().map(|&arg| {
+        let len = (0..).take_while(|&i| *arg.add(i) != 0).count();
+        OsString::from_wide(slice::from_raw_parts(arg, len))
+    }).collect();
+    LocalFree(argv as *mut c_void);
+    Some(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell32() {
+        let expected = DetectedQuirks::Matched { compat: Compat::Shell32, delimiters: DelimiterPolicy::ControlPlane };
+        assert_eq!(detect(), expected);
+    }
+}
@@ -0,0 +1,231 @@
+//! Editing individual arguments of a command line, leaving the rest of it untouched.
+//!
+//! Only the edited argument is rewritten; every other argument keeps its original quoting,
+//! and all whitespace between arguments is preserved byte for byte.
+//!
+//! ```
+//! use windows_args::edit::{insert_arg_after, remove_arg, replace_arg};
+//!
+//! let line = r#"app.exe  "--input" C:\old.txt   -v"#;
+//! assert_eq!(replace_arg(line, 2, r#"C:\new dir\"#).unwrap(), r#"app.exe  "--input" "C:\new dir\\"   -v"#);
+//! assert_eq!(remove_arg(line, 3).unwrap(), r#"app.exe  "--input" C:\old.txt"#);
+//! assert_eq!(insert_arg_after(line, 0, "-q").unwrap(), r#"app.exe -q  "--input" C:\old.txt   -v"#);
+//! ```
+//!
+//! Every edit is checked by parsing the result; if the command line would not parse to the
+//! original arguments with exactly the requested change, an error is returned instead.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use crate::args::{parse_lp_cmd_line, unwrap_infallible, Infallible};
+use crate::quote::write_quoted_with;
+
+/// Replace the argument at `index` (where `0` is the executable name) with `new_value`.
+///
+/// The new value is quoted only if necessary, or if the original argument began with a quote.
+pub fn replace_arg(input: &str, index: usize, new_value: &str) -> Result<String, EditError> {
+    let parsed = Parsed::new(input);
+    let span = parsed.span(index)?;
+
+    let was_quoted = input[span.clone()].starts_with('"');
+    let mut out = String::with_capacity(input.len() + new_value.len() + 2);
+    out.push_str(&input[..span.start]);
+    push_quoted(&mut out, index, new_value, was_quoted)?;
+    out.push_str(&input[span.end..]);
+
+    let mut expected = parsed.args;
+    expected[index] = new_value.to_string();
+    check(out, &expected)
+}
+
+/// Remove the argument at `index`, along with the whitespace that precedes it.
+pub fn remove_arg(input: &str, index: usize) -> Result<String, EditError> {
+    let parsed = Parsed::new(input);
+    let span = parsed.span(index)?;
+
+    let removed = match index {
+        // this also removes the whitespace that would otherwise become an empty exe name
+        0 => 0..parsed.spans.get(1).map_or(span.end, |next| next.start),
+        _ => parsed.spans[index - 1].end..span.end,
+    };
+    let mut out = String::with_capacity(input.len());
+    out.push_str(&input[..removed.start]);
+    out.push_str(&input[removed.end..]);
+
+    let mut expected = parsed.args;
+    expected.remove(index);
+    check(out, &expected)
+}
+
+/// Insert a new argument immediately after the argument at `index`.
+pub fn insert_arg_after(input: &str, index: usize, new_value: &str) -> Result<String, EditError> {
+    let parsed = Parsed::new(input);
+    let span = parsed.span(index)?;
+
+    let mut out = String::with_capacity(input.len() + new_value.len() + 4);
+    out.push_str(&input[..span.end]);
+    out.push(' ');
+    push_quoted(&mut out, index + 1, new_value, false)?;
+    if !input[span.end..].starts_with(&[' ', '\t', '\0'][..]) && span.end < input.len() {
+        out.push(' ');
+    }
+    out.push_str(&input[span.end..]);
+
+    let mut expected = parsed.args;
+    expected.insert(index + 1, new_value.to_string());
+    check(out, &expected)
+}
+
+/// An argument could not be edited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    /// There is no argument at this index.
+    IndexOutOfRange { index: usize, len: usize },
+    /// No spelling of the edit would preserve the other arguments.
+    ///
+    /// For instance, the new value may contain a NUL, or it may be destined for the
+    /// executable name and contain a `"`.
+    Unrepresentable,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::IndexOutOfRange { index, len } => {
+                write!(f, "argument index {} out of range for {} arguments", index, len)
+            },
+            EditError::Unrepresentable => write!(f, "the edited command line cannot be represented"),
+        }
+    }
+}
+
+impl Error for EditError {}
+
+/// The arguments of a command line, with their byte ranges.
+struct Parsed {
+    args: Vec<String>,
+    spans: Vec<Range<usize>>,
+}
+
+impl Parsed {
+    fn new(input: &str) -> Parsed {
+        let wide = input.encode_utf16().collect::<Vec<_>>();
+
+        // the byte offset of each code unit, and of the end
+        let mut offsets = Vec::with_capacity(wide.len());
+        for (offset, c) in input.char_indices() {
+            offsets.extend(std::iter::repeat_n(offset, c.len_utf16()));
+        }
+        offsets.push(input.len());
+
+        let mut parsed = Parsed { args: vec![], spans: vec![] };
+        unwrap_infallible(parse_lp_cmd_line::<Infallible>(&wide, &mut vec![], |arg, span| {
+            // arguments always begin and end at ASCII characters, so pairs are never split
+            parsed.args.push(String::from_utf16(arg).expect("surrogate pair was split?!"));
+            parsed.spans.push(offsets[span.start]..offsets[span.end]);
+            Ok(())
+        }));
+        parsed
+    }
+
+    fn span(&self, index: usize) -> Result<Range<usize>, EditError> {
+        self.spans.get(index).cloned().ok_or(EditError::IndexOutOfRange { index, len: self.spans.len() })
+    }
+}
+
+fn push_quoted(out: &mut String, index: usize, value: &str, force_quotes: bool) -> Result<(), EditError> {
+    if index > 0 {
+        write_quoted_with(out, value.chars(), force_quotes).expect("a formatting trait implementation returned an error");
+        return Ok(());
+    }
+
+    // The executable name ends at the next quote if it begins with one, and otherwise at
+    // the first whitespace or control character.  Nothing can be escaped.
+    if value.contains('"') {
+        return Err(EditError::Unrepresentable);
+    }
+    if force_quotes || value.is_empty() || value.chars().any(|c| c <= ' ') {
+        out.push('"');
+        out.push_str(value);
+        out.push('"');
+    } else {
+        out.push_str(value);
+    }
+    Ok(())
+}
+
+fn check(out: String, expected: &[String]) -> Result<String, EditError> {
+    match Parsed::new(&out).args == expected {
+        true => Ok(out),
+        false => Err(EditError::Unrepresentable),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::short_strings::short_strs;
+
+    #[test]
+    fn untouched_regions() {
+        let line = "a.exe\t\"x\"  y\\\\  \"z\" ";
+        assert_eq!(replace_arg(line, 2, "new").unwrap(), "a.exe\t\"x\"  new  \"z\" ");
+        assert_eq!(replace_arg(line, 1, "new").unwrap(), "a.exe\t\"new\"  y\\\\  \"z\" ");
+        assert_eq!(replace_arg(line, 3, "a b").unwrap(), "a.exe\t\"x\"  y\\\\  \"a b\" ");
+        assert_eq!(replace_arg(line, 0, "b.exe").unwrap(), "b.exe\t\"x\"  y\\\\  \"z\" ");
+        assert_eq!(replace_arg(line, 0, "C:\\b c.exe").unwrap(), "\"C:\\b c.exe\"\t\"x\"  y\\\\  \"z\" ");
+        assert_eq!(remove_arg(line, 2).unwrap(), "a.exe\t\"x\"  \"z\" ");
+        assert_eq!(remove_arg(line, 3).unwrap(), "a.exe\t\"x\"  y\\\\ ");
+        assert_eq!(insert_arg_after(line, 1, "").unwrap(), "a.exe\t\"x\" \"\"  y\\\\  \"z\" ");
+        assert_eq!(insert_arg_after(line, 3, "w").unwrap(), "a.exe\t\"x\"  y\\\\  \"z\" w ");
+    }
+
+    #[test]
+    fn adjacent_runs() {
+        // a single argument made of quoted and unquoted runs
+        let line = r#"exe a"b c"d e"#;
+        assert_eq!(replace_arg(line, 1, "x").unwrap(), "exe x e");
+        assert_eq!(replace_arg(line, 1, "x y").unwrap(), r#"exe "x y" e"#);
+        assert_eq!(replace_arg(r#"exe "b c"d e"#, 1, "x").unwrap(), r#"exe "x" e"#);
+
+        // an argument immediately following a quoted executable name
+        let line = r#""a b.exe"c d"#;
+        assert_eq!(replace_arg(line, 1, "x y").unwrap(), r#""a b.exe""x y" d"#);
+        assert_eq!(replace_arg(line, 0, "e").unwrap(), r#""e"c d"#);
+        assert_eq!(remove_arg(line, 1).unwrap(), r#""a b.exe" d"#);
+        assert_eq!(insert_arg_after(line, 0, "x").unwrap(), r#""a b.exe" x c d"#);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(replace_arg("a b", 2, "c"), Err(EditError::IndexOutOfRange { index: 2, len: 2 }));
+        assert_eq!(replace_arg("a b", 0, r#"c"d"#), Err(EditError::Unrepresentable));
+        assert_eq!(replace_arg("a b", 1, "c\0d"), Err(EditError::Unrepresentable));
+        assert_eq!(remove_arg("a", 0), Err(EditError::Unrepresentable));
+        // an unterminated quote would swallow the new argument
+        assert_eq!(insert_arg_after(r#"a "b"#, 1, "c"), Err(EditError::Unrepresentable));
+    }
+
+    #[test]
+    fn exhaustive() {
+        const CHARS: &[char] = &['a', ' ', '\t', '"', '\\', '\0'];
+        let values = ["", "v", "v w", "\"", "v\\", "\\\"", "\\ "];
+
+        for input in short_strs(CHARS, 5) {
+            let len = Parsed::new(&input).args.len();
+
+            for index in 1..len {
+                for value in &values {
+                    // edits away from the executable name only fail after an unterminated quote
+                    replace_arg(&input, index, value).unwrap();
+                    if index + 1 < len {
+                        insert_arg_after(&input, index, value).unwrap();
+                    }
+                }
+                remove_arg(&input, index).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::ffi::{OsStr, OsString};
+use std::ops::Range;
+
+use crate::split::split_exe_bytes;
+
+/// Which parts of a command line [`expand_env_vars`] applies to.
+///
+/// [`expand_env_vars`]: fn.expand_env_vars.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExpandScope {
+    /// Only the executable name, up to and including the character that ends it.
+    Exe,
+    /// Only what follows the executable name.
+    Args,
+    /// The entire input.
+    Both,
+}
+
+/// Expand `%NAME%` references to environment variables, as `ExpandEnvironmentStrings` does.
+///
+/// This is meant for values like registry `REG_EXPAND_SZ` commands and scheduled task
+/// definitions.  Variables are looked up with `lookup`, so that they can be expanded against
+/// a captured environment rather than that of the current process.  (Note that on Windows,
+/// the names of environment variables are case-insensitive.)
+///
+/// The rules are those of Windows: a reference to a variable for which `lookup` returns
+/// `None` is left as written, but its closing `%` may begin another reference (so
+/// `%UNKNOWN%SystemRoot%` becomes `%UNKNOWNC:\Windows`), and `%%` is not an escape (so
+/// `%%SystemRoot%` becomes `%C:\Windows`).  Expansion is purely textual
+/// and happens before any parsing, so quotes in the input have no effect on it, and an
+/// expanded value containing spaces or quotes changes how the result is parsed (just as it
+/// would for `CreateProcess`).
+///
+/// ```
+/// use std::ffi::OsString;
+/// use windows_args::{expand_env_vars, Args, ExpandScope};
+///
+/// let lookup = |name: &str| match name {
+///     "SystemRoot" => Some(OsString::from(r"C:\Windows")),
+///     _ => None,
+/// };
+/// let input = r#"%SystemRoot%\system32\cmd.exe /c "echo %UNKNOWN% 100%%""#;
+/// let expanded = expand_env_vars(input.as_ref(), ExpandScope::Both, lookup);
+/// assert_eq!(expanded, r#"C:\Windows\system32\cmd.exe /c "echo %UNKNOWN% 100%%""#);
+/// ```
+///
+/// With [`ExpandScope::Exe`] or [`ExpandScope::Args`], the executable name is found (as by
+/// [`split_exe`]) in the unexpanded input, and only the chosen part is expanded.
+///
+/// [`ExpandScope::Exe`]: enum.ExpandScope.html#variant.Exe
+/// [`ExpandScope::Args`]: enum.ExpandScope.html#variant.Args
+/// [`split_exe`]: fn.split_exe.html
+pub fn expand_env_vars(
+    input: &OsStr,
+    scope: ExpandScope,
+    mut lookup: impl FnMut(&str) -> Option<OsString>,
+) -> OsString {
+    let bytes = input.as_encoded_bytes();
+    let (_, rest) = split_exe_bytes(bytes);
+    let (exe, args) = bytes.split_at(rest.start);
+
+    let mut out = OsString::with_capacity(bytes.len());
+    match scope {
+        ExpandScope::Exe => {
+            expand_into(&mut out, exe, &mut lookup);
+            out.push(from_bytes(args));
+        },
+        ExpandScope::Args => {
+            out.push(from_bytes(exe));
+            expand_into(&mut out, args, &mut lookup);
+        },
+        ExpandScope::Both => expand_into(&mut out, bytes, &mut lookup),
+    }
+    out
+}
+
+fn expand_into(out: &mut OsString, input: &[u8], lookup: &mut impl FnMut(&str) -> Option<OsString>) {
+    expand_refs(input, |piece| match piece {
+        Piece::Text(range) => {
+            out.push(from_bytes(&input[range]));
+            true
+        },
+        Piece::Var(range) => {
+            let value = std::str::from_utf8(&input[range]).ok().and_then(&mut *lookup);
+            value.map(|value| out.push(value)).is_some()
+        },
+    })
+}
+
+/// A part of the input to [`expand_refs`].
+pub(crate) enum Piece {
+    /// Text to copy as it is.
+    Text(Range<usize>),
+    /// The name in a `%NAME%` reference.
+    Var(Range<usize>),
+}
+
+/// Split text in an ASCII-compatible encoding into literal text and `%NAME%` references, by
+/// the rules of `ExpandEnvironmentStrings`.
+///
+/// `emit` receives the pieces in order, and returns whether it expanded a reference.  If it
+/// did not, only the `%NAME` is copied as text, and scanning resumes at the closing `%`.  The
+/// return value for text is ignored.
+pub(crate) fn expand_refs(input: &[u8], mut emit: impl FnMut(Piece) -> bool) {
+    let mut pos = 0;
+    while let Some(open) = input[pos..].iter().position(|&b| b == b'%').map(|i| pos + i) {
+        let close = match input[open + 1..].iter().position(|&b| b == b'%') {
+            Some(len) => open + 1 + len,
+            None => break,
+        };
+        emit(Piece::Text(pos..open));
+        if close > open + 1 && emit(Piece::Var(open + 1..close)) {
+            pos = close + 1;
+        } else {
+            // `%%` is not an escape, and the closing `%` may begin another reference
+            emit(Piece::Text(open..close));
+            pos = close;
+        }
+    }
+    emit(Piece::Text(pos..input.len()));
+}
+
+/// View part of the encoded bytes of an `OsStr`, split next to ASCII characters.
+///
+/// The pieces are joined with `OsString::push`, which correctly joins surrogates that end up
+/// next to each other.
+fn from_bytes(bytes: &[u8]) -> &OsStr {
+    // SAFETY: every split is next to an ASCII character, which is a valid place to split
+    // the encoding
+    unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+
+    fn lookup(name: &str) -> Option<OsString> {
+        match name {
+            "ROOT" => Some(r"C:\Windows".into()),
+            "PF" => Some(r"C:\Program Files".into()),
+            "Q" => Some("\"".into()),
+            "EMPTY" => Some("".into()),
+            _ => None,
+        }
+    }
+
+    fn expand(input: &str, scope: ExpandScope) -> String {
+        expand_env_vars(input.as_ref(), scope, lookup).into_string().unwrap()
+    }
+
+    #[test]
+    fn windows_rules() {
+        let chk = |input: &str, expected: &str| assert_eq!(expand(input, ExpandScope::Both), expected, "{:?}", input);
+        chk("", "");
+        chk(r"%ROOT%\a.exe", r"C:\Windows\a.exe");
+        chk("%ROOT%%ROOT%", r"C:\WindowsC:\Windows");
+        chk("a%EMPTY%b", "ab");
+        // unknown variables and `%%` are left as written
+        chk("%NOPE% %% 100%", "%NOPE% %% 100%");
+        chk("%ROOT", "%ROOT");
+        // but their closing `%` may begin another reference
+        chk("%NOPE%ROOT%", r"%NOPEC:\Windows");
+        chk("%%ROOT%", r"%C:\Windows");
+        chk("%%%ROOT%%", r"%%C:\Windows%");
+        // quotes do not prevent expansion
+        chk(r#""%ROOT%" '%ROOT%'"#, r#""C:\Windows" 'C:\Windows'"#);
+    }
+
+    #[test]
+    fn scopes() {
+        let input = r#""%PF%\app.exe" %ROOT% x%ROOT%"#;
+        assert_eq!(expand(input, ExpandScope::Exe), r#""C:\Program Files\app.exe" %ROOT% x%ROOT%"#);
+        assert_eq!(expand(input, ExpandScope::Args), r#""%PF%\app.exe" C:\Windows xC:\Windows"#);
+        assert_eq!(expand(input, ExpandScope::Both), r#""C:\Program Files\app.exe" C:\Windows xC:\Windows"#);
+
+        // the exe ends before a reference that begins right after its delimiter
+        assert_eq!(expand("app.exe\t%ROOT%", ExpandScope::Exe), "app.exe\t%ROOT%");
+        assert_eq!(expand("app.exe\t%ROOT%", ExpandScope::Args), "app.exe\tC:\\Windows");
+        assert_eq!(expand("%ROOT%", ExpandScope::Args), "%ROOT%");
+        assert_eq!(expand("\"%ROOT%", ExpandScope::Args), "\"%ROOT%");
+    }
+
+    #[test]
+    fn expansion_precedes_parsing() {
+        let parse = |input: &str, scope| Args::parse_cmd(&expand(input, scope)).collect::<Vec<_>>();
+        // an unquoted value containing a space splits, as it would for CreateProcess
+        assert_eq!(parse(r"%PF%\app.exe x", ExpandScope::Both), [r"C:\Program", r"Files\app.exe", "x"]);
+        assert_eq!(parse(r"app.exe %Q%a b%Q%", ExpandScope::Both), ["app.exe", "a b"]);
+        assert_eq!(parse(r"app.exe %Q%a b%Q%", ExpandScope::Exe), ["app.exe", "%Q%a", "b%Q%"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn agrees_with_expand_environment_strings() {
+        use std::os::windows::ffi::OsStringExt;
+        use crate::short_strings::short_strings;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn ExpandEnvironmentStringsW(lpSrc: *const u16, lpDst: *mut u16, nSize: u32) -> u32;
+        }
+
+        fn system(input: &str) -> OsString {
+            let src = input.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+            let mut buf = vec![0u16; 64];
+            loop {
+                let len = unsafe { ExpandEnvironmentStringsW(src.as_ptr(), buf.as_mut_ptr(), buf.len() as u32) } as usize;
+                assert_ne!(len, 0, "{}", std::io::Error::last_os_error());
+                // the length includes the terminator
+                if len <= buf.len() {
+                    return OsString::from_wide(&buf[..len - 1]);
+                }
+                buf.resize(len, 0);
+            }
+        }
+
+        std::env::set_var("WINDOWS_ARGS_EXPAND_X", "[x]");
+        std::env::remove_var("WINDOWS_ARGS_EXPAND_NO");
+        let pieces = ["%", "WINDOWS_ARGS_EXPAND_X", "WINDOWS_ARGS_EXPAND_NO", "a", " "];
+        for input in short_strings(&pieces, 5) {
+            let input = input.concat();
+            let expanded = expand_env_vars(input.as_ref(), ExpandScope::Both, |name| std::env::var_os(name));
+            assert_eq!(expanded, system(&input), "{:?}", input);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn surrogates() {
+        use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+        // halves of a surrogate pair that meet are joined
+        let lead = OsString::from_wide(&[0xD83D]);
+        let trail = OsString::from_wide(&[0xDE05]);
+        let mut input = lead.clone();
+        input.push("%T%");
+        let lookup = |name: &str| match name {
+            "T" => Some(trail.clone()),
+            _ => None,
+        };
+        let expanded = expand_env_vars(&input, ExpandScope::Both, lookup);
+        assert_eq!(expanded, "😅");
+        assert_eq!(expanded.encode_wide().collect::<Vec<_>>(), "😅".encode_utf16().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,154 @@
+//! Tools for command lines captured by logging, which may be damaged.
+
+#[cfg(windows)]
+use std::ffi::OsStr;
+use crate::args::{parse_lp_cmd_line, unwrap_infallible, until_nul, ArgsWtf8, Infallible, Mode};
+use crate::wtf8like::{IsWtf8Buf, IsWtf8Slice};
+use crate::Args;
+#[cfg(windows)]
+use crate::ArgsOs;
+
+/// Lengths (in UTF-16 code units) at which command lines are commonly cut off by loggers.
+///
+/// These include the `cmd.exe` line limit of 8191, the 8192 used by many event feeds, and
+/// the 32767 limit of `CreateProcessW`.
+pub const KNOWN_TRUNCATION_LIMITS: &[usize] = &[8191, 8192, 32767, 32768];
+
+/// Signs that a command line was truncated, as found by [`parse_possibly_truncated`].
+///
+/// [`parse_possibly_truncated`]: fn.parse_possibly_truncated.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TruncationAssessment {
+    /// The length of the input matches the truncation limit that was hinted, or, if none
+    /// was given, one of the [`KNOWN_TRUNCATION_LIMITS`].
+    pub at_limit: bool,
+    /// Parsing ended inside a quoted region, which is typical of a cut through a quoted
+    /// argument (but can also just be sloppy input).
+    pub ends_in_quotes: bool,
+    /// The input appears to be truncated and ends in the middle of the final argument,
+    /// so that argument may be incomplete.
+    ///
+    /// When this is `false`, a cut at the limit fell on whitespace between arguments.
+    pub suspect_final_arg: bool,
+}
+
+impl TruncationAssessment {
+    /// Whether there is any indication of truncation.
+    pub fn is_suspicious(&self) -> bool {
+        self.at_limit || self.ends_in_quotes
+    }
+}
+
+/// **Windows only.**
+/// Parse a complete command line (like [`ArgsOs::parse_cmd`]), also assessing whether it
+/// was cut off by a length limit.
+///
+/// `limit_hint` is the length, in UTF-16 code units, at which the source of the input is
+/// known to truncate.  The parse itself is unaffected by the assessment.
+///
+/// [`ArgsOs::parse_cmd`]: ../struct.ArgsOs.html#method.parse_cmd
+#[cfg(windows)]
+pub fn parse_possibly_truncated(input: &OsStr, limit_hint: Option<usize>) -> (ArgsOs, TruncationAssessment) {
+    let (inner, assessment) = assess(input, limit_hint);
+    (ArgsOs { inner }, assessment)
+}
+
+/// Like [`parse_possibly_truncated`], but for a `str`.
+///
+/// [`parse_possibly_truncated`]: fn.parse_possibly_truncated.html
+pub fn parse_possibly_truncated_str(input: &str, limit_hint: Option<usize>) -> (Args, TruncationAssessment) {
+    let (inner, assessment) = assess(wtf8::Wtf8::from_str(input), limit_hint);
+    (Args { inner }, assessment)
+}
+
+fn assess<S: IsWtf8Buf, I: IsWtf8Slice + ?Sized>(
+    input: &I,
+    limit_hint: Option<usize>,
+) -> (ArgsWtf8<S>, TruncationAssessment) {
+    let mut wide = Vec::with_capacity(input.wide_len_bound());
+    input.extend_wide(&mut wide);
+    let len = wide.len();
+
+    let mut args = vec![];
+    let mut last_end = 0;
+    let open_quote = unwrap_infallible(parse_lp_cmd_line::<Infallible>(&wide, &mut vec![], |arg, span| {
+        args.push(S::from_wide(arg));
+        last_end = span.end;
+        Ok(())
+    }));
+
+    let at_limit = match limit_hint {
+        Some(limit) => len == limit,
+        None => KNOWN_TRUNCATION_LIMITS.contains(&len),
+    };
+    let assessment = TruncationAssessment {
+        at_limit,
+        ends_in_quotes: open_quote.is_some(),
+        suspect_final_arg: at_limit && last_end == len,
+    };
+    (ArgsWtf8::with_raw(args, S::from_wide(until_nul(&wide)), Mode::Cmd), assessment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chk(input: &str, limit_hint: Option<usize>) -> (Vec<String>, TruncationAssessment) {
+        let (args, assessment) = parse_possibly_truncated_str(input, limit_hint);
+        (args.collect(), assessment)
+    }
+
+    #[test]
+    fn cut_inside_quotes() {
+        let full = r#"app.exe -f "C:\Program Files\target.txt" -v"#;
+        let cut = &full[..25];
+        let (args, assessment) = chk(cut, Some(25));
+        assert_eq!(args, ["app.exe", "-f", r#"C:\Program Fi"#]);
+        assert_eq!(assessment, TruncationAssessment {
+            at_limit: true,
+            ends_in_quotes: true,
+            suspect_final_arg: true,
+        });
+    }
+
+    #[test]
+    fn cut_between_args() {
+        let (args, assessment) = chk("app.exe -a -b ", Some(14));
+        assert_eq!(args, ["app.exe", "-a", "-b"]);
+        assert_eq!(assessment, TruncationAssessment {
+            at_limit: true,
+            ends_in_quotes: false,
+            suspect_final_arg: false,
+        });
+    }
+
+    #[test]
+    fn keeps_raw() {
+        let input = r#"app.exe  -f "C:\a b"#;
+        let (mut args, _) = parse_possibly_truncated_str(input, None);
+        assert_eq!(args.raw(), Some(input));
+        args.next();
+        assert_eq!(args.rest_raw(), Some(r#"-f "C:\a b"#));
+    }
+
+    #[test]
+    fn known_limits() {
+        let input = format!("app.exe {}", "a".repeat(8192 - 8));
+        let (_, assessment) = chk(&input, None);
+        assert!(assessment.at_limit && assessment.suspect_final_arg);
+        let (_, assessment) = chk(&input, Some(1000));
+        assert!(!assessment.is_suspicious());
+    }
+
+    #[test]
+    fn not_truncated() {
+        let (args, assessment) = chk(r#"app.exe "a b""#, None);
+        assert_eq!(args, ["app.exe", "a b"]);
+        assert_eq!(assessment, TruncationAssessment::default());
+        assert!(!assessment.is_suspicious());
+
+        // an unterminated quote is noticed even below the limit
+        let (_, assessment) = chk(r#""app.exe"#, None);
+        assert!(assessment.ends_in_quotes && !assessment.suspect_final_arg);
+    }
+}
@@ -10,7 +10,8 @@
 //! * Two parsing functions, [`Args::parse_cmd`] and [`Args::parse_args`].
 //!     * These differ in how they parse the first argument, and in how they treat empty input.
 //!
-//! `ArgsOs` is only available on Windows.
+//! `ArgsOs` is only available on Windows.  [`ArgsWtf8`] offers the same lossless treatment of
+//! unpaired surrogates on every platform, for input stored as [`wtf8::Wtf8`].
 //!
 //! ```rust
 //! use windows_args::Args;
@@ -28,29 +29,142 @@
 //! assert_eq!(args.next(), Some("now".to_string()));
 //! assert_eq!(args.next(), None);
 //! ```
+//!
+//! # Performance
+//!
+//! Parsing takes time linear in the length of the input, and uses memory proportional to the
+//! size of the input and output.  This holds for every input, including adversarial ones such
+//! as long runs of backslashes or quotes, so it is safe to parse untrusted command lines.
 
-#[cfg(windows)]
 use std::ffi::{OsStr, OsString};
+#[cfg(windows)]
+use std::borrow::Cow;
+use std::collections::TryReserveError;
 use std::fmt;
-use crate::args::ArgsWtf8;
+use std::io;
+use std::iter::{FromIterator, FusedIterator};
+use std::string::FromUtf16Error;
+use std::ops::{self, RangeBounds};
+use std::path::Path;
+use crate::args::slice_range_panic;
+use crate::nul::check_no_nul_in;
 use wtf8::{Wtf8, Wtf8Buf};
 
 mod wtf8like;
 mod args;
+mod args0;
+mod bytes;
+mod cmdline;
+mod command;
+mod config;
+mod crt;
+mod expand;
+mod lines;
+mod nul;
+#[cfg(windows)]
+mod buffer;
+mod prepend;
+mod quote;
+mod response;
+mod rust_std;
+#[cfg(test)]
+mod short_strings;
+mod split;
+mod surrogate;
+mod trim;
+mod wildcard;
+#[cfg(windows)]
+mod detect;
+#[cfg(windows)]
+mod process;
+#[cfg(all(windows, feature = "process"))]
+mod remote;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(all(windows, feature = "widestring"))]
+mod widestr;
+#[cfg(all(windows, feature = "windows-strings"))]
+mod winstr;
+pub mod batch;
+pub mod cmd;
+pub mod edit;
+pub mod forensics;
+pub mod msvc;
+#[cfg(all(windows, feature = "nt"))]
+pub mod nt;
+pub mod posix;
+pub mod powershell;
+#[cfg(feature = "proptest")]
+pub mod testing;
+
+pub use crate::args::ParseScratch;
+pub use crate::args0::{Args0Encoding, Args0Error, Args0Options, NulInArgError};
+pub use crate::bytes::{Endianness, Utf16BytesError, Utf8CmdError};
+pub use crate::cmdline::AsCmdLine;
+pub use crate::command::{Command, FromStrError};
+pub use crate::config::{Compat, DelimiterPolicy, LimitKind, ParseError, ParserConfig, TrailingEmpty};
+pub use crate::expand::{expand_env_vars, ExpandScope};
+#[cfg(windows)]
+pub use crate::buffer::CmdLineBuffer;
+#[cfg(windows)]
+pub use crate::detect::DetectedQuirks;
+#[cfg(windows)]
+pub use crate::process::{args, args_os};
+pub use crate::lines::{parse_lines_args, parse_lines_cmd};
+pub use crate::nul::InteriorNulError;
+pub use crate::prepend::{prepend_cmd, PrependCmdError};
+pub use crate::quote::Quoted;
+pub use crate::response::{ResponseFileError, ResponseFileOptions, RspSyntax};
+pub use crate::split::{exe_token, split_exe};
+pub use crate::surrogate::{NonUnicodeArg, SurrogatePolicy};
+pub use crate::trim::TrimOptions;
+#[cfg(windows)]
+pub use crate::split::{exe_token_os, split_exe_os};
+#[cfg(windows)]
+pub use crate::quote::QuotedOs;
+pub use wtf8;
 
 /// An iterator over the arguments of a process, yielding a [`String`] value for
 /// each argument.
 ///
+/// Comparison and hashing consider only the arguments that remain, so a partially consumed
+/// iterator equals a fresh one that holds the same remaining arguments:
+///
+/// ```
+/// use windows_args::Args;
+///
+/// let mut args = Args::parse_cmd("app.exe a b");
+/// assert_ne!(args, Args::parse_args("a b"));
+/// args.next();
+/// assert_eq!(args, Args::parse_args("a b"));
+/// ```
+///
 /// [`String`]: ../string/struct.String.html
-pub struct Args { inner: ArgsWtf8<Wtf8Buf> }
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Args { inner: args::ArgsWtf8<Wtf8Buf> }
 
 /// **Windows only.**
 /// An iterator over the arguments of a process, yielding an [`OsString`] value
 /// for each argument.
 ///
+/// Like [`Args`], it compares and hashes by the arguments that remain.
+///
 /// [`OsString`]: ../ffi/struct.OsString.html
+/// [`Args`]: struct.Args.html
 #[cfg(windows)]
-pub struct ArgsOs { inner: ArgsWtf8<OsString> }
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ArgsOs { inner: args::ArgsWtf8<OsString> }
+
+/// An iterator over the arguments of a process, yielding a [`Wtf8Buf`] value for
+/// each argument.
+///
+/// Like `ArgsOs`, this preserves unpaired surrogates, but it is available on every
+/// platform.  The [`wtf8`] crate is re-exported so that the versions match.
+///
+/// [`Wtf8Buf`]: https://docs.rs/wtf8/0.0.3/wtf8/struct.Wtf8Buf.html
+/// [`wtf8`]: https://docs.rs/wtf8/0.0.3/wtf8/
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ArgsWtf8 { inner: args::ArgsWtf8<Wtf8Buf> }
 
 #[cfg(windows)]
 impl ArgsOs {
@@ -69,7 +183,28 @@ impl ArgsOs {
     /// );
     /// ```
     pub fn parse_cmd(input: &OsStr) -> Self {
-        ArgsOs { inner: ArgsWtf8::parse_cmd(input) }
+        ArgsOs { inner: args::ArgsWtf8::parse_cmd(input) }
+    }
+
+    /// Parse a complete command line given in any of the encodings supported by [`AsCmdLine`].
+    ///
+    /// This behaves like [`ArgsOs::parse_cmd`], but dispatches on the type of the input.
+    ///
+    /// ```rust
+    /// use std::ffi::{OsStr, OsString};
+    /// use windows_args::{ArgsOs, wtf8::Wtf8};
+    ///
+    /// let expected = vec!["app.exe".into(), "a b".into()] as Vec<OsString>;
+    /// let wide = r#"app.exe "a b""#.encode_utf16().collect::<Vec<u16>>();
+    /// assert_eq!(ArgsOs::parse(r#"app.exe "a b""#).collect::<Vec<_>>(), expected);
+    /// assert_eq!(ArgsOs::parse(OsStr::new(r#"app.exe "a b""#)).collect::<Vec<_>>(), expected);
+    /// assert_eq!(ArgsOs::parse(&wide[..]).collect::<Vec<_>>(), expected);
+    /// assert_eq!(ArgsOs::parse(Wtf8::from_str(r#"app.exe "a b""#)).collect::<Vec<_>>(), expected);
+    /// ```
+    ///
+    /// [`AsCmdLine`]: trait.AsCmdLine.html
+    pub fn parse(input: impl AsCmdLine) -> Self {
+        ArgsOs { inner: input.cmd_line().parse_cmd() }
     }
 
     /// Parse an [`OsStr`] containing whitespace-separated arguments to an executable.
@@ -87,13 +222,231 @@ impl ArgsOs {
     /// );
     /// ```
     pub fn parse_args(input: &OsStr) -> Self {
-        parse_args_via_parse_cmd(
-            input,
-            ArgsOs::parse_cmd,
-            OsString::with_capacity,
-            |buf, s| buf.push(s),
-            OsStr::len,
-        )
+        ArgsOs { inner: args::ArgsWtf8::parse_args(input) }
+    }
+
+    /// Like [`ArgsOs::parse_cmd`], but first trims trailing NULs and line breaks.
+    ///
+    /// See [`Args::parse_cmd_trimmed`] for details.
+    ///
+    /// [`Args::parse_cmd_trimmed`]: struct.Args.html#method.parse_cmd_trimmed
+    pub fn parse_cmd_trimmed(input: &OsStr, opts: &TrimOptions) -> Self {
+        ArgsOs::parse_cmd(trim::trim_os(input, opts))
+    }
+
+    /// Like [`ArgsOs::parse_cmd`], but applies a [`SurrogatePolicy`] to arguments that are
+    /// not valid Unicode.
+    ///
+    /// With [`SurrogatePolicy::Keep`], this is the same as [`ArgsOs::parse_cmd`].
+    ///
+    /// [`SurrogatePolicy`]: enum.SurrogatePolicy.html
+    /// [`SurrogatePolicy::Keep`]: enum.SurrogatePolicy.html#variant.Keep
+    pub fn parse_cmd_surrogates(input: &OsStr, policy: SurrogatePolicy) -> Result<Self, NonUnicodeArg> {
+        match policy {
+            SurrogatePolicy::Keep => Ok(ArgsOs::parse_cmd(input)),
+            _ => {
+                let args = surrogate::parse_os_to_strings(input, args::Mode::Cmd, policy)?;
+                Ok(ArgsOs { inner: args::ArgsWtf8::from_vec(args.into_iter().map(OsString::from).collect()) })
+            },
+        }
+    }
+
+    /// Like [`ArgsOs::parse_cmd`], but first expands `%NAME%` references to environment
+    /// variables, as by [`expand_env_vars`].
+    ///
+    /// [`expand_env_vars`]: fn.expand_env_vars.html
+    pub fn parse_cmd_expanded(
+        input: &OsStr,
+        scope: ExpandScope,
+        lookup: impl FnMut(&str) -> Option<OsString>,
+    ) -> Self {
+        ArgsOs::parse_cmd(&expand_env_vars(input, scope, lookup))
+    }
+
+    /// Like [`ArgsOs::parse_cmd`], but fails if the input contains a NUL.
+    ///
+    /// [`ArgsOs::parse_cmd`] stops at the first NUL and silently ignores anything after it.
+    /// This is the function to use on untrusted input, where that could hide arguments.
+    ///
+    /// ```rust
+    /// let err = windows_args::ArgsOs::try_parse_cmd("a.exe x\0--hidden".as_ref()).unwrap_err();
+    /// assert_eq!(err.position(), 7);
+    /// ```
+    pub fn try_parse_cmd(input: &OsStr) -> Result<Self, InteriorNulError> {
+        use std::os::windows::ffi::OsStrExt;
+
+        check_no_nul_in(input.encode_wide())?;
+        Ok(ArgsOs::parse_cmd(input))
+    }
+
+    /// Like [`ArgsOs::parse_args`], but fails if the input contains a NUL.
+    ///
+    /// The reported position is relative to `input`.
+    pub fn try_parse_args(input: &OsStr) -> Result<Self, InteriorNulError> {
+        use std::os::windows::ffi::OsStrExt;
+
+        check_no_nul_in(input.encode_wide())?;
+        Ok(ArgsOs::parse_args(input))
+    }
+
+    /// Parse a complete command line given as UTF-16 code units, which may contain unpaired
+    /// surrogates.
+    ///
+    /// No terminator is required, and the input is parsed in place.  It is still copied once,
+    /// into the WTF-8 string kept for [`ArgsOs::raw`].  Like with
+    /// [`ArgsOs::parse_cmd`], parsing stops at the first NUL, if any, so trailing NUL padding
+    /// is ignored.
+    ///
+    /// ```rust
+    /// use std::ffi::OsString;
+    ///
+    /// let wide = "me.exe a\0b".encode_utf16().collect::<Vec<_>>();
+    /// let args = windows_args::ArgsOs::parse_cmd_wide(&wide);
+    /// assert_eq!(args.collect::<Vec<_>>(), vec!["me.exe".into(), "a".into()] as Vec<OsString>);
+    /// ```
+    pub fn parse_cmd_wide(input: &[u16]) -> Self {
+        ArgsOs { inner: args::ArgsWtf8::parse_cmd_wide(input) }
+    }
+
+    /// Like [`ArgsOs::parse_cmd_wide`], but reads the code units one at a time from an iterator.
+    ///
+    /// Parsing stops at the first NUL or when the iterator is exhausted, and the iterator is
+    /// not advanced past that point.  The input is never collected into a buffer, so this suits
+    /// input that is decoded lazily.
+    ///
+    /// ```rust
+    /// use std::ffi::OsString;
+    ///
+    /// let args = windows_args::ArgsOs::parse_cmd_units("me.exe \"a b\"".encode_utf16());
+    /// assert_eq!(args.collect::<Vec<_>>(), vec!["me.exe".into(), "a b".into()] as Vec<OsString>);
+    /// ```
+    pub fn parse_cmd_units(input: impl IntoIterator<Item=u16>) -> Self {
+        ArgsOs { inner: args::ArgsWtf8::parse_cmd_units(input) }
+    }
+
+    /// Like [`ArgsOs::parse_cmd_wide`], but takes ownership of the buffer.
+    ///
+    /// The buffer is parsed in place and then dropped.  As with [`ArgsOs::parse_cmd_wide`],
+    /// a WTF-8 copy of it is kept for [`ArgsOs::raw`], so this saves no memory over borrowing.
+    pub fn parse_cmd_wide_owned(input: Vec<u16>) -> Self {
+        ArgsOs::parse_cmd_wide(&input)
+    }
+
+    /// Parse whitespace-separated arguments given as UTF-16 code units.
+    ///
+    /// This is to [`ArgsOs::parse_cmd_wide`] what [`ArgsOs::parse_args`] is to
+    /// [`ArgsOs::parse_cmd`].
+    pub fn parse_args_wide(input: &[u16]) -> Self {
+        ArgsOs { inner: args::ArgsWtf8::parse_args_wide(input) }
+    }
+
+    /// Parse a complete command line given as little-endian UTF-16 bytes.
+    ///
+    /// A leading byte order mark is removed, and the buffer need not be aligned.  Unpaired
+    /// surrogates are preserved, and parsing stops at the first NUL code unit, if any.
+    ///
+    /// ```rust
+    /// use std::ffi::OsString;
+    ///
+    /// let bytes = b"\xff\xfea\0 \0b\0";
+    /// let args = windows_args::ArgsOs::parse_cmd_utf16le_bytes(bytes)?;
+    /// assert_eq!(args.collect::<Vec<_>>(), vec!["a".into(), "b".into()] as Vec<OsString>);
+    /// # Ok::<(), windows_args::Utf16BytesError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the buffer has an odd length.
+    pub fn parse_cmd_utf16le_bytes(input: &[u8]) -> Result<Self, Utf16BytesError> {
+        ArgsOs::parse_cmd_utf16_bytes(input, Endianness::Little)
+    }
+
+    /// Like [`ArgsOs::parse_cmd_utf16le_bytes`], but for big-endian UTF-16.
+    pub fn parse_cmd_utf16be_bytes(input: &[u8]) -> Result<Self, Utf16BytesError> {
+        ArgsOs::parse_cmd_utf16_bytes(input, Endianness::Big)
+    }
+
+    /// Like [`ArgsOs::parse_cmd_utf16le_bytes`], but with the byte order guessed by
+    /// [`Endianness::sniff`].
+    pub fn parse_cmd_utf16_bytes_auto(input: &[u8]) -> Result<Self, Utf16BytesError> {
+        ArgsOs::parse_cmd_utf16_bytes(input, Endianness::sniff(input))
+    }
+
+    /// Like [`ArgsOs::parse_cmd_utf16le_bytes`], but with the given byte order.
+    pub fn parse_cmd_utf16_bytes(input: &[u8], endianness: Endianness) -> Result<Self, Utf16BytesError> {
+        bytes::decode_utf16_bytes(input, endianness, true).map(ArgsOs::parse_cmd_wide_owned)
+    }
+
+    /// Parse the NUL-terminated command line at `ptr`, such as the one returned by
+    /// `GetCommandLineW`.
+    ///
+    /// The terminator is found while parsing, and the command line is parsed in place; it is
+    /// only copied into the string kept for [`ArgsOs::raw`].
+    /// A null pointer is treated like an empty command line.
+    ///
+    /// # Safety
+    ///
+    /// Unless it is null, `ptr` must be properly aligned and point to a sequence of `u16`s
+    /// terminated by a `0`, all of which must be valid for reads and must not be modified for
+    /// the duration of the call.
+    ///
+    /// ```rust
+    /// use std::ffi::OsString;
+    ///
+    /// let wide = "me.exe a\0".encode_utf16().collect::<Vec<_>>();
+    /// let args = unsafe { windows_args::ArgsOs::from_lpcmdline(wide.as_ptr()) };
+    /// assert_eq!(args.collect::<Vec<_>>(), vec!["me.exe".into(), "a".into()] as Vec<OsString>);
+    /// ```
+    pub unsafe fn from_lpcmdline(ptr: *const u16) -> Self {
+        if ptr.is_null() {
+            return ArgsOs::parse_cmd_wide(&[]);
+        }
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        ArgsOs::parse_cmd_wide(std::slice::from_raw_parts(ptr, len))
+    }
+
+    /// Like [`ArgsOs::parse_cmd`], but writes the arguments into an existing vector.
+    ///
+    /// `out` is cleared first, and its elements are overwritten in place so that their
+    /// capacity gets reused.  (any elements beyond the new length are dropped)  Together
+    /// with a [`ParseScratch`] that is kept around between calls, this allows parsing many
+    /// command lines without allocating in the steady state.
+    ///
+    /// ```rust
+    /// use std::ffi::OsString;
+    /// use windows_args::{ArgsOs, ParseScratch};
+    ///
+    /// let mut scratch = ParseScratch::new();
+    /// let mut out = vec![];
+    /// for line in &["a.exe b", "c.exe \"d e\""] {
+    ///     ArgsOs::parse_cmd_into(line.as_ref(), &mut out, &mut scratch);
+    ///     assert_eq!(out.len(), 2);
+    /// }
+    /// assert_eq!(out, vec!["c.exe".into(), "d e".into()] as Vec<OsString>);
+    /// ```
+    pub fn parse_cmd_into(input: &OsStr, out: &mut Vec<OsString>, scratch: &mut ParseScratch) {
+        args::parse_cmd_into(input, out, scratch)
+    }
+
+    /// Like [`ArgsOs::parse_cmd`], but reports allocation failure instead of aborting.
+    ///
+    /// Every buffer grown while parsing is grown through `try_reserve`.
+    ///
+    /// ```rust
+    /// let args = windows_args::ArgsOs::try_parse_cmd_reserving("test  \" \"".as_ref())?;
+    /// assert_eq!(args.len(), 2);
+    /// # Ok::<(), std::collections::TryReserveError>(())
+    /// ```
+    pub fn try_parse_cmd_reserving(input: &OsStr) -> Result<Self, TryReserveError> {
+        args::ArgsWtf8::try_parse_cmd_reserving(input).map(|inner| ArgsOs { inner })
+    }
+
+    /// Like [`ArgsOs::parse_args`], but reports allocation failure instead of aborting.
+    pub fn try_parse_args_reserving(input: &OsStr) -> Result<Self, TryReserveError> {
+        args::ArgsWtf8::try_parse_args_reserving(input).map(|inner| ArgsOs { inner })
     }
 }
 
@@ -111,7 +464,68 @@ impl Args {
     /// );
     /// ```
     pub fn parse_cmd(input: &str) -> Self {
-        Args { inner: ArgsWtf8::parse_cmd(Wtf8::from_str(input)) }
+        Args { inner: args::ArgsWtf8::parse_cmd_str(input) }
+    }
+
+    /// Like [`Args::parse_cmd`], but with the executable name separated from the arguments,
+    /// and `None` in place of a placeholder name when there is none.
+    ///
+    /// The executable name is `None` only when the input (up to the first NUL) is empty or
+    /// consists of nothing but spaces and tabs, in which case there are no arguments either.
+    /// Input that merely *begins* with whitespace has an empty executable name, as in
+    /// `CommandLineToArgvW`.
+    ///
+    /// ```
+    /// use windows_args::Args;
+    ///
+    /// let (exe, args) = Args::parse_cmd_opt("app.exe a b");
+    /// assert_eq!(exe.as_deref(), Some("app.exe"));
+    /// assert_eq!(args.collect::<Vec<_>>(), ["a", "b"]);
+    ///
+    /// let (exe, args) = Args::parse_cmd_opt("  ");
+    /// assert_eq!((exe, args.len()), (None, 0));
+    ///
+    /// let (exe, args) = Args::parse_cmd_opt(" foo");
+    /// assert_eq!(exe.as_deref(), Some(""));
+    /// assert_eq!(args.collect::<Vec<_>>(), ["foo"]);
+    /// ```
+    ///
+    /// [`Args::parse_cmd`]: #method.parse_cmd
+    pub fn parse_cmd_opt(input: &str) -> (Option<String>, Self) {
+        let mut args = Args::parse_cmd(input);
+        let exe = args.next();
+        let blank = input.split('\0').next().unwrap_or("").bytes().all(|c| c == b' ' || c == b'\t');
+        match blank {
+            true => (None, args),
+            false => (exe, args),
+        }
+    }
+
+    /// Parse a complete command line given in any of the encodings supported by [`AsCmdLine`].
+    ///
+    /// This behaves like [`Args::parse_cmd`], but dispatches on the type of the input.
+    ///
+    /// ```
+    /// use windows_args::{Args, wtf8::Wtf8};
+    ///
+    /// let wide = r#"app.exe "a b""#.encode_utf16().collect::<Vec<u16>>();
+    /// assert_eq!(Args::parse(r#"app.exe "a b""#).collect::<Vec<_>>(), ["app.exe", "a b"]);
+    /// assert_eq!(Args::parse(&wide[..]).collect::<Vec<_>>(), ["app.exe", "a b"]);
+    /// assert_eq!(Args::parse(Wtf8::from_str(r#"app.exe "a b""#)).collect::<Vec<_>>(), ["app.exe", "a b"]);
+    /// # #[cfg(windows)]
+    /// assert_eq!(Args::parse(std::ffi::OsStr::new(r#"app.exe "a b""#)).collect::<Vec<_>>(), ["app.exe", "a b"]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Like `std::env::args`, the iterator panics if it reaches an argument that is not valid
+    /// Unicode.  Use [`ArgsOs::parse`] (or a checked function such as [`Args::parse_cmd_wide`])
+    /// for input that might contain unpaired surrogates.
+    ///
+    /// [`AsCmdLine`]: trait.AsCmdLine.html
+    /// [`ArgsOs::parse`]: struct.ArgsOs.html#method.parse
+    pub fn parse(input: impl AsCmdLine) -> Self {
+        Args { inner: input.cmd_line().parse_cmd_wtf8() }
     }
 
     /// Parse a string containing whitespace-separated arguments to an executable.
@@ -127,114 +541,1890 @@ impl Args {
     /// );
     /// ```
     pub fn parse_args(input: &str) -> Self {
-        parse_args_via_parse_cmd(
-            input,
-            Args::parse_cmd,
-            String::with_capacity,
-            String::push_str,
-            str::len,
-        )
+        Args { inner: args::ArgsWtf8::parse_args_str(input) }
     }
-}
-
-fn expect_still_utf8(arg: Wtf8Buf) -> String {
-    arg.into_string().unwrap_or_else(|arg| {
-        panic!("\
-valid UTF-8 became invalid after arg splitting?!
-BadArg: {:?}\
-", arg);
-    })
-}
-
-impl Iterator for Args {
-    type Item = String;
-    fn next(&mut self) -> Option<String> { self.inner.next().map(expect_still_utf8) }
-    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
-}
 
-impl ExactSizeIterator for Args {
-    fn len(&self) -> usize { self.inner.len() }
-}
-
-impl DoubleEndedIterator for Args {
-    fn next_back(&mut self) -> Option<String> { self.inner.next_back().map(expect_still_utf8) }
-}
+    /// Parse a complete command line given as UTF-8 bytes.
+    ///
+    /// A leading byte order mark is removed.  The input is validated once and then parsed
+    /// in place, without first being copied into a `String`.
+    ///
+    /// ```
+    /// let args = windows_args::Args::parse_cmd_utf8(b"\xef\xbb\xbfme.exe \"a b\"")?;
+    /// assert_eq!(args.collect::<Vec<_>>(), ["me.exe", "a b"]);
+    /// # Ok::<(), windows_args::Utf8CmdError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the input is not valid UTF-8.
+    pub fn parse_cmd_utf8(input: &[u8]) -> Result<Self, Utf8CmdError> {
+        bytes::decode_utf8_bytes(input).map(Args::parse_cmd)
+    }
 
-impl fmt::Debug for Args {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Args")
-            .field("inner", &self.inner.inner_debug())
-            .finish()
+    /// Parse whitespace-separated arguments given as UTF-8 bytes.
+    ///
+    /// This is to [`Args::parse_cmd_utf8`] what [`Args::parse_args`] is to [`Args::parse_cmd`].
+    pub fn parse_args_utf8(input: &[u8]) -> Result<Self, Utf8CmdError> {
+        bytes::decode_utf8_bytes(input).map(Args::parse_args)
     }
-}
 
-#[cfg(windows)]
-impl Iterator for ArgsOs {
-    type Item = OsString;
-    fn next(&mut self) -> Option<OsString> { self.inner.next() }
-    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
-}
+    /// Like [`Args::parse_cmd`], but first trims trailing NULs and line breaks.
+    ///
+    /// This is meant for command lines read from places like the registry, which often end in
+    /// `\r\n` or in padding NULs.  With [`TrimOptions::stop_at_newline`], everything after the
+    /// first line break is ignored as well.  Tabs and spaces are parsed as usual.
+    ///
+    /// ```
+    /// use windows_args::{Args, TrimOptions};
+    ///
+    /// let opts = TrimOptions { stop_at_newline: true };
+    /// let args = Args::parse_cmd_trimmed("app.exe /s\r\nrem junk\0\0", &opts);
+    /// assert_eq!(args.collect::<Vec<_>>(), ["app.exe", "/s"]);
+    /// ```
+    ///
+    /// [`TrimOptions::stop_at_newline`]: struct.TrimOptions.html#structfield.stop_at_newline
+    pub fn parse_cmd_trimmed(input: &str, opts: &TrimOptions) -> Self {
+        Args::parse_cmd(trim::trim_str(input, opts))
+    }
 
-#[cfg(windows)]
-impl ExactSizeIterator for ArgsOs {
-    fn len(&self) -> usize { self.inner.len() }
-}
+    /// Like [`Args::parse_cmd`], but fails if the input ends inside a quoted region.
+    ///
+    /// [`Args::parse_cmd`] silently supplies a missing closing quote, so that a typo can turn
+    /// the rest of the line into part of one argument.  This is the function to use on command
+    /// lines written by hand.  Input that parses without error produces the same arguments as
+    /// [`Args::parse_cmd`].
+    ///
+    /// ```
+    /// use windows_args::{Args, ParseError};
+    ///
+    /// let err = Args::parse_cmd_strict(r#"run "C:\Program Files\app.exe"#).unwrap_err();
+    /// assert_eq!(err, ParseError::UnterminatedQuote { offset: 4 });
+    /// ```
+    ///
+    /// This is shorthand for [`ParserConfig::strict_quotes`].
+    ///
+    /// [`ParserConfig::strict_quotes`]: struct.ParserConfig.html#method.strict_quotes
+    pub fn parse_cmd_strict(input: &str) -> Result<Self, ParseError> {
+        ParserConfig::new().strict_quotes(true).parse_cmd(input)
+    }
 
-#[cfg(windows)]
-impl DoubleEndedIterator for ArgsOs {
-    fn next_back(&mut self) -> Option<OsString> { self.inner.next_back() }
-}
+    /// Like [`Args::parse_args`], but fails if the input ends inside a quoted region.
+    ///
+    /// This is to [`Args::parse_args`] what [`Args::parse_cmd_strict`] is to [`Args::parse_cmd`].
+    pub fn parse_args_strict(input: &str) -> Result<Self, ParseError> {
+        ParserConfig::new().strict_quotes(true).parse_args(input)
+    }
 
-#[cfg(windows)]
-impl fmt::Debug for ArgsOs {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("ArgsOs")
-            .field("inner", &self.inner.inner_debug())
-            .finish()
+    /// Parse a complete command line given as an `OsStr`, converting each argument to a
+    /// `String` according to a [`SurrogatePolicy`].
+    ///
+    /// This is for input that may have been through a lossy conversion, such as command lines
+    /// recovered from event logs.  Unlike the iterators produced by [`Args::parse`], the
+    /// output of this function never panics, because every argument has already been
+    /// converted.
+    ///
+    /// ```
+    /// use windows_args::{Args, SurrogatePolicy};
+    ///
+    /// let args = Args::parse_cmd_os(r#"app.exe "a b""#.as_ref(), SurrogatePolicy::Error)?;
+    /// assert_eq!(args.collect::<Vec<_>>(), ["app.exe", "a b"]);
+    /// # Ok::<(), windows_args::NonUnicodeArg>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// With [`SurrogatePolicy::Error`] or [`SurrogatePolicy::Keep`], fails on the first
+    /// argument that is not valid Unicode.
+    ///
+    /// [`SurrogatePolicy`]: enum.SurrogatePolicy.html
+    /// [`SurrogatePolicy::Error`]: enum.SurrogatePolicy.html#variant.Error
+    /// [`SurrogatePolicy::Keep`]: enum.SurrogatePolicy.html#variant.Keep
+    pub fn parse_cmd_os(input: &OsStr, policy: SurrogatePolicy) -> Result<Self, NonUnicodeArg> {
+        let args = surrogate::parse_os_to_strings(input, args::Mode::Cmd, policy)?;
+        Ok(Args { inner: args::ArgsWtf8::from_vec(args.into_iter().map(Wtf8Buf::from_string).collect()) })
     }
-}
 
-fn parse_args_via_parse_cmd<A, OwnS, RefS: ?Sized>(
-    input: &RefS,
-    parse_cmd: impl FnOnce(&RefS) -> A,
-    with_capacity: impl FnOnce(usize) -> OwnS,
-    push_str: impl Fn(&mut OwnS, &RefS),
-    len: impl Fn(&RefS) -> usize,
-) -> A
-where
-    A: Iterator,
-    OwnS: std::ops::Deref<Target=RefS>,
-    str: AsRef<RefS>,
-{
-    // Prepend a command name
-    let mut modified_input = with_capacity(len(input) + 2);
-    push_str(&mut modified_input, "a ".as_ref());
-    push_str(&mut modified_input, input);
+    /// Like [`Args::parse_cmd_os`], for arguments to an executable.
+    ///
+    /// This is to [`Args::parse_cmd_os`] what [`Args::parse_args`] is to [`Args::parse_cmd`].
+    pub fn parse_args_os(input: &OsStr, policy: SurrogatePolicy) -> Result<Self, NonUnicodeArg> {
+        let args = surrogate::parse_os_to_strings(input, args::Mode::Args, policy)?;
+        Ok(Args { inner: args::ArgsWtf8::from_vec(args.into_iter().map(Wtf8Buf::from_string).collect()) })
+    }
 
-    // Skip the command name in the output
-    let mut out = parse_cmd(&modified_input);
-    out.next();
+    /// Like [`Args::parse_cmd`], but first expands `%NAME%` references to environment
+    /// variables, as by [`expand_env_vars`].
+    ///
+    /// ```
+    /// use windows_args::{Args, ExpandScope};
+    ///
+    /// let lookup = |name: &str| match name {
+    ///     "SystemRoot" => Some(r"C:\Windows".into()),
+    ///     _ => None,
+    /// };
+    /// let args = Args::parse_cmd_expanded(r"%SystemRoot%\notepad.exe %TEMP%", ExpandScope::Exe, lookup)?;
+    /// assert_eq!(args.collect::<Vec<_>>(), [r"C:\Windows\notepad.exe", "%TEMP%"]);
+    /// # Ok::<(), windows_args::NonUnicodeArg>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if an argument is not valid Unicode after expansion.
+    ///
+    /// [`expand_env_vars`]: fn.expand_env_vars.html
+    pub fn parse_cmd_expanded(
+        input: &str,
+        scope: ExpandScope,
+        lookup: impl FnMut(&str) -> Option<OsString>,
+    ) -> Result<Self, NonUnicodeArg> {
+        Args::parse_cmd_os(&expand_env_vars(input.as_ref(), scope, lookup), SurrogatePolicy::Error)
+    }
 
-    out
-}
+    /// Like [`Args::parse_cmd`], but replaces each argument of the form `@path` with the
+    /// arguments in that response file, as compilers and linkers do.
+    ///
+    /// Files are read with `loader`, so that this can be tested without touching the
+    /// filesystem; `std::fs::read_to_string` is the usual choice.  By default, each line of a
+    /// file is split with the rules of [`Args::parse_args`] (so a quoted region ends with its
+    /// line); see [`RspSyntax`] for the alternative.  A leading byte order mark is skipped.
+    /// Response files may refer to other response files.
+    ///
+    /// Like `cl.exe`, an argument is expanded even if the `@` was quoted, because only the
+    /// parsed argument is considered.  The executable name is never expanded, and neither is
+    /// an argument that is just `@`.
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::path::Path;
+    /// use windows_args::{Args, ResponseFileOptions};
+    ///
+    /// let loader = |path: &Path| match path.to_str() {
+    ///     Some("args.rsp") => Ok("/nologo \"C:\\My Files\\x.c\"\r\n/O2".to_string()),
+    ///     _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+    /// };
+    /// let args = Args::parse_cmd_with_response_files("cl.exe @args.rsp /c", &ResponseFileOptions::default(), loader)?;
+    /// assert_eq!(args.collect::<Vec<_>>(), ["cl.exe", "/nologo", r"C:\My Files\x.c", "/O2", "/c"]);
+    /// # Ok::<(), windows_args::ResponseFileError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if `loader` fails, if a response file includes itself (directly or indirectly),
+    /// or if response files are nested deeper than [`ResponseFileOptions::max_depth`].
+    ///
+    /// [`ResponseFileOptions::max_depth`]: struct.ResponseFileOptions.html#structfield.max_depth
+    /// [`RspSyntax`]: enum.RspSyntax.html
+    pub fn parse_cmd_with_response_files(
+        input: &str,
+        opts: &ResponseFileOptions,
+        loader: impl FnMut(&Path) -> io::Result<String>,
+    ) -> Result<Self, ResponseFileError> {
+        let args = response::expand_response_files(Args::parse_cmd(input), opts, loader)?;
+        Ok(Args { inner: args::ArgsWtf8::from_vec(args.into_iter().map(Wtf8Buf::from_string).collect()) })
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Like [`Args::parse_cmd`], but fails if the input contains a NUL.
+    ///
+    /// [`Args::parse_cmd`] stops at the first NUL and silently ignores anything after it.
+    /// This is the function to use on untrusted input, where that could hide arguments.
+    ///
+    /// ```
+    /// let err = windows_args::Args::try_parse_cmd("a.exe x\0--hidden").unwrap_err();
+    /// assert_eq!(err.position(), 7);
+    /// ```
+    pub fn try_parse_cmd(input: &str) -> Result<Self, InteriorNulError> {
+        check_no_nul_in(input.encode_utf16())?;
+        Ok(Args::parse_cmd(input))
+    }
 
-    #[test]
-    fn special_traits() {
-        assert_eq!(Args::parse_cmd("a b").next_back(), Some("b".into()));
-        assert_eq!(Args::parse_cmd("a b").len(), 2);
+    /// Like [`Args::parse_args`], but fails if the input contains a NUL.
+    ///
+    /// The reported position is relative to `input`, in UTF-16 code units.
+    pub fn try_parse_args(input: &str) -> Result<Self, InteriorNulError> {
+        check_no_nul_in(input.encode_utf16())?;
+        Ok(Args::parse_args(input))
     }
 
-    #[cfg(windows)]
-    #[test]
-    fn special_traits_windows() {
+    /// Parse a complete command line given as UTF-16 code units.
+    ///
+    /// No terminator is required.  Like with [`Args::parse_cmd`], parsing stops at the
+    /// first NUL, if any.
+    ///
+    /// ```
+    /// let wide = "me.exe a\0b".encode_utf16().collect::<Vec<_>>();
+    /// let args = windows_args::Args::parse_cmd_wide(&wide).unwrap();
+    /// assert_eq!(args.collect::<Vec<_>>(), ["me.exe", "a"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the input contains an unpaired surrogate (even after a NUL).
+    pub fn parse_cmd_wide(input: &[u16]) -> Result<Self, FromUtf16Error> {
+        check_utf16(input)?;
+        Ok(Args { inner: args::ArgsWtf8::parse_cmd_wide(input) })
+    }
+
+    /// Like [`Args::parse_cmd_wide`], but takes ownership of the buffer.
+    ///
+    /// The buffer is parsed in place and then dropped.  A UTF-8 copy of it is kept for
+    /// [`Args::raw`], as with [`Args::parse_cmd_wide`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the input contains an unpaired surrogate (even after a NUL).
+    pub fn parse_cmd_wide_owned(input: Vec<u16>) -> Result<Self, FromUtf16Error> {
+        Args::parse_cmd_wide(&input)
+    }
+
+    /// Parse a complete command line given as little-endian UTF-16 bytes.
+    ///
+    /// A leading byte order mark is removed, and the buffer need not be aligned.  Parsing
+    /// stops at the first NUL code unit, if any.
+    ///
+    /// ```
+    /// let bytes = b"\xff\xfea\0 \0b\0";
+    /// let args = windows_args::Args::parse_cmd_utf16le_bytes(bytes)?;
+    /// assert_eq!(args.collect::<Vec<_>>(), ["a", "b"]);
+    /// # Ok::<(), windows_args::Utf16BytesError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the buffer has an odd length, or contains an unpaired surrogate.
+    pub fn parse_cmd_utf16le_bytes(input: &[u8]) -> Result<Self, Utf16BytesError> {
+        Args::parse_cmd_utf16_bytes(input, Endianness::Little)
+    }
+
+    /// Like [`Args::parse_cmd_utf16le_bytes`], but for big-endian UTF-16.
+    pub fn parse_cmd_utf16be_bytes(input: &[u8]) -> Result<Self, Utf16BytesError> {
+        Args::parse_cmd_utf16_bytes(input, Endianness::Big)
+    }
+
+    /// Like [`Args::parse_cmd_utf16le_bytes`], but with the byte order guessed by
+    /// [`Endianness::sniff`].
+    ///
+    /// ```
+    /// use windows_args::Args;
+    ///
+    /// assert_eq!(Args::parse_cmd_utf16_bytes_auto(b"\0a\0 \0b")?.collect::<Vec<_>>(), ["a", "b"]);
+    /// assert_eq!(Args::parse_cmd_utf16_bytes_auto(b"a\0 \0b\0")?.collect::<Vec<_>>(), ["a", "b"]);
+    /// # Ok::<(), windows_args::Utf16BytesError>(())
+    /// ```
+    pub fn parse_cmd_utf16_bytes_auto(input: &[u8]) -> Result<Self, Utf16BytesError> {
+        Args::parse_cmd_utf16_bytes(input, Endianness::sniff(input))
+    }
+
+    /// Like [`Args::parse_cmd_utf16le_bytes`], but with the given byte order.
+    pub fn parse_cmd_utf16_bytes(input: &[u8], endianness: Endianness) -> Result<Self, Utf16BytesError> {
+        let wide = bytes::decode_utf16_bytes(input, endianness, false)?;
+        Ok(Args { inner: args::ArgsWtf8::parse_cmd_wide(&wide) })
+    }
+
+    /// Parse whitespace-separated arguments given as UTF-16 code units.
+    ///
+    /// This is to [`Args::parse_cmd_wide`] what [`Args::parse_args`] is to
+    /// [`Args::parse_cmd`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the input contains an unpaired surrogate (even after a NUL).
+    pub fn parse_args_wide(input: &[u16]) -> Result<Self, FromUtf16Error> {
+        check_utf16(input)?;
+        Ok(Args { inner: args::ArgsWtf8::parse_args_wide(input) })
+    }
+
+    /// Like [`Args::parse_cmd`], but writes the arguments into an existing vector.
+    ///
+    /// `out` is cleared first, and its elements are overwritten in place so that their
+    /// capacity gets reused.  (any elements beyond the new length are dropped)  Together
+    /// with a [`ParseScratch`] that is kept around between calls, this allows parsing many
+    /// command lines without allocating in the steady state.
+    ///
+    /// ```
+    /// use windows_args::{Args, ParseScratch};
+    ///
+    /// let mut scratch = ParseScratch::new();
+    /// let mut out = vec![];
+    /// for line in &["a.exe b", r#"c.exe "d e""#] {
+    ///     Args::parse_cmd_into(line, &mut out, &mut scratch);
+    ///     assert_eq!(out.len(), 2);
+    /// }
+    /// assert_eq!(out, vec!["c.exe".to_string(), "d e".to_string()]);
+    /// ```
+    pub fn parse_cmd_into(input: &str, out: &mut Vec<String>, scratch: &mut ParseScratch) {
+        args::parse_cmd_into_str(input, out, scratch)
+    }
+
+    /// Like [`Args::parse_cmd`], but reports allocation failure instead of aborting.
+    ///
+    /// This is intended for services that parse untrusted input and would rather shed a
+    /// request than die.  Every buffer grown while parsing is grown through `try_reserve`.
+    ///
+    /// ```
+    /// let args = windows_args::Args::try_parse_cmd_reserving(r#"me.exe  \\\"#)?;
+    /// assert_eq!(
+    ///     args.collect::<Vec<_>>(),
+    ///     vec!["me.exe".to_string(), r#"\\\"#.to_string()],
+    /// );
+    /// # Ok::<(), std::collections::TryReserveError>(())
+    /// ```
+    pub fn try_parse_cmd_reserving(input: &str) -> Result<Self, TryReserveError> {
+        args::ArgsWtf8::try_parse_cmd_str_reserving(input).map(|inner| Args { inner })
+    }
+
+    /// Like [`Args::parse_args`], but reports allocation failure instead of aborting.
+    pub fn try_parse_args_reserving(input: &str) -> Result<Self, TryReserveError> {
+        args::ArgsWtf8::try_parse_args_str_reserving(input).map(|inner| Args { inner })
+    }
+}
+
+impl ArgsWtf8 {
+    /// Parse a [`Wtf8`] string containing the complete command line.
+    ///
+    /// The output will always contain at least one argument (representing the executable name).
+    /// If the input was empty, a placeholder name is given.
+    ///
+    /// ```
+    /// use windows_args::wtf8::{Wtf8, Wtf8Buf};
+    ///
+    /// let args = windows_args::ArgsWtf8::parse_cmd(Wtf8::from_str(r#"me.exe "a b" c"#));
+    /// assert_eq!(
+    ///     args.collect::<Vec<_>>(),
+    ///     ["me.exe", "a b", "c"].iter().map(|s| Wtf8Buf::from_str(s)).collect::<Vec<_>>(),
+    /// );
+    /// ```
+    ///
+    /// [`Wtf8`]: https://docs.rs/wtf8/0.0.3/wtf8/struct.Wtf8.html
+    pub fn parse_cmd(input: &Wtf8) -> Self {
+        ArgsWtf8 { inner: args::ArgsWtf8::parse_cmd(input) }
+    }
+
+    /// Parse UTF-16 code units from an iterator, as [`ArgsOs::parse_cmd_units`] does.
+    ///
+    /// Unpaired surrogates are preserved.
+    ///
+    /// [`ArgsOs::parse_cmd_units`]: struct.ArgsOs.html#method.parse_cmd_units
+    pub fn parse_cmd_units(input: impl IntoIterator<Item=u16>) -> Self {
+        ArgsWtf8 { inner: args::ArgsWtf8::parse_cmd_units(input) }
+    }
+
+    /// Parse a [`Wtf8`] string containing whitespace-separated arguments to an executable.
+    ///
+    /// This function is intended to be used for strings which **do not** begin with
+    /// the executable name.
+    ///
+    /// [`Wtf8`]: https://docs.rs/wtf8/0.0.3/wtf8/struct.Wtf8.html
+    pub fn parse_args(input: &Wtf8) -> Self {
+        ArgsWtf8 { inner: args::ArgsWtf8::parse_args(input) }
+    }
+
+    /// The remaining arguments, without consuming them.
+    pub fn as_slice(&self) -> &[Wtf8Buf] {
+        self.inner.as_slice()
+    }
+
+    /// Borrow one of the remaining arguments, like [`Args::get`].  `args[index]` does the
+    /// same, but panics if there are not that many arguments left.
+    ///
+    /// [`Args::get`]: struct.Args.html#method.get
+    pub fn get(&self, index: usize) -> Option<&Wtf8> {
+        self.as_slice().get(index).map(|arg| &**arg)
+    }
+}
+
+/// Indexing into the remaining arguments.  See [`ArgsWtf8::get`].
+///
+/// [`ArgsWtf8::get`]: struct.ArgsWtf8.html#method.get
+impl ops::Index<usize> for ArgsWtf8 {
+    type Output = Wtf8;
+
+    fn index(&self, index: usize) -> &Wtf8 {
+        &self.as_slice()[index]
+    }
+}
+
+impl Iterator for ArgsWtf8 {
+    type Item = Wtf8Buf;
+    fn next(&mut self) -> Option<Wtf8Buf> { self.inner.next() }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+    fn nth(&mut self, n: usize) -> Option<Wtf8Buf> { self.inner.nth(n) }
+    fn count(self) -> usize { self.inner.count() }
+    fn last(self) -> Option<Wtf8Buf> { self.inner.last() }
+}
+
+impl ExactSizeIterator for ArgsWtf8 {
+    fn len(&self) -> usize { self.inner.len() }
+}
+
+impl DoubleEndedIterator for ArgsWtf8 {
+    fn next_back(&mut self) -> Option<Wtf8Buf> { self.inner.next_back() }
+    fn nth_back(&mut self, n: usize) -> Option<Wtf8Buf> { self.inner.nth_back(n) }
+}
+
+impl FusedIterator for ArgsWtf8 {}
+
+impl fmt::Debug for ArgsWtf8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArgsWtf8")
+            .field("inner", &self.inner.inner_debug())
+            .finish()
+    }
+}
+
+/// Every `String` is also valid WTF-8.
+impl From<Args> for ArgsWtf8 {
+    fn from(args: Args) -> ArgsWtf8 {
+        ArgsWtf8 { inner: args.inner }
+    }
+}
+
+fn expect_still_utf8(arg: Wtf8Buf) -> String {
+    arg.into_string().unwrap_or_else(|arg| {
+        panic!("\
+valid UTF-8 became invalid after arg splitting?!
+BadArg: {:?}\
+", arg);
+    })
+}
+
+fn expect_still_utf8_ref(arg: &Wtf8) -> &str {
+    arg.as_str().unwrap_or_else(|| {
+        panic!("\
+valid UTF-8 became invalid after arg splitting?!
+BadArg: {:?}\
+", arg);
+    })
+}
+
+impl Iterator for Args {
+    type Item = String;
+    fn next(&mut self) -> Option<String> { self.inner.next().map(expect_still_utf8) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+    // the skipped arguments are dropped without being converted
+    fn nth(&mut self, n: usize) -> Option<String> { self.inner.nth(n).map(expect_still_utf8) }
+    fn count(self) -> usize { self.inner.count() }
+    fn last(self) -> Option<String> { self.inner.last().map(expect_still_utf8) }
+}
+
+impl ExactSizeIterator for Args {
+    fn len(&self) -> usize { self.inner.len() }
+}
+
+impl DoubleEndedIterator for Args {
+    fn next_back(&mut self) -> Option<String> { self.inner.next_back().map(expect_still_utf8) }
+    fn nth_back(&mut self, n: usize) -> Option<String> { self.inner.nth_back(n).map(expect_still_utf8) }
+}
+
+impl FusedIterator for Args {}
+
+impl Args {
+    /// Iterate over the remaining arguments by reference, without consuming them.
+    ///
+    /// `for arg in &args` does the same.
+    ///
+    /// ```
+    /// let args = windows_args::Args::parse_cmd("app.exe a b");
+    /// assert_eq!(args.iter().rev().collect::<Vec<&str>>(), ["b", "a", "app.exe"]);
+    /// for arg in &args {
+    ///     assert!(!arg.is_empty());
+    /// }
+    /// assert_eq!(args.len(), 3);
+    /// ```
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { inner: self.inner.as_slice().iter() }
+    }
+
+    /// Borrow one of the remaining arguments, without consuming anything.
+    ///
+    /// The index counts from the next argument that the iterator would yield, so this
+    /// composes with iteration.  Returns `None` if there are not that many arguments left.
+    /// `args[index]` does the same, but panics instead.
+    ///
+    /// ```
+    /// let mut args = windows_args::Args::parse_cmd("app.exe -v file.txt");
+    /// assert_eq!(args.get(2), Some("file.txt"));
+    /// args.next();
+    /// assert_eq!(args.get(1), Some("file.txt"));
+    /// assert_eq!(&args[0], "-v");
+    /// assert_eq!(args.get(2), None);
+    /// ```
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.inner.as_slice().get(index).map(|arg| expect_still_utf8_ref(arg))
+    }
+
+    /// Borrow the argument that `next` would return, without consuming it.
+    ///
+    /// Unlike wrapping the iterator in [`Peekable`], this keeps `len` and `next_back`
+    /// available.
+    ///
+    /// ```
+    /// let mut args = windows_args::Args::parse_cmd("app.exe -v file.txt");
+    /// args.next();
+    /// if args.peek() == Some("-v") {
+    ///     args.next();
+    /// }
+    /// assert_eq!(args.peek(), Some("file.txt"));
+    /// assert_eq!(args.peek_back(), Some("file.txt"));
+    /// assert_eq!(args.len(), 1);
+    /// ```
+    ///
+    /// [`Peekable`]: https://doc.rust-lang.org/std/iter/struct.Peekable.html
+    pub fn peek(&self) -> Option<&str> {
+        self.get(0)
+    }
+
+    /// Borrow the argument that `next_back` would return, without consuming it.
+    pub fn peek_back(&self) -> Option<&str> {
+        self.inner.as_slice().last().map(|arg| expect_still_utf8_ref(arg))
+    }
+
+    /// The command line that these arguments were parsed from, exactly as it was given.
+    ///
+    /// This is useful for logging and error messages, where a re-quoted approximation would
+    /// be misleading.  Only the input up to the first NUL is kept, since nothing after it is
+    /// parsed.  For [`Args::parse_args`], this is the string that was passed in.
+    ///
+    /// ```
+    /// let mut args = windows_args::Args::parse_cmd(r#"app.exe  "a b"  c"#);
+    /// args.next();
+    /// assert_eq!(args.raw(), Some(r#"app.exe  "a b"  c"#));
+    /// ```
+    ///
+    /// The functions that parse a string keep a copy of it, as do those of [`ParserConfig`]
+    /// unless told otherwise by [`ParserConfig::retain_raw`].  This is `None` for arguments
+    /// that were built in any other way, such as by [`Args::append`] or [`Args::get_slice`].
+    ///
+    /// [`Args::parse_args`]: #method.parse_args
+    /// [`Args::append`]: #method.append
+    /// [`Args::get_slice`]: #method.get_slice
+    /// [`ParserConfig`]: struct.ParserConfig.html
+    /// [`ParserConfig::retain_raw`]: struct.ParserConfig.html#method.retain_raw
+    pub fn raw(&self) -> Option<&str> {
+        self.inner.raw().map(|raw| expect_still_utf8_ref(raw))
+    }
+
+    /// The part of [`Args::raw`] that begins with the next argument, exactly as it was given.
+    ///
+    /// This is for programs that consume some arguments of their own and then pass the rest
+    /// of the command line along untouched, as `cmd /c` does; joining the parsed arguments
+    /// again would not preserve the original quoting.  The whitespace before the next argument
+    /// is not included, and once there are no arguments left, this is empty.  Arguments that
+    /// were already taken from the back are still included, since the remainder always runs
+    /// to the end of the input.
+    ///
+    /// ```
+    /// let mut args = windows_args::Args::parse_cmd(r#"runner.exe -v  --  "C:\a b"\x.exe  /q"#);
+    /// while let Some(arg) = args.next() {
+    ///     if arg == "--" {
+    ///         break;
+    ///     }
+    /// }
+    /// assert_eq!(args.rest_raw(), Some(r#""C:\a b"\x.exe  /q"#));
+    /// args.by_ref().for_each(drop);
+    /// assert_eq!(args.rest_raw(), Some(""));
+    /// ```
+    ///
+    /// This is `None` whenever [`Args::raw`] is.  No offsets are stored while parsing, so each
+    /// call finds the next argument by scanning the input again, in time linear in its length.
+    ///
+    /// [`Args::raw`]: #method.raw
+    pub fn rest_raw(&self) -> Option<&str> {
+        self.inner.rest_raw().map(|(raw, start)| {
+            let raw = expect_still_utf8_ref(raw);
+            &raw[start.unwrap_or(raw.len())..]
+        })
+    }
+
+    /// Borrow the remaining arguments as [`OsStr`]s, without consuming them.
+    ///
+    /// This is free, as every `str` is already a valid `OsStr`.
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    ///
+    /// let args = windows_args::Args::parse_args(r#"C:\a.txt "C:\Program Files\b.txt""#);
+    /// let paths = args.iter_os().map(PathBuf::from).collect::<Vec<_>>();
+    /// assert_eq!(paths[1], PathBuf::from(r#"C:\Program Files\b.txt"#));
+    /// ```
+    ///
+    /// [`OsStr`]: https://doc.rust-lang.org/std/ffi/struct.OsStr.html
+    pub fn iter_os(&self) -> IterAsOs<'_> {
+        IterAsOs { inner: self.iter() }
+    }
+
+    /// Display the remaining arguments as a command line, quoting each one as [`Quoted`] does.
+    ///
+    /// The arguments are separated by single spaces, and the result parses back into them
+    /// with [`Args::parse_args`].  The quoting is streamed directly into the formatter, and
+    /// this does not advance the iterator.
+    ///
+    /// ```
+    /// let mut args = windows_args::Args::parse_cmd(r#"app.exe "C:\My Files\\" -v"#);
+    /// args.next();
+    /// assert_eq!(args.display_quoted().to_string(), r#""C:\My Files\\" -v"#);
+    /// ```
+    pub fn display_quoted(&self) -> impl fmt::Display + '_ {
+        quote::DisplayQuoted(self.inner.as_slice().iter().map(|arg| expect_still_utf8_ref(arg).chars()))
+    }
+
+    /// Write the remaining arguments as WTF-8, each one followed by a NUL byte.
+    ///
+    /// No quoting is performed.  This is equivalent to [`Args::write_args0_with`] with the
+    /// default [`Args0Options`].
+    ///
+    /// ```
+    /// let args = windows_args::Args::parse_args(r#"a "b c" """#);
+    /// let mut out = vec![];
+    /// args.write_args0(&mut out)?;
+    /// assert_eq!(out, b"a\0b c\0\0");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn write_args0(&self, w: impl io::Write) -> io::Result<()> {
+        self.write_args0_with(w, &Args0Options::default())
+    }
+
+    /// Write the remaining arguments as NUL-separated records.
+    ///
+    /// # Errors
+    ///
+    /// Besides I/O errors, this fails with [`io::ErrorKind::InvalidInput`] if an argument
+    /// contains a NUL, in which case nothing is written.  The inner error is a
+    /// [`NulInArgError`].
+    pub fn write_args0_with(&self, w: impl io::Write, opts: &Args0Options) -> io::Result<()> {
+        let records = self.inner.as_slice().iter().map(|arg| arg.to_ill_formed_utf16());
+        args0::write_args0(w, records, opts)
+    }
+
+    /// Read arguments from NUL-separated records, as written by [`Args::write_args0`].
+    ///
+    /// A NUL at the very end of the input terminates the final record, so input with or
+    /// without a trailing separator is accepted.  Empty input produces no arguments.
+    ///
+    /// ```
+    /// let args = windows_args::Args::from_args0(b"a\0b c\0\0", windows_args::Args0Encoding::Wtf8)?;
+    /// assert_eq!(args.collect::<Vec<_>>(), ["a", "b c", ""]);
+    /// # Ok::<(), windows_args::Args0Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if a record is not valid UTF-8 (or UTF-16, for [`Args0Encoding::Utf16Le`]).
+    /// Unpaired surrogates are rejected; use [`ArgsOs::from_args0`] to accept them.
+    pub fn from_args0(bytes: &[u8], encoding: Args0Encoding) -> Result<Args, Args0Error> {
+        let records = args0::read_args0(bytes, encoding, false)?;
+        Ok(Args { inner: args::ArgsWtf8::from_vec(records) })
+    }
+
+    /// Clone a range of the remaining arguments into a new `Args`.
+    ///
+    /// Indices are relative to the first argument that has not yet been produced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, like slice indexing.
+    ///
+    /// ```
+    /// use windows_args::Args;
+    ///
+    /// let mut args = Args::parse_cmd("git -C dir commit -m msg");
+    /// args.next();
+    /// assert_eq!(args.slice(2..).collect::<Vec<_>>(), vec!["commit", "-m", "msg"]);
+    /// ```
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Args {
+        self.get_slice((range.start_bound().cloned(), range.end_bound().cloned()))
+            .unwrap_or_else(|| slice_range_panic(&range, self.len()))
+    }
+
+    /// Like [`Args::slice`], but returns `None` if the range is out of bounds.
+    pub fn get_slice(&self, range: impl RangeBounds<usize>) -> Option<Args> {
+        self.inner.get_slice(range).map(|inner| Args { inner })
+    }
+
+    /// Like [`Args::slice`], but reuses the existing storage instead of cloning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, like slice indexing.
+    pub fn into_slice(self, range: impl RangeBounds<usize>) -> Args {
+        Args { inner: self.inner.into_slice(range) }
+    }
+
+    /// Move the remaining arguments of `other` onto the end of `self`.
+    ///
+    /// Only the arguments that have not yet been produced by either iterator are kept.
+    ///
+    /// ```
+    /// use windows_args::Args;
+    ///
+    /// let mut args = Args::parse_cmd("a.exe --base");
+    /// let mut extra = Args::parse_args("--skipped --extra");
+    /// extra.next();
+    ///
+    /// args.append(extra);
+    /// assert_eq!(args.collect::<Vec<_>>(), vec!["a.exe", "--base", "--extra"]);
+    /// ```
+    pub fn append(&mut self, other: Args) {
+        self.inner.append(other.inner)
+    }
+
+    /// Convert the remaining arguments into a vector.
+    ///
+    /// This gives the same result as `args.collect::<Vec<_>>()`, but the vector that the
+    /// parser produced is converted in place, so its allocation (and that of every argument)
+    /// is reused.  `Vec::from(args)` does the same.
+    ///
+    /// Only the arguments that have not yet been produced by the iterator are included.
+    ///
+    /// ```
+    /// let mut args = windows_args::Args::parse_cmd(r#"app.exe "a b" c"#);
+    /// args.next();
+    /// assert_eq!(args.into_vec(), ["a b", "c"]);
+    /// ```
+    pub fn into_vec(self) -> Vec<String> {
+        // an in-place collect, as `String` and `Wtf8Buf` have the same layout
+        self.inner.into_vec().into_iter().map(expect_still_utf8).collect()
+    }
+}
+
+/// Equivalent to [`Args::into_vec`].
+///
+/// [`Args::into_vec`]: struct.Args.html#method.into_vec
+impl From<Args> for Vec<String> {
+    fn from(args: Args) -> Vec<String> {
+        args.into_vec()
+    }
+}
+
+/// A borrowing iterator over the remaining arguments of an [`Args`], created by
+/// [`Args::iter`].
+///
+/// [`Args`]: struct.Args.html
+/// [`Args::iter`]: struct.Args.html#method.iter
+#[derive(Debug, Clone)]
+pub struct Iter<'a> { inner: std::slice::Iter<'a, Wtf8Buf> }
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a str;
+    fn next(&mut self) -> Option<&'a str> { self.inner.next().map(|arg| expect_still_utf8_ref(arg)) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+    fn nth(&mut self, n: usize) -> Option<&'a str> { self.inner.nth(n).map(|arg| expect_still_utf8_ref(arg)) }
+    fn count(self) -> usize { self.inner.len() }
+    fn last(mut self) -> Option<&'a str> { self.next_back() }
+}
+
+impl ExactSizeIterator for Iter<'_> {
+    fn len(&self) -> usize { self.inner.len() }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<&'a str> { self.inner.next_back().map(|arg| expect_still_utf8_ref(arg)) }
+    fn nth_back(&mut self, n: usize) -> Option<&'a str> { self.inner.nth_back(n).map(|arg| expect_still_utf8_ref(arg)) }
+}
+
+impl FusedIterator for Iter<'_> {}
+
+/// A borrowing iterator over the remaining arguments of an [`Args`] as [`OsStr`]s, created by
+/// [`Args::iter_os`].
+///
+/// This is an [`Iter`] with each item converted, which costs nothing.
+///
+/// [`Args`]: struct.Args.html
+/// [`Args::iter_os`]: struct.Args.html#method.iter_os
+/// [`Iter`]: struct.Iter.html
+/// [`OsStr`]: https://doc.rust-lang.org/std/ffi/struct.OsStr.html
+#[derive(Debug, Clone)]
+pub struct IterAsOs<'a> { inner: Iter<'a> }
+
+impl<'a> Iterator for IterAsOs<'a> {
+    type Item = &'a OsStr;
+    fn next(&mut self) -> Option<&'a OsStr> { self.inner.next().map(OsStr::new) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+    fn nth(&mut self, n: usize) -> Option<&'a OsStr> { self.inner.nth(n).map(OsStr::new) }
+    fn count(self) -> usize { self.inner.count() }
+    fn last(self) -> Option<&'a OsStr> { self.inner.last().map(OsStr::new) }
+}
+
+impl ExactSizeIterator for IterAsOs<'_> {
+    fn len(&self) -> usize { self.inner.len() }
+}
+
+impl<'a> DoubleEndedIterator for IterAsOs<'a> {
+    fn next_back(&mut self) -> Option<&'a OsStr> { self.inner.next_back().map(OsStr::new) }
+    fn nth_back(&mut self, n: usize) -> Option<&'a OsStr> { self.inner.nth_back(n).map(OsStr::new) }
+}
+
+impl FusedIterator for IterAsOs<'_> {}
+
+impl<'a> IntoIterator for &'a Args {
+    type Item = &'a str;
+    type IntoIter = Iter<'a>;
+    fn into_iter(self) -> Iter<'a> { self.iter() }
+}
+
+/// Indexing into the remaining arguments.  See [`Args::get`].
+///
+/// [`Args::get`]: struct.Args.html#method.get
+impl ops::Index<usize> for Args {
+    type Output = str;
+
+    fn index(&self, index: usize) -> &str {
+        expect_still_utf8_ref(&self.inner.as_slice()[index])
+    }
+}
+
+/// Collect arguments that are already split.
+///
+/// Each string becomes one argument exactly as it is: nothing is parsed, so quotes,
+/// whitespace and even NULs are kept verbatim.  Use [`Args::parse_args`] for command-line
+/// text instead.
+///
+/// ```
+/// use windows_args::Args;
+///
+/// let args: Args = vec!["app.exe".to_string(), r#""a b""#.to_string()].into_iter().collect();
+/// assert_eq!(args.collect::<Vec<_>>(), ["app.exe", r#""a b""#]);
+/// ```
+///
+/// [`Args::parse_args`]: struct.Args.html#method.parse_args
+impl FromIterator<String> for Args {
+    fn from_iter<I: IntoIterator<Item=String>>(iter: I) -> Args {
+        Args { inner: args::ArgsWtf8::from_vec(iter.into_iter().map(Wtf8Buf::from_string).collect()) }
+    }
+}
+
+/// Add arguments that are already split after the remaining ones.
+///
+/// As with `collect`, the strings are kept verbatim without being parsed.
+impl Extend<String> for Args {
+    fn extend<I: IntoIterator<Item=String>>(&mut self, iter: I) {
+        self.inner.extend(iter.into_iter().map(Wtf8Buf::from_string))
+    }
+}
+
+/// Concatenation.  Equivalent to [`Args::append`].
+impl ops::Add for Args {
+    type Output = Args;
+
+    fn add(mut self, other: Args) -> Args {
+        self.append(other);
+        self
+    }
+}
+
+impl fmt::Debug for Args {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Args")
+            .field("inner", &self.inner.inner_debug())
+            .finish()
+    }
+}
+
+#[cfg(windows)]
+impl Iterator for ArgsOs {
+    type Item = OsString;
+    fn next(&mut self) -> Option<OsString> { self.inner.next() }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+    fn nth(&mut self, n: usize) -> Option<OsString> { self.inner.nth(n) }
+    fn count(self) -> usize { self.inner.count() }
+    fn last(self) -> Option<OsString> { self.inner.last() }
+}
+
+#[cfg(windows)]
+impl ExactSizeIterator for ArgsOs {
+    fn len(&self) -> usize { self.inner.len() }
+}
+
+#[cfg(windows)]
+impl DoubleEndedIterator for ArgsOs {
+    fn next_back(&mut self) -> Option<OsString> { self.inner.next_back() }
+    fn nth_back(&mut self, n: usize) -> Option<OsString> { self.inner.nth_back(n) }
+}
+
+#[cfg(windows)]
+impl FusedIterator for ArgsOs {}
+
+#[cfg(windows)]
+impl ArgsOs {
+    /// Iterate over the remaining arguments by reference, like [`Args::iter`].
+    ///
+    /// `for arg in &args` does the same.
+    ///
+    /// [`Args::iter`]: struct.Args.html#method.iter
+    pub fn iter(&self) -> IterOs<'_> {
+        IterOs { inner: self.as_slice().iter() }
+    }
+
+    /// The remaining arguments, without consuming them.
+    ///
+    /// ```
+    /// let mut args = windows_args::ArgsOs::parse_cmd("app.exe a b".as_ref());
+    /// args.next();
+    /// assert_eq!(args.as_slice(), ["a", "b"]);
+    /// ```
+    pub fn as_slice(&self) -> &[OsString] {
+        self.inner.as_slice()
+    }
+
+    /// Borrow one of the remaining arguments, like [`Args::get`].  `args[index]` does the
+    /// same, but panics if there are not that many arguments left.
+    ///
+    /// [`Args::get`]: struct.Args.html#method.get
+    pub fn get(&self, index: usize) -> Option<&OsStr> {
+        self.as_slice().get(index).map(|arg| &arg[..])
+    }
+
+    /// Borrow the argument that `next` would return, like [`Args::peek`].
+    ///
+    /// [`Args::peek`]: struct.Args.html#method.peek
+    pub fn peek(&self) -> Option<&OsStr> {
+        self.get(0)
+    }
+
+    /// Borrow the argument that `next_back` would return, like [`Args::peek_back`].
+    ///
+    /// [`Args::peek_back`]: struct.Args.html#method.peek_back
+    pub fn peek_back(&self) -> Option<&OsStr> {
+        self.as_slice().last().map(|arg| &arg[..])
+    }
+
+    /// The command line that these arguments were parsed from, like [`Args::raw`].
+    ///
+    /// [`Args::raw`]: struct.Args.html#method.raw
+    pub fn raw(&self) -> Option<&OsStr> {
+        self.inner.raw().map(|raw| &raw[..])
+    }
+
+    /// The part of [`ArgsOs::raw`] that begins with the next argument, like
+    /// [`Args::rest_raw`].
+    ///
+    /// [`ArgsOs::raw`]: #method.raw
+    /// [`Args::rest_raw`]: struct.Args.html#method.rest_raw
+    pub fn rest_raw(&self) -> Option<&OsStr> {
+        self.inner.rest_raw().map(|(raw, start)| {
+            let bytes = raw.as_encoded_bytes();
+            let rest = &bytes[start.unwrap_or(bytes.len())..];
+            // SAFETY: an argument begins either at the start of the input or right after a
+            // space or tab, so this splits the encoding just after a valid UTF-8 substring
+            unsafe { OsStr::from_encoded_bytes_unchecked(rest) }
+        })
+    }
+
+    /// Borrow the remaining arguments as strings, replacing invalid UTF-16 with `U+FFFD`.
+    ///
+    /// Arguments are converted lazily, one at a time.  Arguments that are valid Unicode are
+    /// borrowed; only those that contain a lone surrogate need to allocate.
+    ///
+    /// ```rust
+    /// use std::borrow::Cow;
+    ///
+    /// let args = windows_args::ArgsOs::parse_cmd("prog.exe arg".as_ref());
+    /// assert!(args.iter_lossy().all(|arg| matches!(arg, Cow::Borrowed(_))));
+    /// ```
+    pub fn iter_lossy(&self) -> impl DoubleEndedIterator<Item=Cow<'_, str>> + ExactSizeIterator + Clone + '_ {
+        self.inner.as_slice().iter().map(|arg| arg.to_string_lossy())
+    }
+
+    /// Display the remaining arguments as a command line, quoting each one as [`QuotedOs`] does.
+    ///
+    /// Like [`QuotedOs`], this renders unpaired surrogates as `U+FFFD REPLACEMENT CHARACTER`.
+    /// This does not advance the iterator.
+    pub fn display_quoted(&self) -> impl fmt::Display + '_ {
+        use std::os::windows::ffi::OsStrExt;
+
+        quote::DisplayQuoted(self.inner.as_slice().iter().map(|arg| {
+            std::char::decode_utf16(arg.encode_wide())
+                .map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER))
+        }))
+    }
+
+    /// Write the remaining arguments as WTF-8, each one followed by a NUL byte.
+    ///
+    /// No quoting is performed, and unpaired surrogates are preserved.  This is equivalent
+    /// to [`ArgsOs::write_args0_with`] with the default [`Args0Options`].
+    pub fn write_args0(&self, w: impl io::Write) -> io::Result<()> {
+        self.write_args0_with(w, &Args0Options::default())
+    }
+
+    /// Write the remaining arguments as NUL-separated records.
+    ///
+    /// # Errors
+    ///
+    /// Besides I/O errors, this fails with [`io::ErrorKind::InvalidInput`] if an argument
+    /// contains a NUL, in which case nothing is written.  The inner error is a
+    /// [`NulInArgError`].
+    pub fn write_args0_with(&self, w: impl io::Write, opts: &Args0Options) -> io::Result<()> {
+        use std::os::windows::ffi::OsStrExt;
+
+        let records = self.inner.as_slice().iter().map(|arg| arg.encode_wide());
+        args0::write_args0(w, records, opts)
+    }
+
+    /// Read arguments from NUL-separated records, as written by [`ArgsOs::write_args0`].
+    ///
+    /// A NUL at the very end of the input terminates the final record, so input with or
+    /// without a trailing separator is accepted.  Empty input produces no arguments.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a record is not valid WTF-8 (or has an odd length, for
+    /// [`Args0Encoding::Utf16Le`]).  Unpaired surrogates are accepted.
+    pub fn from_args0(bytes: &[u8], encoding: Args0Encoding) -> Result<ArgsOs, Args0Error> {
+        let records = args0::read_args0(bytes, encoding, true)?;
+        Ok(ArgsOs { inner: args::ArgsWtf8::from_vec(records) })
+    }
+
+    /// Clone a range of the remaining arguments into a new `ArgsOs`.
+    ///
+    /// Indices are relative to the first argument that has not yet been produced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, like slice indexing.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> ArgsOs {
+        self.get_slice((range.start_bound().cloned(), range.end_bound().cloned()))
+            .unwrap_or_else(|| slice_range_panic(&range, self.len()))
+    }
+
+    /// Like [`ArgsOs::slice`], but returns `None` if the range is out of bounds.
+    pub fn get_slice(&self, range: impl RangeBounds<usize>) -> Option<ArgsOs> {
+        self.inner.get_slice(range).map(|inner| ArgsOs { inner })
+    }
+
+    /// Like [`ArgsOs::slice`], but reuses the existing storage instead of cloning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, like slice indexing.
+    pub fn into_slice(self, range: impl RangeBounds<usize>) -> ArgsOs {
+        ArgsOs { inner: self.inner.into_slice(range) }
+    }
+
+    /// Move the remaining arguments of `other` onto the end of `self`.
+    ///
+    /// Only the arguments that have not yet been produced by either iterator are kept.
+    pub fn append(&mut self, other: ArgsOs) {
+        self.inner.append(other.inner)
+    }
+
+    /// Convert the remaining arguments into a vector, reusing its allocation, like
+    /// [`Args::into_vec`].  `Vec::from(args)` does the same.
+    ///
+    /// [`Args::into_vec`]: struct.Args.html#method.into_vec
+    pub fn into_vec(self) -> Vec<OsString> {
+        self.inner.into_vec()
+    }
+}
+
+/// **Windows only.**
+/// A borrowing iterator over the remaining arguments of an [`ArgsOs`], created by
+/// [`ArgsOs::iter`].
+///
+/// [`ArgsOs`]: struct.ArgsOs.html
+/// [`ArgsOs::iter`]: struct.ArgsOs.html#method.iter
+#[cfg(windows)]
+#[derive(Debug, Clone)]
+pub struct IterOs<'a> { inner: std::slice::Iter<'a, OsString> }
+
+#[cfg(windows)]
+impl<'a> Iterator for IterOs<'a> {
+    type Item = &'a OsStr;
+    fn next(&mut self) -> Option<&'a OsStr> { self.inner.next().map(|arg| &arg[..]) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+    fn nth(&mut self, n: usize) -> Option<&'a OsStr> { self.inner.nth(n).map(|arg| &arg[..]) }
+    fn count(self) -> usize { self.inner.len() }
+    fn last(mut self) -> Option<&'a OsStr> { self.next_back() }
+}
+
+#[cfg(windows)]
+impl ExactSizeIterator for IterOs<'_> {
+    fn len(&self) -> usize { self.inner.len() }
+}
+
+#[cfg(windows)]
+impl<'a> DoubleEndedIterator for IterOs<'a> {
+    fn next_back(&mut self) -> Option<&'a OsStr> { self.inner.next_back().map(|arg| &arg[..]) }
+    fn nth_back(&mut self, n: usize) -> Option<&'a OsStr> { self.inner.nth_back(n).map(|arg| &arg[..]) }
+}
+
+#[cfg(windows)]
+impl FusedIterator for IterOs<'_> {}
+
+#[cfg(windows)]
+impl<'a> IntoIterator for &'a ArgsOs {
+    type Item = &'a OsStr;
+    type IntoIter = IterOs<'a>;
+    fn into_iter(self) -> IterOs<'a> { self.iter() }
+}
+
+/// Indexing into the remaining arguments.  See [`ArgsOs::get`].
+///
+/// [`ArgsOs::get`]: struct.ArgsOs.html#method.get
+#[cfg(windows)]
+impl ops::Index<usize> for ArgsOs {
+    type Output = OsStr;
+
+    fn index(&self, index: usize) -> &OsStr {
+        &self.as_slice()[index]
+    }
+}
+
+/// Collect arguments that are already split, keeping each one verbatim as for [`Args`].
+///
+/// [`Args`]: struct.Args.html
+#[cfg(windows)]
+impl FromIterator<OsString> for ArgsOs {
+    fn from_iter<I: IntoIterator<Item=OsString>>(iter: I) -> ArgsOs {
+        ArgsOs { inner: args::ArgsWtf8::from_vec(iter.into_iter().collect()) }
+    }
+}
+
+/// Add arguments that are already split after the remaining ones, keeping each one verbatim.
+#[cfg(windows)]
+impl Extend<OsString> for ArgsOs {
+    fn extend<I: IntoIterator<Item=OsString>>(&mut self, iter: I) {
+        self.inner.extend(iter)
+    }
+}
+
+/// Equivalent to [`ArgsOs::into_vec`].
+///
+/// [`ArgsOs::into_vec`]: struct.ArgsOs.html#method.into_vec
+#[cfg(windows)]
+impl From<ArgsOs> for Vec<OsString> {
+    fn from(args: ArgsOs) -> Vec<OsString> {
+        args.into_vec()
+    }
+}
+
+/// Concatenation.  Equivalent to [`ArgsOs::append`].
+#[cfg(windows)]
+impl ops::Add for ArgsOs {
+    type Output = ArgsOs;
+
+    fn add(mut self, other: ArgsOs) -> ArgsOs {
+        self.append(other);
+        self
+    }
+}
+
+#[cfg(windows)]
+impl fmt::Debug for ArgsOs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArgsOs")
+            .field("inner", &self.inner.inner_debug())
+            .finish()
+    }
+}
+
+fn check_utf16(wide: &[u16]) -> Result<(), FromUtf16Error> {
+    match std::char::decode_utf16(wide.iter().cloned()).all(|c| c.is_ok()) {
+        true => Ok(()),
+        // construct the error through std, which is the only way to get one
+        false => String::from_utf16(wide).map(drop),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn special_traits() {
+        assert_eq!(Args::parse_cmd("a b").next_back(), Some("b".into()));
+        assert_eq!(Args::parse_cmd("a b").len(), 2);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn special_traits_windows() {
         assert_eq!(ArgsOs::parse_cmd("a b".as_ref()).next_back(), Some("b".into()));
         assert_eq!(ArgsOs::parse_cmd("a b".as_ref()).len(), 2);
     }
 
+    #[test]
+    fn wtf8() {
+        // unpaired surrogates
+        let lead = Wtf8Buf::from_ill_formed_utf16(&[0xd800]);
+        let trail = Wtf8Buf::from_ill_formed_utf16(&[0xdc00]);
+
+        let chk = |wide: &[u16], parts: &[&Wtf8]| {
+            let input = Wtf8Buf::from_ill_formed_utf16(wide);
+            let parsed = ArgsWtf8::parse_cmd(&input).collect::<Vec<_>>();
+            assert_eq!(parsed, parts.iter().map(|&p| p.to_owned()).collect::<Vec<_>>());
+        };
+        let w = |s: &str| s.encode_utf16().collect::<Vec<_>>();
+        let concat = |parts: &[&[u16]]| parts.concat();
+
+        chk(&[], &[Wtf8::from_str("")]);
+        chk(&w("EXE a"), &[Wtf8::from_str("EXE"), Wtf8::from_str("a")]);
+        chk(&concat(&[&w("EXE "), &[0xd800]]), &[Wtf8::from_str("EXE"), &lead]);
+        chk(&concat(&[&[0xdc00], &w(" \"\"")]), &[&trail, Wtf8::from_str("")]);
+        chk(
+            &concat(&[&w("EXE \""), &[0xd800], &w(" \\\"b"), &[0xdc00]]),
+            &[Wtf8::from_str("EXE"), &Wtf8Buf::from_ill_formed_utf16(&concat(&[&[0xd800], &w(" \"b"), &[0xdc00]]))],
+        );
+
+        let mut args = ArgsWtf8::parse_args(&Wtf8Buf::from_ill_formed_utf16(&concat(&[&w("a "), &[0xdc00]])));
+        assert_eq!(args.len(), 2);
+        assert_eq!(args.next_back(), Some(trail));
+        assert_eq!(args.next(), Some(Wtf8Buf::from_str("a")));
+        assert!(ArgsWtf8::parse_args(Wtf8::from_str("")).next().is_none());
+
+        let args = ArgsWtf8::from(Args::parse_args("x y"));
+        assert!(format!("{:?}", args).starts_with("ArgsWtf8 {"));
+        assert_eq!(args.collect::<Vec<_>>(), [Wtf8Buf::from_str("x"), Wtf8Buf::from_str("y")]);
+    }
+
+    #[test]
+    fn parse_cmd_opt() {
+        let chk = |input: &str, exe: Option<&str>, rest: &[&str]| {
+            let (parsed_exe, args) = Args::parse_cmd_opt(input);
+            assert_eq!(parsed_exe.as_deref(), exe, "{:?}", input);
+            assert_eq!(args.collect::<Vec<_>>(), rest, "{:?}", input);
+        };
+        chk("", None, &[]);
+        chk(" \t ", None, &[]);
+        chk("\0app.exe x", None, &[]);
+        chk(" foo", Some(""), &["foo"]);
+        chk("\"\"", Some(""), &[]);
+        chk("\n", Some(""), &[]);
+        chk(" \n", Some(""), &["\n"]);
+        chk(r#""C:\a b\app.exe" x"#, Some(r"C:\a b\app.exe"), &["x"]);
+    }
+
+    #[test]
+    fn value_semantics() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::HashSet;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |args: &Args| {
+            let mut hasher = DefaultHasher::new();
+            args.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut consumed = Args::parse_cmd("app.exe a b");
+        let fresh = Args::parse_args(r#""a" b"#);
+        assert_ne!(consumed, fresh);
+        consumed.next();
+        assert_eq!(consumed, fresh);
+        assert_eq!(hash(&consumed), hash(&fresh));
+
+        // consuming from the back counts too
+        let mut clone = consumed.clone();
+        assert_eq!(clone.next_back(), Some("b".into()));
+        assert_eq!(clone, Args::parse_args("a"));
+        assert_eq!(consumed.len(), 2);
+
+        // fully consumed iterators are all equal
+        let mut empty = Args::parse_cmd("x");
+        empty.next();
+        assert_eq!(empty, Args::parse_args(""));
+        assert_eq!(hash(&empty), hash(&Args::parse_args("")));
+
+        let set = ["app.exe a", "app.exe  \"a\"", "app.exe b"].iter().map(|s| Args::parse_cmd(s)).collect::<HashSet<_>>();
+        assert_eq!(set.len(), 2);
+
+        let wtf8 = ArgsWtf8::from(Args::parse_args("a b"));
+        assert_eq!(wtf8.clone(), ArgsWtf8::parse_args(Wtf8::from_str("a  b")));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn value_semantics_os() {
+        use std::collections::HashSet;
+
+        let mut args = ArgsOs::parse_cmd("app.exe a".as_ref());
+        args.next();
+        assert_eq!(args.clone(), ArgsOs::parse_args("a".as_ref()));
+        let set = vec![args.clone(), ArgsOs::parse_args("\"a\"".as_ref())].into_iter().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn random_access() {
+        let mut args = Args::parse_cmd("app.exe a b c");
+        assert_eq!((args.get(0), args.get(3), args.get(4)), (Some("app.exe"), Some("c"), None));
+        args.next();
+        args.next_back();
+        assert_eq!((args.get(0), args.get(1), args.get(2)), (Some("a"), Some("b"), None));
+        assert_eq!((&args[0], &args[1]), ("a", "b"));
+        assert_eq!(args.collect::<Vec<_>>(), ["a", "b"]);
+
+        let lead = Wtf8Buf::from_ill_formed_utf16(&[0xd800]);
+        let mut args = ArgsWtf8::parse_cmd(&Wtf8Buf::from_ill_formed_utf16(&[b'x' as u16, b' ' as u16, 0xd800]));
+        args.next();
+        assert_eq!(args.as_slice(), std::slice::from_ref(&lead));
+        assert_eq!(args.get(0), Some(&*lead));
+        assert_eq!(&args[0], &*lead);
+        assert_eq!(args.get(1), None);
+    }
+
+    #[test]
+    fn borrowed_iteration() {
+        let mut args = Args::parse_cmd("app.exe a b c");
+        args.next();
+        let mut iter = args.iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back(), Some("c"));
+        let rest = iter.clone();
+        assert_eq!(iter.next(), Some("a"));
+        assert_eq!(rest.collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(iter.len(), 1);
+
+        let mut seen = vec![];
+        for arg in &args {
+            seen.push(arg);
+        }
+        assert_eq!(seen, ["a", "b", "c"]);
+        assert_eq!(args.collect::<Vec<_>>(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn peeking() {
+        let mut args = Args::parse_cmd("app.exe a b");
+        assert_eq!(args.peek(), Some("app.exe"));
+        assert_eq!(args.peek(), Some("app.exe"));
+        assert_eq!(args.next().as_deref(), Some("app.exe"));
+        assert_eq!(args.peek(), Some("a"));
+        assert_eq!(args.peek_back(), Some("b"));
+        assert_eq!(args.next_back().as_deref(), Some("b"));
+        assert_eq!(args.peek_back(), Some("a"));
+        assert_eq!(args.peek(), Some("a"));
+        // peeking is not visible to Debug, which only shows what remains
+        assert_eq!(format!("{:?}", args), format!("{:?}", Args::parse_args("a")));
+
+        args.next();
+        assert_eq!(args.peek(), None);
+        assert_eq!(args.peek_back(), None);
+        assert_eq!(Args::parse_args("").peek(), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn peeking_os() {
+        let mut args = ArgsOs::parse_cmd("app.exe a b".as_ref());
+        assert_eq!(args.peek(), Some("app.exe".as_ref()));
+        args.next_back();
+        assert_eq!(args.peek_back(), Some("a".as_ref()));
+        args.next();
+        args.next();
+        assert_eq!(args.peek(), None);
+    }
+
+    #[test]
+    fn raw_input() {
+        let mut args = Args::parse_cmd("app.exe \"a b\"\0c");
+        args.next();
+        assert_eq!(args.raw(), Some("app.exe \"a b\""));
+        assert_eq!(args.clone().raw(), args.raw());
+        assert_eq!(Args::parse_args("  a  b ").raw(), Some("  a  b "));
+        assert_eq!(Args::parse_cmd("").raw(), Some(""));
+        assert_eq!(Args::try_parse_args_reserving("a b").unwrap().raw(), Some("a b"));
+        let wide = "a.exe b\0".encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(Args::parse_cmd_wide(&wide).unwrap().raw(), Some("a.exe b"));
+
+        // the arguments are compared without it
+        assert_eq!(Args::parse_args("a  b"), Args::parse_args("a b"));
+        // and it is gone once the arguments no longer come from one input
+        let mut args = Args::parse_args("a");
+        args.append(Args::parse_args("b"));
+        assert_eq!(args.raw(), None);
+        assert_eq!(Args::parse_args("a b").get_slice(1..).unwrap().raw(), None);
+
+        let config = ParserConfig::new().expand_wildcards(false);
+        assert_eq!(config.parse_args("a\tb").unwrap().raw(), Some("a\tb"));
+        assert_eq!(config.retain_raw(false).parse_args("a\tb").unwrap().raw(), None);
+    }
+
+    #[test]
+    fn rest_raw() {
+        let mut args = Args::parse_cmd("app.exe  -v \t\"a  b\"\\x  \"\"  c \0d");
+        assert_eq!(args.rest_raw(), Some("app.exe  -v \t\"a  b\"\\x  \"\"  c "));
+        args.nth(1);
+        assert_eq!(args.rest_raw(), Some("\"a  b\"\\x  \"\"  c "));
+        args.next_back();
+        assert_eq!(args.rest_raw(), Some("\"a  b\"\\x  \"\"  c "));
+        args.next();
+        assert_eq!(args.rest_raw(), Some("\"\"  c "));
+        assert_eq!(args.clone().rest_raw(), args.rest_raw());
+        args.next();
+        assert_eq!(args.rest_raw(), Some(""));
+        assert_eq!(args.nth(5), None);
+        assert_eq!(args.rest_raw(), Some(""));
+
+        let mut args = Args::parse_cmd(" x");
+        assert_eq!(args.rest_raw(), Some(" x"));
+        args.next();
+        assert_eq!(args.rest_raw(), Some("x"));
+
+        // the same offsets come from every parser, through UTF-16 as well as UTF-8
+        let input = "é.exe  /é  \"ü ö\"  \u{1F600}x  y";
+        let wide = input.encode_utf16().collect::<Vec<u16>>();
+        let configs = [ParserConfig::new(), ParserConfig::new().compat(Compat::CrtModern), ParserConfig::new().compat(Compat::RustStd)];
+        let parsed = vec![Args::parse_cmd(input), Args::parse_cmd_wide(&wide).unwrap(), Args::parse(&wide[..])]
+            .into_iter()
+            .chain(configs.iter().flat_map(|config| vec![config.parse_cmd(input).unwrap(), config.parse_cmd(&wide[..]).unwrap()]));
+        for mut args in parsed {
+            args.nth(1);
+            assert_eq!(args.rest_raw(), Some("\"ü ö\"  \u{1F600}x  y"));
+            args.next();
+            assert_eq!(args.rest_raw(), Some("\u{1F600}x  y"));
+        }
+
+        // arguments that are dropped at the end do not leave an offset behind
+        let mut args = ParserConfig::new().trailing_empty(TrailingEmpty::Drop).parse_args("a \"").unwrap();
+        args.next();
+        assert_eq!(args.rest_raw(), Some(""));
+        assert_eq!(ParserConfig::new().retain_raw(false).parse_args("a").unwrap().rest_raw(), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn raw_input_os() {
+        let args = ArgsOs::parse_cmd("app.exe  a\0b".as_ref());
+        assert_eq!(args.raw(), Some("app.exe  a".as_ref()));
+        let config = ParserConfig::new();
+        assert_eq!(config.parse_cmd_os("x  y").unwrap().raw(), Some("x  y".as_ref()));
+        assert_eq!(config.retain_raw(false).parse_cmd_os("x  y").unwrap().raw(), None);
+
+        use std::os::windows::ffi::OsStringExt;
+        let mut args = ArgsOs::parse_cmd(OsString::from_wide(&[0x61, 0x20, 0xD800, 0x20, 0x62]).as_os_str());
+        args.nth(1);
+        assert_eq!(args.rest_raw(), Some("b".as_ref()));
+    }
+
+    #[test]
+    fn into_vec() {
+        let mut args = Args::parse_cmd("app.exe a b c");
+        assert_eq!(args.clone().into_vec(), ["app.exe", "a", "b", "c"]);
+        args.next();
+        args.next_back();
+        assert_eq!(Vec::from(args), ["a", "b"]);
+
+        let mut args = Args::parse_args("a");
+        args.next();
+        assert_eq!(args.into_vec(), Vec::<String>::new());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn into_vec_os() {
+        let mut args = ArgsOs::parse_cmd("app.exe a b".as_ref());
+        args.next();
+        assert_eq!(args.clone().into_vec(), ["a", "b"]);
+        assert_eq!(Vec::<OsString>::from(args), ["a", "b"]);
+    }
+
+    #[test]
+    fn from_iter_and_extend() {
+        let pieces = ["app.exe", "\"a b\"", "c\0d", " "];
+        let mut args: Args = pieces.iter().map(|s| s.to_string()).collect();
+        // nothing is parsed
+        assert_eq!(args.len(), 4);
+        assert_eq!(args.get(2), Some("c\0d"));
+        assert_eq!(args.raw(), None);
+        args.next();
+        args.extend(vec!["--".to_string(), "\\\"".to_string()]);
+        assert_eq!(args.collect::<Vec<_>>(), ["\"a b\"", "c\0d", " ", "--", "\\\""]);
+
+        let mut args = Args::parse_cmd("app.exe a");
+        args.extend(None);
+        assert_eq!(args.raw(), None);
+        assert_eq!(args, Args::parse_cmd("app.exe a"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn from_iter_and_extend_os() {
+        let mut args: ArgsOs = vec![OsString::from("a\"b")].into_iter().collect();
+        args.extend(vec![OsString::from("c\0")]);
+        assert_eq!(args.into_vec(), ["a\"b", "c\0"]);
+    }
+
+    #[test]
+    fn skipping() {
+        let mut args = Args::parse_cmd("app.exe a");
+        assert_eq!(args.nth(1_000_000), None);
+        assert_eq!(args.len(), 0);
+        assert_eq!(args.next(), None);
+
+        let mut args = Args::parse_cmd("app.exe a b c d e");
+        assert_eq!(args.nth(1), Some("a".to_string()));
+        assert_eq!(args.nth_back(1), Some("d".to_string()));
+        assert_eq!(args.clone().count(), args.len());
+        assert_eq!(args.clone().last(), Some("c".to_string()));
+        assert_eq!(args.iter().nth(1), Some("c"));
+        assert_eq!(args.iter().nth_back(1), Some("b"));
+        assert_eq!(args.iter().last(), Some("c"));
+        assert_eq!(args.iter().count(), 2);
+        assert_eq!(args.nth_back(5), None);
+        assert_eq!(args.len(), 0);
+
+        let mut args = ArgsWtf8::from(Args::parse_cmd("app.exe a b"));
+        args.next();
+        assert_eq!(args.clone().count(), 2);
+        assert_eq!(args.last(), Some(Wtf8Buf::from_str("b")));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn borrowed_iteration_os() {
+        let args = ArgsOs::parse_cmd("app.exe a".as_ref());
+        assert_eq!(args.iter().rev().collect::<Vec<_>>(), ["a", "app.exe"]);
+        assert_eq!((&args).into_iter().len(), 2);
+        for arg in &args {
+            assert!(!arg.is_empty());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_out_of_range() {
+        let mut args = Args::parse_cmd("app.exe a");
+        args.next();
+        let _ = &args[1];
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn random_access_os() {
+        let mut args = ArgsOs::parse_cmd("app.exe a b".as_ref());
+        args.next_back();
+        assert_eq!(args.as_slice(), ["app.exe", "a"]);
+        assert_eq!(args.get(1), Some(OsStr::new("a")));
+        assert_eq!(&args[0], "app.exe");
+        assert_eq!(args.get(2), None);
+    }
+
+    #[test]
+    fn append() {
+        let mut args = Args::parse_cmd("exe a b");
+        assert_eq!(args.next(), Some("exe".into()));
+
+        let mut second = Args::parse_args("c d");
+        assert_eq!(second.next_back(), Some("d".into()));
+        args.append(second);
+        assert_eq!(args.len(), 3);
+        assert_eq!(args.next(), Some("a".into()));
+
+        let args = args + Args::parse_args("e") + Args::parse_args("");
+        assert_eq!(args.len(), 3);
+        assert_eq!(args.collect::<Vec<_>>(), vec!["b", "c", "e"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn append_windows() {
+        let mut args = ArgsOs::parse_cmd("exe a".as_ref());
+        args.next();
+        let args = args + ArgsOs::parse_args("b".as_ref()) + ArgsOs::parse_args("c".as_ref());
+        assert_eq!(args.len(), 3);
+        assert_eq!(args.collect::<Vec<_>>(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn iter_os() {
+        let mut args = Args::parse_cmd(r#"prog.exe "C:\Program Files\x" -v"#);
+        args.next();
+        assert_eq!(args.iter_os().len(), 2);
+        assert_eq!(args.iter_os().next_back(), Some(OsStr::new("-v")));
+
+        let path = std::path::PathBuf::from(args.iter_os().next().unwrap());
+        assert_eq!(path.as_os_str(), r#"C:\Program Files\x"#);
+
+        let mut command = std::process::Command::new("prog.exe");
+        command.args(args.iter_os());
+        assert_eq!(command.get_args().collect::<Vec<_>>(), args.iter_os().collect::<Vec<_>>());
+
+        // still unconsumed
+        assert_eq!(args.len(), 2);
+
+        let mut iter = args.iter_os();
+        assert_eq!(iter.clone().rev().collect::<Vec<_>>(), ["-v", r#"C:\Program Files\x"#]);
+        iter.next_back();
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next(), Some(OsStr::new(r#"C:\Program Files\x"#)));
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn iter_lossy() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let wide = [b'a' as u16, b' ' as u16, 0xd800, b' ' as u16, b'c' as u16, 0xdc00];
+        let args = ArgsOs::parse_cmd(&OsString::from_wide(&wide));
+        let lossy = args.iter_lossy().collect::<Vec<_>>();
+        assert_eq!(lossy, vec!["a", "\u{FFFD}", "c\u{FFFD}"]);
+        assert!(matches!(lossy[0], Cow::Borrowed(_)));
+        assert!(matches!(lossy[1], Cow::Owned(_)));
+        assert!(matches!(lossy[2], Cow::Owned(_)));
+
+        // only the first argument needs to be converted
+        assert_eq!(args.iter_lossy().next().unwrap(), "a");
+        assert_eq!(args.len(), 3);
+    }
+
+    #[test]
+    fn parse_wide() {
+        let wide = |s: &str| s.encode_utf16().collect::<Vec<_>>();
+        assert_eq!(Args::parse_cmd_wide(&[]).unwrap().collect::<Vec<_>>(), [""]);
+        assert_eq!(Args::parse_cmd_wide(&wide("a \"b c\"")).unwrap().collect::<Vec<_>>(), ["a", "b c"]);
+        assert_eq!(Args::parse_cmd_wide(&wide("a b\0c")).unwrap().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(Args::parse_cmd_wide(&wide("\0a")).unwrap().collect::<Vec<_>>(), [""]);
+        assert_eq!(Args::parse_args_wide(&[]).unwrap().count(), 0);
+        assert_eq!(Args::parse_args_wide(&wide("😅 x\0y")).unwrap().collect::<Vec<_>>(), ["😅", "x"]);
+
+        assert!(Args::parse_cmd_wide(&[b'a' as u16, b' ' as u16, 0xd800]).is_err());
+        assert!(Args::parse_args_wide(&[0, 0xdc00]).is_err());
+    }
+
+    #[test]
+    fn parse_wide_owned() {
+        for line in &["", " ", "a \"b c\"", "a b\0c", "\0a", "😅 \"x"] {
+            let wide = line.encode_utf16().collect::<Vec<_>>();
+            let expected = Args::parse_cmd_wide(&wide).unwrap().collect::<Vec<_>>();
+            assert_eq!(Args::parse_cmd_wide_owned(wide.clone()).unwrap().collect::<Vec<_>>(), expected);
+
+            let mut spare = Vec::with_capacity(wide.len() + 1);
+            spare.extend_from_slice(&wide);
+            assert_eq!(Args::parse_cmd_wide_owned(spare).unwrap().collect::<Vec<_>>(), expected);
+        }
+        assert!(Args::parse_cmd_wide_owned(vec![0, 0xd800]).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn parse_wide_windows() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let wide = [b'a' as u16, b' ' as u16, 0xd800, b'x' as u16, 0, b'y' as u16];
+        let expected = vec![OsString::from("a"), OsString::from_wide(&[0xd800, b'x' as u16])];
+        assert_eq!(ArgsOs::parse_cmd_wide(&wide).collect::<Vec<_>>(), expected);
+        assert_eq!(ArgsOs::parse_args_wide(&wide).collect::<Vec<_>>(), expected);
+        assert_eq!(ArgsOs::parse_cmd_wide(&[]).collect::<Vec<_>>(), [OsString::new()]);
+        assert_eq!(ArgsOs::parse_args_wide(&[]).count(), 0);
+
+        assert_eq!(ArgsOs::parse_cmd_wide_owned(wide.to_vec()).collect::<Vec<_>>(), expected);
+        assert_eq!(ArgsOs::parse_cmd_wide_owned(wide[..4].to_vec()).collect::<Vec<_>>(), expected);
+        assert_eq!(ArgsOs::parse_cmd_wide_owned(vec![]).collect::<Vec<_>>(), [OsString::new()]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn from_lpcmdline() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let wide = [b'a' as u16, b' ' as u16, 0xd800, 0, b'y' as u16, 0];
+        let args = unsafe { ArgsOs::from_lpcmdline(wide.as_ptr()) };
+        assert_eq!(args.collect::<Vec<_>>(), [OsString::from("a"), OsString::from_wide(&[0xd800])]);
+
+        let args = unsafe { ArgsOs::from_lpcmdline(wide[3..].as_ptr()) };
+        assert_eq!(args.collect::<Vec<_>>(), [OsString::new()]);
+        let args = unsafe { ArgsOs::from_lpcmdline(std::ptr::null()) };
+        assert_eq!(args.collect::<Vec<_>>(), [OsString::new()]);
+    }
+
+    #[test]
+    fn parse_utf8() {
+        let bom = |s: &str| [&b"\xef\xbb\xbf"[..], s.as_bytes()].concat();
+        assert_eq!(Args::parse_args_utf8(&bom(r#""a b" c"#)).unwrap().collect::<Vec<_>>(), ["a b", "c"]);
+        assert_eq!(Args::parse_cmd_utf8(&bom(r#""C:\x y\a.exe" c"#)).unwrap().collect::<Vec<_>>(), [r#"C:\x y\a.exe"#, "c"]);
+        assert_eq!(Args::parse_cmd_utf8(b"").unwrap().collect::<Vec<_>>(), [""]);
+        assert_eq!(Args::parse_args_utf8(&bom("")).unwrap().count(), 0);
+
+        // a continuation byte without a leading byte, in the middle of an argument
+        let err = Args::parse_args_utf8(b"ok \"bad\x80arg\"").unwrap_err();
+        assert_eq!(err.offset(), 7);
+        // a leading byte followed by something other than a continuation byte
+        let err = Args::parse_cmd_utf8(&[&bom("a.exe x")[..], b"\xe2\x82 y"].concat()).unwrap_err();
+        assert_eq!(err.offset(), 10);
+    }
+
+    #[test]
+    fn parse_utf16le_bytes() {
+        let chk = |bytes: &[u8], expected: &[&str]| {
+            assert_eq!(Args::parse_cmd_utf16le_bytes(bytes).unwrap().collect::<Vec<_>>(), expected);
+        };
+        chk(b"", &[""]);
+        chk(b"\xff\xfe", &[""]);
+        chk(b"\xff\xfe\"\0a\0 \0b\0\"\0 \0c\0", &["a b", "c"]);
+        chk(b"a\0 \0b\0\0\0c\0", &["a", "b"]);
+        // a zero byte that is half of a code unit is not a terminator
+        chk(b"a\0 \0\0\x01\0\0", &["a", "\u{100}"]);
+
+        assert_eq!(Args::parse_cmd_utf16le_bytes(b"a\0 ").unwrap_err(), Utf16BytesError::OddLength { len: 3 });
+        assert_eq!(
+            Args::parse_cmd_utf16le_bytes(b"a\0 \0\x00\xd8").unwrap_err(),
+            Utf16BytesError::UnpairedSurrogate { offset: 4 },
+        );
+    }
+
+    #[test]
+    fn parse_utf16_bytes_endianness() {
+        let lines = [r#"EXE a\\\"b c d"#, r#""C:\My Files\a.exe"b c"#, "😅 \"é 😅\"", ""];
+        for line in &lines {
+            let expected = Args::parse_cmd(line).collect::<Vec<_>>();
+            let le = line.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+            let be = line.encode_utf16().flat_map(u16::to_be_bytes).collect::<Vec<_>>();
+            let be_bom = [&[0xfe, 0xff][..], &be].concat();
+
+            assert_eq!(Args::parse_cmd_utf16le_bytes(&le).unwrap().collect::<Vec<_>>(), expected);
+            assert_eq!(Args::parse_cmd_utf16be_bytes(&be).unwrap().collect::<Vec<_>>(), expected);
+            assert_eq!(Args::parse_cmd_utf16be_bytes(&be_bom).unwrap().collect::<Vec<_>>(), expected);
+            for bytes in &[&le, &be, &be_bom] {
+                assert_eq!(Args::parse_cmd_utf16_bytes_auto(bytes).unwrap().collect::<Vec<_>>(), expected);
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn parse_utf16le_bytes_windows() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let args = ArgsOs::parse_cmd_utf16le_bytes(b"\xff\xfea\0 \0\x00\xd8\0\0b\0").unwrap();
+        assert_eq!(args.collect::<Vec<_>>(), [OsString::from("a"), OsString::from_wide(&[0xd800])]);
+        assert!(ArgsOs::parse_cmd_utf16le_bytes(b"a").is_err());
+
+        let wide = [b'"' as u16, 0xdc00, b' ' as u16, b'"' as u16, b' ' as u16, 0xd800];
+        let expected = ArgsOs::parse_cmd_wide(&wide).collect::<Vec<_>>();
+        let le = wide.iter().flat_map(|u| u.to_le_bytes()).collect::<Vec<_>>();
+        let be = wide.iter().flat_map(|u| u.to_be_bytes()).collect::<Vec<_>>();
+        assert_eq!(ArgsOs::parse_cmd_utf16be_bytes(&be).unwrap().collect::<Vec<_>>(), expected);
+        assert_eq!(ArgsOs::parse_cmd_utf16_bytes_auto(&be).unwrap().collect::<Vec<_>>(), expected);
+        assert_eq!(ArgsOs::parse_cmd_utf16_bytes_auto(&le).unwrap().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn display_quoted() {
+        let mut args = Args::parse_cmd(r#"exe a "b c" "" d\"#);
+        assert_eq!(args.display_quoted().to_string(), r#"exe a "b c" "" d\"#);
+        args.next();
+        args.next_back();
+        assert_eq!(args.display_quoted().to_string(), r#"a "b c" """#);
+        assert_eq!(format!("[{}]", args.display_quoted()), r#"[a "b c" ""]"#);
+        assert_eq!(args.len(), 3);
+
+        assert_eq!(Args::parse_args("").display_quoted().to_string(), "");
+    }
+
+    #[test]
+    fn write_args0() {
+        let mut args = Args::parse_cmd(r#"exe "" "a b" 😅"#);
+        args.next();
+
+        let mut out = vec![];
+        args.write_args0(&mut out).unwrap();
+        assert_eq!(out, "\0a b\0😅\0".as_bytes());
+
+        let opts = Args0Options { encoding: Args0Encoding::Utf16Le, trailing_separator: false };
+        let mut out = vec![];
+        args.write_args0_with(&mut out, &opts).unwrap();
+        assert_eq!(out, b"\0\0a\0 \0b\0\0\0\x3d\xd8\x05\xde");
+        assert_eq!(args.len(), 3);
+    }
+
+    #[test]
+    fn args0_roundtrip() {
+        let lists: &[&[&str]] = &[&[], &[""], &["", ""], &["a", "", "b c"], &["😅", "\"x\""]];
+        for &list in lists {
+            for &encoding in &[Args0Encoding::Wtf8, Args0Encoding::Utf16Le] {
+                let args = Args::parse_args(&list.iter().map(|a| Quoted(a).to_string()).collect::<Vec<_>>().join(" "));
+                let mut out = vec![];
+                args.write_args0_with(&mut out, &Args0Options { encoding, trailing_separator: true }).unwrap();
+                let read = Args::from_args0(&out, encoding).unwrap();
+                assert_eq!(read.collect::<Vec<_>>(), list);
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn write_args0_windows() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let wide = [b'a' as u16, b' ' as u16, 0xd800, b' ' as u16, b'"' as u16, b'"' as u16];
+        let args = ArgsOs::parse_cmd(&OsString::from_wide(&wide));
+        let mut out = vec![];
+        args.write_args0(&mut out).unwrap();
+        assert_eq!(out, b"a\0\xed\xa0\x80\0\0");
+
+        for &encoding in &[Args0Encoding::Wtf8, Args0Encoding::Utf16Le] {
+            let mut out = vec![];
+            args.write_args0_with(&mut out, &Args0Options { encoding, trailing_separator: true }).unwrap();
+            let read = ArgsOs::from_args0(&out, encoding).unwrap();
+            assert_eq!(read.collect::<Vec<_>>(), args.slice(..).collect::<Vec<_>>());
+        }
+        assert!(ArgsOs::from_args0(b"", Args0Encoding::Wtf8).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn slice() {
+        let all = || Args::parse_cmd("a b c d e");
+        assert_eq!(all().slice(..).collect::<Vec<_>>(), all().collect::<Vec<_>>());
+        assert_eq!(all().slice(2..2).len(), 0);
+        assert_eq!(all().slice(5..).len(), 0);
+        assert_eq!(all().slice(1..=2).collect::<Vec<_>>(), vec!["b", "c"]);
+        assert_eq!(all().into_slice(1..=2).collect::<Vec<_>>(), vec!["b", "c"]);
+        assert_eq!(all().into_slice(..).len(), 5);
+
+        let mut args = all();
+        args.next();
+        args.next_back();
+        assert_eq!(args.slice(1..).collect::<Vec<_>>(), vec!["c", "d"]);
+        assert_eq!(args.len(), 3);
+        assert_eq!(args.slice(..).into_slice(..1).collect::<Vec<_>>(), vec!["b"]);
+
+        assert!(args.get_slice(..3).is_some());
+        assert!(args.get_slice(..4).is_none());
+        let (start, end) = (3, 2);
+        assert!(args.get_slice(start..end).is_none());
+        assert!(args.get_slice(..=usize::MAX).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_out_of_range() {
+        Args::parse_cmd("a b").slice(1..3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn into_slice_out_of_range() {
+        Args::parse_cmd("a b").into_slice(3..);
+    }
+
+    #[test]
+    fn interior_nul() {
+        assert_eq!(Args::try_parse_cmd("a b").unwrap().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(Args::try_parse_args("").unwrap().count(), 0);
+        assert_eq!(Args::try_parse_cmd("\0").unwrap_err().position(), 0);
+        assert_eq!(Args::try_parse_cmd("foo\0bar baz").unwrap_err().position(), 3);
+        // positions are in code units, relative to the input of parse_args
+        assert_eq!(Args::try_parse_args("😅é \"\0\"").unwrap_err().position(), 5);
+
+        // the unchecked functions still truncate
+        assert_eq!(Args::parse_cmd("foo\0bar baz").collect::<Vec<_>>(), ["foo"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn interior_nul_windows() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let input = OsString::from_wide(&[0xd800, b' ' as u16, 0, b'x' as u16]);
+        assert_eq!(ArgsOs::try_parse_cmd(&input).unwrap_err().position(), 2);
+        assert_eq!(ArgsOs::try_parse_args(&input).unwrap_err().position(), 2);
+        assert_eq!(ArgsOs::try_parse_cmd("a b".as_ref()).unwrap().len(), 2);
+        assert_eq!(ArgsOs::try_parse_args("a b".as_ref()).unwrap().len(), 2);
+    }
+
     #[test]
     fn args_cmd_differences() {
         assert_eq!(Args::parse_cmd("").collect::<Vec<_>>(), vec![String::new()]);
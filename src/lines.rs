@@ -0,0 +1,133 @@
+use std::io::{self, BufRead};
+
+use crate::args::{ArgsWtf8, Mode};
+use crate::Args;
+
+/// Parse each line of a reader as a complete command line, as by [`Args::parse_cmd`].
+///
+/// This is meant for bulk processing of exported logs, with one command line per line.
+/// Lines may end in either `\n` or `\r\n`, and a UTF-8 byte order mark at the beginning of
+/// the input is skipped.  Buffers are reused from one line to the next, so the only
+/// allocations made per line are for the arguments themselves.
+///
+/// Blank lines are not skipped; like any empty input to [`Args::parse_cmd`], they produce
+/// a single empty argument.  As with [`BufRead::lines`], a line that is not valid UTF-8
+/// produces an error of kind [`InvalidData`], and reading continues with the next line.
+///
+/// ```
+/// use windows_args::parse_lines_cmd;
+///
+/// let input = "\u{FEFF}app.exe \"a b\"\r\n\r\nother.exe -x\n";
+/// let lines = parse_lines_cmd(input.as_bytes())
+///     .map(|args| args.map(|args| args.collect::<Vec<_>>()))
+///     .collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(lines, [vec!["app.exe", "a b"], vec![""], vec!["other.exe", "-x"]]);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// [`Args::parse_cmd`]: struct.Args.html#method.parse_cmd
+/// [`BufRead::lines`]: https://doc.rust-lang.org/std/io/trait.BufRead.html#method.lines
+/// [`InvalidData`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidData
+pub fn parse_lines_cmd<R: BufRead>(reader: R) -> impl Iterator<Item=io::Result<Args>> {
+    ParseLines::new(reader, Mode::Cmd)
+}
+
+/// Like [`parse_lines_cmd`], but parses each line as by [`Args::parse_args`].
+///
+/// Blank lines produce no arguments.
+///
+/// [`parse_lines_cmd`]: fn.parse_lines_cmd.html
+/// [`Args::parse_args`]: struct.Args.html#method.parse_args
+pub fn parse_lines_args<R: BufRead>(reader: R) -> impl Iterator<Item=io::Result<Args>> {
+    ParseLines::new(reader, Mode::Args)
+}
+
+struct ParseLines<R> {
+    reader: R,
+    mode: Mode,
+    first: bool,
+    line: String,
+    cur: Vec<u8>,
+}
+
+impl<R: BufRead> ParseLines<R> {
+    fn new(reader: R, mode: Mode) -> Self {
+        ParseLines { reader, mode, first: true, line: String::new(), cur: Vec::new() }
+    }
+}
+
+impl<R: BufRead> Iterator for ParseLines<R> {
+    type Item = io::Result<Args>;
+
+    fn next(&mut self) -> Option<io::Result<Args>> {
+        self.line.clear();
+        let first = std::mem::replace(&mut self.first, false);
+        match self.reader.read_line(&mut self.line) {
+            Ok(0) => return None,
+            Ok(_) => {},
+            Err(e) => return Some(Err(e)),
+        }
+
+        let mut line = self.line.as_str();
+        if first {
+            line = line.strip_prefix('\u{FEFF}').unwrap_or(line);
+        }
+        if let Some(rest) = line.strip_suffix('\n') {
+            line = rest.strip_suffix('\r').unwrap_or(rest);
+        }
+        Some(Ok(Args { inner: ArgsWtf8::parse_str_with(line, self.mode, &mut self.cur) }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(iter: impl Iterator<Item=io::Result<Args>>) -> Vec<Result<Vec<String>, io::ErrorKind>> {
+        iter.map(|args| args.map(Iterator::collect).map_err(|e| e.kind())).collect()
+    }
+
+    #[test]
+    fn line_endings() {
+        let input = "a.exe x\r\nb.exe \"y\r\n\"\n\nc.exe\r";
+        assert_eq!(collect(parse_lines_cmd(input.as_bytes())), [
+            Ok(vec!["a.exe".to_string(), "x".to_string()]),
+            Ok(vec!["b.exe".to_string(), "y".to_string()]),
+            Ok(vec!["".to_string()]),
+            Ok(vec!["".to_string()]),
+            Ok(vec!["c.exe".to_string()]),
+        ]);
+        assert_eq!(collect(parse_lines_args(input.as_bytes())), [
+            Ok(vec!["a.exe".to_string(), "x".to_string()]),
+            Ok(vec!["b.exe".to_string(), "y".to_string()]),
+            Ok(vec!["".to_string()]),
+            Ok(vec![]),
+            Ok(vec!["c.exe\r".to_string()]),
+        ]);
+    }
+
+    #[test]
+    fn bom() {
+        // only at the very beginning
+        let input = "\u{FEFF}a.exe\n\u{FEFF}b.exe";
+        assert_eq!(collect(parse_lines_cmd(input.as_bytes())), [
+            Ok(vec!["a.exe".to_string()]),
+            Ok(vec!["\u{FEFF}b.exe".to_string()]),
+        ]);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let input = b"a.exe\n\xff.exe\nc.exe";
+        assert_eq!(collect(parse_lines_cmd(&input[..])), [
+            Ok(vec!["a.exe".to_string()]),
+            Err(io::ErrorKind::InvalidData),
+            Ok(vec!["c.exe".to_string()]),
+        ]);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(collect(parse_lines_cmd(&b""[..])), []);
+    }
+}
@@ -0,0 +1,158 @@
+//! Conventions of the MSVC toolchain.
+//!
+//! `cl.exe` reads more arguments from two environment variables: those in `CL` come before
+//! the arguments on its command line, and those in `_CL_` come after them.  [`effective_cl_args`]
+//! reconstructs the arguments that the compiler acts upon:
+//!
+//! ```
+//! use windows_args::{msvc, Args};
+//!
+//! let args = Args::parse_cmd(r#"cl.exe /c main.c"#);
+//! let args = msvc::effective_cl_args_str(Some(r#"/O2 "/I C:\My Headers""#), args, Some("/link /DEBUG"));
+//! assert_eq!(args.collect::<Vec<_>>(), [
+//!     "cl.exe", "/O2", r"/I C:\My Headers", "/c", "main.c", "/link", "/DEBUG",
+//! ]);
+//! ```
+//!
+//! The variables are split by the rules of the C runtime (as with [`Compat::CrtModern`] and
+//! [`ParserConfig::parse_args`]).  Because `set` cannot put an equals sign in the value of a
+//! variable, `cl.exe` reads `#` in them as `=` (so `CL=/DVERSION#2` defines `VERSION` as `2`);
+//! this is done here as well.  A variable that is not set, or that contains only whitespace,
+//! contributes nothing.
+//!
+//! [`effective_cl_args`]: fn.effective_cl_args.html
+//! [`Compat::CrtModern`]: ../enum.Compat.html#variant.CrtModern
+//! [`ParserConfig::parse_args`]: ../struct.ParserConfig.html#method.parse_args
+
+use crate::{args, Args, Compat, ParserConfig};
+#[cfg(windows)]
+use crate::ArgsOs;
+#[cfg(windows)]
+use std::ffi::{OsStr, OsString};
+
+/// Where an argument in the output of [`effective_cl_args_annotated`] came from.
+///
+/// [`effective_cl_args_annotated`]: fn.effective_cl_args_annotated.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClArgSource {
+    /// The `CL` environment variable.
+    Cl,
+    /// The command line, including the executable name.
+    CommandLine,
+    /// The `_CL_` environment variable.
+    TrailingCl,
+}
+
+/// **Windows only.**
+/// The arguments that `cl.exe` acts upon, given the values of `CL` and `_CL_` and the
+/// arguments on its command line (beginning with the executable name).
+///
+/// The executable name stays first; the arguments from `CL` follow it, then the rest of the
+/// command line, then the arguments from `_CL_`.  See the [module documentation](index.html)
+/// for how the variables are split.
+#[cfg(windows)]
+pub fn effective_cl_args(cl_env: Option<&OsStr>, cmdline_args: ArgsOs, trailing_env: Option<&OsStr>) -> ArgsOs {
+    let args = effective_cl_args_annotated(cl_env, cmdline_args, trailing_env);
+    ArgsOs { inner: args::ArgsWtf8::from_vec(args.into_iter().map(|(_, arg)| arg).collect()) }
+}
+
+/// **Windows only.**
+/// Like [`effective_cl_args`], but with the source of each argument.
+///
+/// [`effective_cl_args`]: fn.effective_cl_args.html
+#[cfg(windows)]
+pub fn effective_cl_args_annotated(
+    cl_env: Option<&OsStr>,
+    cmdline_args: ArgsOs,
+    trailing_env: Option<&OsStr>,
+) -> Vec<(ClArgSource, OsString)> {
+    let split = |env: Option<&OsStr>| -> Vec<OsString> {
+        match env {
+            Some(env) => crt_config().parse_args_os(env).expect("no limits were set").map(|arg| {
+                let bytes = arg.as_encoded_bytes().iter().map(|&b| if b == b'#' { b'=' } else { b }).collect::<Vec<u8>>();
+                // SAFETY: replacing one ASCII character with another keeps the encoding valid
+                unsafe { OsString::from_encoded_bytes_unchecked(bytes) }
+            }).collect(),
+            None => vec![],
+        }
+    };
+    stitch(split(cl_env), cmdline_args, split(trailing_env))
+}
+
+/// Like [`effective_cl_args`], for `str`.
+///
+/// [`effective_cl_args`]: fn.effective_cl_args.html
+pub fn effective_cl_args_str(cl_env: Option<&str>, cmdline_args: Args, trailing_env: Option<&str>) -> Args {
+    let split = |env: Option<&str>| -> Vec<String> {
+        match env {
+            Some(env) => crt_config().parse_args(env).expect("no limits were set").map(|arg| arg.replace('#', "=")).collect(),
+            None => vec![],
+        }
+    };
+    let args = stitch(split(cl_env), cmdline_args, split(trailing_env));
+    let args = args.into_iter().map(|(_, arg)| wtf8::Wtf8Buf::from_string(arg)).collect();
+    Args { inner: args::ArgsWtf8::from_vec(args) }
+}
+
+fn crt_config() -> ParserConfig {
+    ParserConfig::new().compat(Compat::CrtModern)
+}
+
+fn stitch<T>(cl: Vec<T>, mut cmdline_args: impl Iterator<Item=T>, trailing: Vec<T>) -> Vec<(ClArgSource, T)> {
+    let mut out = vec![];
+    out.extend(cmdline_args.next().map(|exe| (ClArgSource::CommandLine, exe)));
+    out.extend(cl.into_iter().map(|arg| (ClArgSource::Cl, arg)));
+    out.extend(cmdline_args.map(|arg| (ClArgSource::CommandLine, arg)));
+    out.extend(trailing.into_iter().map(|arg| (ClArgSource::TrailingCl, arg)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chk(cl: Option<&str>, cmd: &str, trailing: Option<&str>, expected: &[&str]) {
+        let args = effective_cl_args_str(cl, Args::parse_cmd(cmd), trailing);
+        assert_eq!(args.collect::<Vec<_>>(), expected, "{:?} {:?} {:?}", cl, cmd, trailing);
+    }
+
+    #[test]
+    fn order() {
+        chk(Some("/W4 /O2"), "cl.exe a.c", Some("/link x.lib"), &["cl.exe", "/W4", "/O2", "a.c", "/link", "x.lib"]);
+        chk(Some("/W4"), "cl.exe", None, &["cl.exe", "/W4"]);
+        chk(None, "cl.exe a.c", Some("/Zi"), &["cl.exe", "a.c", "/Zi"]);
+    }
+
+    #[test]
+    fn quoting() {
+        chk(Some(r#""/FI C:\a b\pch.h" /D"X=\"1\"""#), "cl.exe", None, &["cl.exe", r"/FI C:\a b\pch.h", r#"/DX="1""#]);
+        // the CRT rules, not those of CommandLineToArgvW
+        chk(Some(r#""a"" b" c"#), "cl.exe", None, &["cl.exe", r#"a" b"#, "c"]);
+        chk(Some("/DVERSION#2 \"/DA B#C\""), "cl.exe", None, &["cl.exe", "/DVERSION=2", "/DA B=C"]);
+        // the command line itself is left alone
+        chk(None, "cl.exe /DA#1", None, &["cl.exe", "/DA#1"]);
+    }
+
+    #[test]
+    fn empty_variables() {
+        chk(Some(""), "cl.exe a.c", Some(" \t "), &["cl.exe", "a.c"]);
+        chk(Some(r#""""#), "cl.exe a.c", None, &["cl.exe", "", "a.c"]);
+        let args = effective_cl_args_str(Some("/a"), Args::parse_args(""), Some("/b"));
+        assert_eq!(args.collect::<Vec<_>>(), ["/a", "/b"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn annotated() {
+        let cmd = ArgsOs::parse_cmd(OsStr::new("cl.exe a.c"));
+        let args = effective_cl_args_annotated(Some(OsStr::new("/DA#1")), cmd, Some(OsStr::new("/Zi")));
+        assert_eq!(args, [
+            (ClArgSource::CommandLine, "cl.exe".into()),
+            (ClArgSource::Cl, "/DA=1".into()),
+            (ClArgSource::CommandLine, "a.c".into()),
+            (ClArgSource::TrailingCl, "/Zi".into()),
+        ]);
+        let cmd = ArgsOs::parse_cmd(OsStr::new("cl.exe a.c"));
+        assert_eq!(effective_cl_args(None, cmd, Some(OsStr::new("/Zi"))).collect::<Vec<_>>(), ["cl.exe", "a.c", "/Zi"]);
+    }
+}
@@ -0,0 +1,285 @@
+//! **Windows only. Requires the `nt` feature.**
+//! Command lines as counted `UNICODE_STRING`s, as found in `RTL_USER_PROCESS_PARAMETERS`.
+//!
+//! These are for tools that call `NtCreateUserProcess` or build process parameters by hand,
+//! and for analyzing the parameters of other processes.
+
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::os::windows::ffi::OsStrExt;
+use crate::ArgsOs;
+use crate::nul::{InteriorNulError, check_no_nul};
+
+/// The largest byte length that fits in a `UNICODE_STRING` and is a whole number of code units.
+const MAX_LENGTH_BYTES: usize = 0xfffe;
+
+/// The buffer of a `UNICODE_STRING` holding a command line, along with its lengths.
+///
+/// ```no_run
+/// # use std::ffi::OsStr;
+/// use windows_args::nt::CommandLineUnicodeString;
+///
+/// let cmd = CommandLineUnicodeString::new(OsStr::new("app.exe --flag"), true).unwrap();
+/// assert_eq!(cmd.length_bytes(), 28);
+/// assert_eq!(cmd.maximum_length_bytes(), 30);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandLineUnicodeString {
+    // the content, plus a NUL if nul_terminated
+    wide: Vec<u16>,
+    nul_terminated: bool,
+}
+
+impl CommandLineUnicodeString {
+    /// Copy a complete command line into a new buffer, optionally followed by a NUL.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the command line contains a NUL, or if it (and the terminator) would not fit
+    /// in the 16-bit byte lengths of a `UNICODE_STRING`.
+    pub fn new(cmd_line: &OsStr, nul_terminate: bool) -> Result<Self, UnicodeStringError> {
+        let mut wide = cmd_line.encode_wide().collect::<Vec<_>>();
+        check_no_nul(&wide).map_err(UnicodeStringError::InteriorNul)?;
+
+        let units = wide.len() + usize::from(nul_terminate);
+        if units * 2 > MAX_LENGTH_BYTES {
+            return Err(UnicodeStringError::TooLong { units });
+        }
+        if nul_terminate {
+            wide.push(0);
+        }
+        Ok(CommandLineUnicodeString { wide, nul_terminated: nul_terminate })
+    }
+
+    /// The value for `UNICODE_STRING::Length`: the size of the content in bytes,
+    /// not counting any terminator.
+    pub fn length_bytes(&self) -> u16 {
+        (2 * self.content().len()) as u16
+    }
+
+    /// The value for `UNICODE_STRING::MaximumLength`: the size of the whole buffer in bytes,
+    /// including any terminator.
+    pub fn maximum_length_bytes(&self) -> u16 {
+        (2 * self.wide.len()) as u16
+    }
+
+    /// The value for `UNICODE_STRING::Buffer`.
+    ///
+    /// The pointer is valid for as long as this value is neither moved nor dropped.
+    pub fn as_ptr(&self) -> *const u16 {
+        self.wide.as_ptr()
+    }
+
+    /// Whether the content is followed by a NUL.
+    pub fn is_nul_terminated(&self) -> bool {
+        self.nul_terminated
+    }
+
+    /// The content, not including any terminator.
+    pub fn as_wide(&self) -> &[u16] {
+        self.content()
+    }
+
+    fn content(&self) -> &[u16] {
+        &self.wide[..self.wide.len() - usize::from(self.nul_terminated)]
+    }
+}
+
+/// Parse the command line held in the buffer of a `UNICODE_STRING`, as a child process would.
+///
+/// Only the first `length_bytes` bytes of `buffer` are read, so the buffer need not be
+/// NUL-terminated.  An odd length is rounded down to a whole code unit, and a length that
+/// exceeds the buffer is clamped to it.  As with any command line, parsing stops at a NUL.
+pub fn parse_unicode_string(buffer: &[u16], length_bytes: u16) -> ArgsOs {
+    let units = usize::min(usize::from(length_bytes) / 2, buffer.len());
+    ArgsOs::parse_cmd_wide(&buffer[..units])
+}
+
+impl ArgsOs {
+    /// **Requires the `nt` feature.**
+    /// Parse the command line held in a `UNICODE_STRING` whose buffer was captured as raw bytes,
+    /// e.g. from the process parameters block in a minidump.
+    ///
+    /// `bytes` is the captured buffer, read as little-endian UTF-16, and `length_bytes` is the
+    /// `Length` field.  Unlike [`parse_unicode_string`], this does not trust the length: as it
+    /// may come from a corrupted dump, it must be even and must fit in the buffer.  The buffer
+    /// need not be aligned or NUL-terminated, and anything past `length_bytes` is ignored.
+    ///
+    /// ```no_run
+    /// use std::ffi::OsString;
+    /// use windows_args::ArgsOs;
+    ///
+    /// // "a.exe x", followed by whatever else was captured
+    /// let bytes = b"a\0.\0e\0x\0e\0 \0x\0\xcd\xcd";
+    /// let args = ArgsOs::parse_cmd_unicode_string(bytes, 14).unwrap();
+    /// assert_eq!(args.collect::<Vec<_>>(), [OsString::from("a.exe"), OsString::from("x")]);
+    /// ```
+    pub fn parse_cmd_unicode_string(bytes: &[u8], length_bytes: u16) -> Result<ArgsOs, UnicodeStringBytesError> {
+        let length = usize::from(length_bytes);
+        if !length.is_multiple_of(2) {
+            return Err(UnicodeStringBytesError::OddLength { length_bytes });
+        }
+        let content = bytes.get(..length).ok_or(UnicodeStringBytesError::ExceedsBuffer {
+            length_bytes,
+            buffer_len: bytes.len(),
+        })?;
+
+        let mut wide = Vec::with_capacity(length / 2 + 1);
+        wide.extend(content.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])));
+        Ok(ArgsOs::parse_cmd_wide_owned(wide))
+    }
+}
+
+/// The fields of a captured `UNICODE_STRING` are inconsistent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnicodeStringBytesError {
+    /// `Length` was not a whole number of code units.
+    OddLength { length_bytes: u16 },
+    /// `Length` was larger than the captured buffer.
+    ExceedsBuffer { length_bytes: u16, buffer_len: usize },
+}
+
+impl fmt::Display for UnicodeStringBytesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnicodeStringBytesError::OddLength { length_bytes } => {
+                write!(f, "UNICODE_STRING length {} is odd", length_bytes)
+            },
+            UnicodeStringBytesError::ExceedsBuffer { length_bytes, buffer_len } => {
+                write!(f, "UNICODE_STRING length {} exceeds the {}-byte buffer", length_bytes, buffer_len)
+            },
+        }
+    }
+}
+
+impl Error for UnicodeStringBytesError {}
+
+/// A command line could not be stored in a `UNICODE_STRING`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnicodeStringError {
+    /// The command line contained a NUL.
+    InteriorNul(InteriorNulError),
+    /// The buffer would need this many code units (including any terminator), which
+    /// exceeds the 16-bit byte length.
+    TooLong { units: usize },
+}
+
+impl fmt::Display for UnicodeStringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnicodeStringError::InteriorNul(e) => fmt::Display::fmt(e, f),
+            UnicodeStringError::TooLong { units } => {
+                write!(f, "{} code units do not fit in a UNICODE_STRING", units)
+            },
+        }
+    }
+}
+
+impl Error for UnicodeStringError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UnicodeStringError::InteriorNul(e) => Some(e),
+            UnicodeStringError::TooLong { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+
+    fn args(args: ArgsOs) -> Vec<OsString> {
+        args.collect()
+    }
+
+    #[test]
+    fn roundtrip() {
+        for &nul_terminate in &[false, true] {
+            let cmd = CommandLineUnicodeString::new(OsStr::new(r#"app.exe "a b" c"#), nul_terminate).unwrap();
+            assert_eq!(cmd.length_bytes(), 30);
+            assert_eq!(cmd.maximum_length_bytes(), if nul_terminate { 32 } else { 30 });
+
+            let buffer = unsafe { std::slice::from_raw_parts(cmd.as_ptr(), usize::from(cmd.maximum_length_bytes()) / 2) };
+            assert_eq!(args(parse_unicode_string(buffer, cmd.length_bytes())), ["app.exe", "a b", "c"]);
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let cmd = CommandLineUnicodeString::new(OsStr::new(""), false).unwrap();
+        assert_eq!((cmd.length_bytes(), cmd.maximum_length_bytes()), (0, 0));
+        let cmd = CommandLineUnicodeString::new(OsStr::new(""), true).unwrap();
+        assert_eq!((cmd.length_bytes(), cmd.maximum_length_bytes()), (0, 2));
+        assert_eq!(args(parse_unicode_string(&[], 0)), [""]);
+    }
+
+    #[test]
+    fn maximum_length() {
+        let at_limit = OsString::from("a".repeat(0x7fff));
+        let cmd = CommandLineUnicodeString::new(&at_limit, false).unwrap();
+        assert_eq!(cmd.maximum_length_bytes(), 0xfffe);
+        assert_eq!(
+            CommandLineUnicodeString::new(&at_limit, true),
+            Err(UnicodeStringError::TooLong { units: 0x8000 }),
+        );
+        let cmd = CommandLineUnicodeString::new(OsStr::new(&"a".repeat(0x7ffe)), true).unwrap();
+        assert_eq!((cmd.length_bytes(), cmd.maximum_length_bytes()), (0xfffc, 0xfffe));
+    }
+
+    #[test]
+    fn lengths_are_respected() {
+        let buffer = "app.exe a b".encode_utf16().collect::<Vec<_>>();
+        assert_eq!(args(parse_unicode_string(&buffer, 18)), ["app.exe", "a"]);
+        assert_eq!(args(parse_unicode_string(&buffer, 19)), ["app.exe", "a"]);
+        assert_eq!(args(parse_unicode_string(&buffer, 0xffff)), ["app.exe", "a", "b"]);
+    }
+
+    #[test]
+    fn captured_bytes() {
+        // laid out as in a dump: the content, no terminator, then unrelated memory
+        let mut bytes = r#""C:\Program Files\app.exe" -v"#.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+        let length = bytes.len() as u16;
+        bytes.extend_from_slice(b"\xcd\xcd\xcd\xcd\x00\xd8");
+
+        let parse = |bytes: &[u8], length| ArgsOs::parse_cmd_unicode_string(bytes, length).map(args);
+        assert_eq!(parse(&bytes, length), Ok(vec![r#"C:\Program Files\app.exe"#.into(), "-v".into()]));
+        // unaligned
+        assert_eq!(parse(&[&[0][..], &bytes].concat()[1..], length), parse(&bytes, length));
+        // truncated
+        assert_eq!(parse(&bytes, 6), Ok(vec!["C:".into()]));
+        assert_eq!(parse(&bytes, 0), Ok(vec!["".into()]));
+        assert_eq!(parse(&[], 0), Ok(vec!["".into()]));
+
+        let with_surrogate = [0x61, 0x00, 0x20, 0x00, 0x00, 0xd8];
+        assert_eq!(parse(&with_surrogate, 6), Ok(vec!["a".into(), OsString::from_wide(&[0xd800])]));
+    }
+
+    #[test]
+    fn captured_bytes_corrupt() {
+        let bytes = [0x61, 0x00, 0x62, 0x00];
+        assert_eq!(
+            ArgsOs::parse_cmd_unicode_string(&bytes, 3).unwrap_err(),
+            UnicodeStringBytesError::OddLength { length_bytes: 3 },
+        );
+        assert_eq!(
+            ArgsOs::parse_cmd_unicode_string(&bytes, 6).unwrap_err(),
+            UnicodeStringBytesError::ExceedsBuffer { length_bytes: 6, buffer_len: 4 },
+        );
+        assert_eq!(
+            ArgsOs::parse_cmd_unicode_string(&[], 0xfffe).unwrap_err(),
+            UnicodeStringBytesError::ExceedsBuffer { length_bytes: 0xfffe, buffer_len: 0 },
+        );
+    }
+
+    #[test]
+    fn interior_nul() {
+        let err = CommandLineUnicodeString::new(OsStr::new("a\0b"), true).unwrap_err();
+        match err {
+            UnicodeStringError::InteriorNul(e) => assert_eq!(e.position(), 1),
+            e => panic!("{:?}", e),
+        }
+    }
+}
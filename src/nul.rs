@@ -0,0 +1,33 @@
+use std::error::Error;
+use std::fmt;
+
+/// A command line contained a NUL, which cannot be passed to a process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InteriorNulError {
+    position: usize,
+}
+
+impl InteriorNulError {
+    /// The offset of the first NUL, in UTF-16 code units.
+    pub fn position(&self) -> usize { self.position }
+}
+
+impl fmt::Display for InteriorNulError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "command line contains a NUL at code unit {}", self.position)
+    }
+}
+
+impl Error for InteriorNulError {}
+
+#[cfg(windows)]
+pub(crate) fn check_no_nul(wide: &[u16]) -> Result<(), InteriorNulError> {
+    check_no_nul_in(wide.iter().cloned())
+}
+
+pub(crate) fn check_no_nul_in(mut units: impl Iterator<Item=u16>) -> Result<(), InteriorNulError> {
+    match units.position(|unit| unit == 0) {
+        Some(position) => Err(InteriorNulError { position }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,55 @@
+//! Parallel iteration, behind the `rayon` feature.
+//!
+//! These are all indexed parallel iterators, so e.g. `collect` preserves argument order.
+
+#[cfg(windows)]
+use std::ffi::{OsStr, OsString};
+use rayon::prelude::*;
+use wtf8::Wtf8Buf;
+use crate::{Args, expect_still_utf8, expect_still_utf8_ref};
+#[cfg(windows)]
+use crate::ArgsOs;
+
+/// **Requires the `rayon` feature.**
+impl IntoParallelIterator for Args {
+    type Iter = rayon::iter::Map<rayon::vec::IntoIter<Wtf8Buf>, fn(Wtf8Buf) -> String>;
+    type Item = String;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.inner.into_vec().into_par_iter().map(expect_still_utf8 as fn(_) -> _)
+    }
+}
+
+/// **Requires the `rayon` feature.**
+/// This provides `Args::par_iter`.
+impl<'a> IntoParallelIterator for &'a Args {
+    type Iter = rayon::iter::Map<rayon::slice::Iter<'a, Wtf8Buf>, fn(&'a Wtf8Buf) -> &'a str>;
+    type Item = &'a str;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.inner.as_slice().par_iter().map(|arg| expect_still_utf8_ref(arg))
+    }
+}
+
+/// **Requires the `rayon` feature.**
+#[cfg(windows)]
+impl IntoParallelIterator for ArgsOs {
+    type Iter = rayon::vec::IntoIter<OsString>;
+    type Item = OsString;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.inner.into_vec().into_par_iter()
+    }
+}
+
+/// **Requires the `rayon` feature.**
+/// This provides `ArgsOs::par_iter`.
+#[cfg(windows)]
+impl<'a> IntoParallelIterator for &'a ArgsOs {
+    type Iter = rayon::iter::Map<rayon::slice::Iter<'a, OsString>, fn(&'a OsString) -> &'a OsStr>;
+    type Item = &'a OsStr;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.inner.as_slice().par_iter().map(|arg| arg.as_os_str())
+    }
+}
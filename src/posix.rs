@@ -0,0 +1,221 @@
+//! Splitting and joining of command lines by the word-splitting rules of a POSIX shell.
+//!
+//! These have nothing to do with Windows, but an application that runs on both may want to
+//! choose its rules per platform, or apply the same rules everywhere:
+//!
+//! ```
+//! use windows_args::posix;
+//!
+//! let args = posix::split(r#"cc -o 'my app' "-DNAME=\"x\"" a\ b.c"#)?;
+//! assert_eq!(args, ["cc", "-o", "my app", r#"-DNAME="x""#, "a b.c"]);
+//! assert_eq!(posix::join(&args), r#"cc -o 'my app' '-DNAME="x"' 'a b.c'"#);
+//! # Ok::<(), windows_args::posix::PosixParseError>(())
+//! ```
+//!
+//! The rules are those of the POSIX shell command language, without any expansion:
+//!
+//! * Spaces, tabs and newlines separate arguments.  Quoted and unquoted pieces that touch are
+//!   joined into one argument.
+//! * Outside of quotes, a backslash escapes the next character, and a backslash followed by
+//!   a newline is removed.
+//! * In single quotes, everything is literal.  There is no way to write a single quote inside
+//!   of them.
+//! * In double quotes, a backslash escapes only `$`, `` ` ``, `"`, `\` and a newline (which is
+//!   then removed); before any other character, it is an ordinary character.
+//! * A `#` at the beginning of an argument starts a comment that runs to the end of the line.
+//!
+//! Characters such as `$`, `*` and `|` are ordinary characters, since nothing is expanded or
+//! interpreted.  An unterminated quote, or a backslash at the very end of the input, is an
+//! error.
+//!
+//! [`join`] quotes each argument so that [`split`] returns it unchanged.
+//!
+//! [`split`]: fn.split.html
+//! [`join`]: fn.join.html
+
+use std::error::Error;
+use std::fmt;
+
+/// A command line could not be split by [`split`].
+///
+/// Offsets are in bytes.
+///
+/// [`split`]: fn.split.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PosixParseError {
+    /// The quote at this offset is never closed.
+    UnterminatedQuote { offset: usize },
+    /// The input ends with a backslash at this offset, which has nothing to escape.
+    TrailingBackslash { offset: usize },
+}
+
+impl fmt::Display for PosixParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PosixParseError::UnterminatedQuote { offset } => write!(f, "quote at offset {} is never closed", offset),
+            PosixParseError::TrailingBackslash { offset } => write!(f, "trailing backslash at offset {}", offset),
+        }
+    }
+}
+
+impl Error for PosixParseError {}
+
+/// Split a command line into arguments by the rules of a POSIX shell.
+///
+/// See the [module documentation](index.html) for the rules.
+pub fn split(input: &str) -> Result<Vec<String>, PosixParseError> {
+    let mut chars = input.char_indices().peekable();
+    let mut args = vec![];
+    loop {
+        while let Some((_, ' ')) | Some((_, '\t')) | Some((_, '\n')) = chars.peek() {
+            chars.next();
+        }
+        match chars.peek() {
+            None => return Ok(args),
+            Some((_, '#')) => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+                continue;
+            },
+            Some(_) => {},
+        }
+
+        let mut arg = String::new();
+        let mut any = false;
+        while let Some((pos, c)) = chars.next() {
+            match c {
+                ' ' | '\t' | '\n' => break,
+                '\\' => match chars.next() {
+                    None => return Err(PosixParseError::TrailingBackslash { offset: pos }),
+                    // a line continuation is removed entirely, and does not begin an argument
+                    Some((_, '\n')) => continue,
+                    Some((_, c)) => arg.push(c),
+                },
+                '\'' => loop {
+                    match chars.next() {
+                        None => return Err(PosixParseError::UnterminatedQuote { offset: pos }),
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => arg.push(c),
+                    }
+                },
+                '"' => loop {
+                    match chars.next() {
+                        None => return Err(PosixParseError::UnterminatedQuote { offset: pos }),
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next_if(|&(_, c)| matches!(c, '$' | '`' | '"' | '\\' | '\n')) {
+                            Some((_, '\n')) => {},
+                            Some((_, c)) => arg.push(c),
+                            None => arg.push('\\'),
+                        },
+                        Some((_, c)) => arg.push(c),
+                    }
+                },
+                _ => arg.push(c),
+            }
+            any = true;
+        }
+        if any {
+            args.push(arg);
+        }
+    }
+}
+
+/// Join arguments into a command line that [`split`] (or a POSIX shell) turns back into them.
+///
+/// Arguments that contain only letters, digits and a few harmless punctuation characters are
+/// written as they are.  Others are put in single quotes, with each single quote written as
+/// `'\''`.
+///
+/// ```
+/// use windows_args::posix;
+///
+/// assert_eq!(posix::join(["echo", "it's", "", "$HOME"]), r#"echo 'it'\''s' '' '$HOME'"#);
+/// ```
+///
+/// [`split`]: fn.split.html
+pub fn join<S: AsRef<str>>(args: impl IntoIterator<Item=S>) -> String {
+    let mut out = String::new();
+    for (i, arg) in args.into_iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        push_quoted(&mut out, arg.as_ref());
+    }
+    out
+}
+
+fn push_quoted(out: &mut String, arg: &str) {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || !c.is_ascii() || ",._+:@%/-".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        out.push_str(arg);
+        return;
+    }
+    out.push('\'');
+    for c in arg.chars() {
+        match c {
+            '\'' => out.push_str(r"'\''"),
+            _ => out.push(c),
+        }
+    }
+    out.push('\'');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::short_strings::short_strs;
+
+    fn chk(input: &str, expected: &[&str]) {
+        assert_eq!(split(input), Ok(expected.iter().map(|s| s.to_string()).collect()), "{:?}", input);
+    }
+
+    #[test]
+    fn quoting() {
+        chk("", &[]);
+        chk(" \t\n a \t\n b ", &["a", "b"]);
+        chk(r#"a 'b c' "d e""#, &["a", "b c", "d e"]);
+        chk(r#"'' "" a''b"#, &["", "", "ab"]);
+        chk(r#"a"b c"'d e'f"#, &["ab cd ef"]);
+        chk(r#"'"\' "'" '$x*'"#, &[r#""\"#, "'", "$x*"]);
+    }
+
+    #[test]
+    fn backslashes() {
+        chk(r#"a\ b \'c \"d \\ \x"#, &["a b", "'c", "\"d", "\\", "x"]);
+        chk(r#""\$ \` \" \\ \x \'""#, &[r#"$ ` " \ \x \'"#]);
+        chk("a\\\nb \"c\\\nd\" e\\\n", &["ab", "cd", "e"]);
+        chk("\\\n a", &["a"]);
+        chk("'a\\\nb'", &["a\\\nb"]);
+    }
+
+    #[test]
+    fn comments() {
+        chk("a #b c\nd", &["a", "d"]);
+        chk("a#b '#c' \\#d", &["a#b", "#c", "#d"]);
+        chk("# only a comment", &[]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(split("a 'b"), Err(PosixParseError::UnterminatedQuote { offset: 2 }));
+        assert_eq!(split(r#"a "b\""#), Err(PosixParseError::UnterminatedQuote { offset: 2 }));
+        assert_eq!(split("é\"'"), Err(PosixParseError::UnterminatedQuote { offset: 2 }));
+        assert_eq!(split(r"a \"), Err(PosixParseError::TrailingBackslash { offset: 2 }));
+        assert_eq!(split(r"a \\"), Ok(vec!["a".to_string(), "\\".to_string()]));
+    }
+
+    #[test]
+    fn joining() {
+        assert_eq!(join(Vec::<String>::new()), "");
+        assert_eq!(join(["a", "-x=1", "C:/é.txt"]), "a '-x=1' C:/é.txt");
+        assert_eq!(join(["", "a b", "'", "#"]), r#"'' 'a b' ''\''' '#'"#);
+    }
+
+    #[test]
+    fn join_round_trips() {
+        let alphabet = ['a', ' ', '\n', '\'', '"', '\\', '#', '$'];
+        for arg in short_strs(&alphabet, 4) {
+            let args = [&arg[..], "x", &arg[..]];
+            assert_eq!(split(&join(args)), Ok(args.iter().map(|s| s.to_string()).collect()), "{:?}", arg);
+        }
+    }
+}
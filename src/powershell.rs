@@ -0,0 +1,378 @@
+//! Splitting of command lines written in PowerShell syntax.
+//!
+//! PowerShell parses the arguments of a command itself, by rules unrelated to those of
+//! `CommandLineToArgvW`.  [`split`] implements its argument-mode tokenization, for command lines
+//! that users copy from a PowerShell prompt:
+//!
+//! ```
+//! use windows_args::powershell;
+//!
+//! let args = powershell::split(r#"app.exe 'C:\My Files' "say ""hi""" a` b $env:TEMP"#)?;
+//! let values = args.iter().map(|arg| &arg.value[..]).collect::<Vec<_>>();
+//! assert_eq!(values, ["app.exe", r"C:\My Files", r#"say "hi""#, "a b", "$env:TEMP"]);
+//! assert!(args[4].dynamic);
+//! # Ok::<(), windows_args::powershell::PsParseError>(())
+//! ```
+//!
+//! The supported subset is:
+//!
+//! * Spaces and tabs separate arguments.  Quoted and unquoted pieces that touch are joined
+//!   into one argument, so `a"b c"'d'` is `ab cd`.
+//! * In single quotes, everything is literal, and `''` is a single quote.
+//! * In double quotes, `""` is a double quote, and a backtick escapes the next character.
+//! * Outside of quotes, a backtick also escapes the next character (including a space).
+//! * The escape sequences `` `0 ``, `` `a ``, `` `b ``, `` `e ``, `` `f ``, `` `n ``,
+//!   `` `r ``, `` `t ``, `` `v `` and `` `u{XXXX} `` produce the usual control characters.
+//! * Typographic quotes (`‘ ’ ‚ ‛` and `“ ” „`) work like the ASCII ones, as in PowerShell.
+//! * A `#` at the beginning of an argument starts a comment that runs to the end of the line.
+//!
+//! Nothing is evaluated.  An argument that PowerShell would evaluate, because it contains a
+//! variable (`$name`, `${name}`) or a subexpression (`$(...)`) outside of single quotes, is
+//! returned exactly as written, with [`PsArg::dynamic`] set.
+//!
+//! Everything else is an error rather than a guess: the statement separators and operators
+//! `;`, `|`, `&`, `<`, `>`, `,` and line breaks; a `(`, `{` or `@` at the beginning of an
+//! argument; and the stop-parsing token `--%`, which the error locates so that the caller can
+//! handle what follows it.
+//!
+//! [`split`]: fn.split.html
+//! [`PsArg::dynamic`]: struct.PsArg.html#structfield.dynamic
+
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// An argument produced by [`split`].
+///
+/// [`split`]: fn.split.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsArg {
+    /// The value of the argument, or its text as written if it is `dynamic`.
+    pub value: String,
+    /// Whether PowerShell would evaluate a variable or subexpression to produce this argument.
+    pub dynamic: bool,
+}
+
+/// A command line could not be split by [`split`].
+///
+/// Offsets are in bytes.
+///
+/// [`split`]: fn.split.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PsParseError {
+    /// The quote at this offset is never closed.
+    UnterminatedString { offset: usize },
+    /// The input ends with a backtick at this offset, which would continue the line.
+    TrailingBacktick { offset: usize },
+    /// This character is special in PowerShell, and is not supported.
+    Unsupported { offset: usize, c: char },
+    /// The stop-parsing token `--%` begins at this offset.
+    StopParsing { offset: usize },
+}
+
+impl fmt::Display for PsParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PsParseError::UnterminatedString { offset } => write!(f, "string at offset {} is never closed", offset),
+            PsParseError::TrailingBacktick { offset } => write!(f, "trailing backtick at offset {}", offset),
+            PsParseError::Unsupported { offset, c } => write!(f, "unsupported {:?} at offset {}", c, offset),
+            PsParseError::StopParsing { offset } => write!(f, "stop-parsing token at offset {}", offset),
+        }
+    }
+}
+
+impl Error for PsParseError {}
+
+/// Split a command line by the rules of PowerShell's argument mode.
+///
+/// See the [module documentation](index.html) for what is supported.
+pub fn split(input: &str) -> Result<Vec<PsArg>, PsParseError> {
+    let mut chars = input.char_indices().peekable();
+    let mut args = vec![];
+    loop {
+        while let Some((_, ' ')) | Some((_, '\t')) = chars.peek() {
+            chars.next();
+        }
+        let start = match chars.peek() {
+            None | Some((_, '#')) => return Ok(args),
+            Some(&(pos, '(')) | Some(&(pos, '{')) | Some(&(pos, '@')) => {
+                return Err(PsParseError::Unsupported { offset: pos, c: input[pos..].chars().next().unwrap() });
+            },
+            Some(&(pos, _)) => pos,
+        };
+        let rest = &input[start..];
+        if rest.starts_with("--%") && rest[3..].chars().next().is_none_or(is_space) {
+            return Err(PsParseError::StopParsing { offset: start });
+        }
+        args.push(parse_arg(input, &mut chars)?);
+    }
+}
+
+fn parse_arg(input: &str, chars: &mut Peekable<CharIndices<'_>>) -> Result<PsArg, PsParseError> {
+    let start = chars.peek().map_or(input.len(), |&(pos, _)| pos);
+    let mut value = String::new();
+    let mut dynamic = false;
+    while let Some(&(pos, c)) = chars.peek() {
+        match c {
+            ' ' | '\t' => break,
+            ';' | '|' | '&' | '<' | '>' | ',' | '\r' | '\n' => {
+                return Err(PsParseError::Unsupported { offset: pos, c });
+            },
+            _ if is_single_quote(c) => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        None => return Err(PsParseError::UnterminatedString { offset: pos }),
+                        Some((_, c)) if is_single_quote(c) => match chars.peek() {
+                            Some(&(_, next)) if is_single_quote(next) => {
+                                chars.next();
+                                value.push(next);
+                            },
+                            _ => break,
+                        },
+                        Some((_, c)) => value.push(c),
+                    }
+                }
+            },
+            _ if is_double_quote(c) => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        None => return Err(PsParseError::UnterminatedString { offset: pos }),
+                        Some((_, c)) if is_double_quote(c) => match chars.peek() {
+                            Some(&(_, next)) if is_double_quote(next) => {
+                                chars.next();
+                                value.push(next);
+                            },
+                            _ => break,
+                        },
+                        Some((pos, '`')) => value.push(parse_escape(pos, chars)?),
+                        Some((_, '$')) => {
+                            dynamic |= skip_variable(input, chars);
+                            value.push('$');
+                        },
+                        Some((_, c)) => value.push(c),
+                    }
+                }
+            },
+            '`' => {
+                chars.next();
+                value.push(parse_escape(pos, chars)?);
+            },
+            '$' => {
+                chars.next();
+                dynamic |= skip_variable(input, chars);
+                value.push('$');
+            },
+            _ => {
+                chars.next();
+                value.push(c);
+            },
+        }
+    }
+
+    if dynamic {
+        let end = chars.peek().map_or(input.len(), |&(pos, _)| pos);
+        value = input[start..end].to_string();
+    }
+    Ok(PsArg { value, dynamic })
+}
+
+/// Parse what follows a backtick at `pos`.
+fn parse_escape(pos: usize, chars: &mut Peekable<CharIndices<'_>>) -> Result<char, PsParseError> {
+    let c = match chars.next() {
+        Some((_, c)) => c,
+        None => return Err(PsParseError::TrailingBacktick { offset: pos }),
+    };
+    Ok(match c {
+        '0' => '\0',
+        'a' => '\x07',
+        'b' => '\x08',
+        'e' => '\x1b',
+        'f' => '\x0c',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'v' => '\x0b',
+        'u' if chars.peek().map(|&(_, c)| c) == Some('{') => {
+            // `u{XXXX}, with up to six hex digits
+            let mut lookahead = chars.clone();
+            lookahead.next();
+            let mut code = 0u32;
+            let mut digits = 0;
+            while let Some((_, d)) = lookahead.next_if(|&(_, d)| d.is_ascii_hexdigit() && digits < 6) {
+                code = code * 16 + d.to_digit(16).unwrap();
+                digits += 1;
+            }
+            match (digits, lookahead.next(), char::from_u32(code)) {
+                (1..=6, Some((_, '}')), Some(c)) => {
+                    *chars = lookahead;
+                    c
+                },
+                _ => 'u',
+            }
+        },
+        c => c,
+    })
+}
+
+/// Skip the rest of a variable reference or subexpression after a `$`, returning whether there
+/// was one.  A `$` that begins neither is an ordinary character.
+fn skip_variable(input: &str, chars: &mut Peekable<CharIndices<'_>>) -> bool {
+    match chars.peek().map(|&(_, c)| c) {
+        Some('(') => {
+            // a subexpression, as written; parentheses in quotes do not count
+            let mut depth = 0;
+            let mut quote = None;
+            for (_, c) in chars.by_ref() {
+                match (quote, c) {
+                    (Some(q), c) if c == q => quote = None,
+                    (Some(_), _) => {},
+                    (None, '\'') | (None, '"') => quote = Some(c),
+                    (None, '(') => depth += 1,
+                    (None, ')') => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    },
+                    _ => {},
+                }
+            }
+            true
+        },
+        Some('{') => {
+            for (_, c) in chars.by_ref() {
+                if c == '}' {
+                    break;
+                }
+            }
+            true
+        },
+        Some('$') | Some('?') | Some('^') => {
+            chars.next();
+            true
+        },
+        Some(c) if is_name_char(c) => {
+            while chars.next_if(|&(pos, c)| is_name_char(c) || (c == ':' && is_scope_colon(input, pos))).is_some() {}
+            true
+        },
+        _ => false,
+    }
+}
+
+/// Whether the `:` at `pos` separates a scope or drive from a variable name, as in `$env:PATH`.
+fn is_scope_colon(input: &str, pos: usize) -> bool {
+    input[pos + 1..].chars().next().is_some_and(is_name_char)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_space(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_single_quote(c: char) -> bool {
+    matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}')
+}
+
+fn is_double_quote(c: char) -> bool {
+    matches!(c, '"' | '\u{201C}' | '\u{201D}' | '\u{201E}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(input: &str) -> Vec<String> {
+        let args = split(input).unwrap();
+        assert!(args.iter().all(|arg| !arg.dynamic), "{:?}", input);
+        args.into_iter().map(|arg| arg.value).collect()
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(values(r#"a 'b c' "d e""#), ["a", "b c", "d e"]);
+        assert_eq!(values(r"'it''s' '' 'a\b'"), ["it's", "", r"a\b"]);
+        assert_eq!(values(r#""say ""hi""" """#), [r#"say "hi""#, ""]);
+        assert_eq!(values(r#"a"b c"d 'a'"b"c"#), ["ab cd", "abc"]);
+        assert_eq!(values(r#""it's" 'say "hi"'"#), ["it's", r#"say "hi""#]);
+        assert_eq!(values("\u{201C}a b\u{201D} \u{2018}c d\u{2019}"), ["a b", "c d"]);
+        assert_eq!(values(" \t a \t "), ["a"]);
+        assert_eq!(values(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn backticks() {
+        assert_eq!(values(r#""a`"b" a`"b a`'b"#), [r#"a"b"#, r#"a"b"#, "a'b"]);
+        assert_eq!(values("a` b ``"), ["a b", "`"]);
+        assert_eq!(values(r#""tab`there" `n "`u{1F605}" "`u{}""#), ["tab\there", "\n", "😅", "u{}"]);
+        assert_eq!(values(r"'`n' `x"), ["`n", "x"]);
+        assert_eq!(values(r#""cost: `$5" `$x"#), ["cost: $5", "$x"]);
+    }
+
+    #[test]
+    fn dynamic() {
+        let chk = |input: &str, expected: &[(&str, bool)]| {
+            let args = split(input).unwrap();
+            let args = args.iter().map(|arg| (&arg.value[..], arg.dynamic)).collect::<Vec<_>>();
+            assert_eq!(args, expected, "{:?}", input);
+        };
+        chk("$env:PATH", &[("$env:PATH", true)]);
+        chk(r#""$x.txt" '$x'"#, &[(r#""$x.txt""#, true), ("$x", false)]);
+        chk("$(Get-Date -f 'yy)').txt x", &[("$(Get-Date -f 'yy)').txt", true), ("x", false)]);
+        chk("${a b}c $$ $?", &[("${a b}c", true), ("$$", true), ("$?", true)]);
+        chk("a$ $ $:", &[("a$", false), ("$", false), ("$:", false)]);
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(values("a #b c"), ["a"]);
+        assert_eq!(values("a#b"), ["a#b"]);
+        assert_eq!(values("'#' # x"), ["#"]);
+    }
+
+    fn decode_fixture_field(field: &str) -> String {
+        assert!(field.len().is_multiple_of(4), "bad fixture field: {:?}", field);
+        let units = (0..field.len()).step_by(4)
+            .map(|i| u16::from_str_radix(&field[i..i + 4], 16).expect("bad fixture field"))
+            .collect::<Vec<_>>();
+        String::from_utf16(&units).expect("bad fixture field")
+    }
+
+    /// Expected output of `pwsh`, which `tests/powershell.rs` can replace with a recording.
+    #[test]
+    fn pwsh_fixture() {
+        let fixture = include_str!("../tests/fixture/pwsh.txt");
+        let mut lines = fixture.lines().filter(|line| !line.starts_with('#'));
+        assert_eq!(lines.next(), Some("version: 1"));
+        assert!(lines.next().unwrap().starts_with("source: "));
+
+        let mut count = 0;
+        for line in lines {
+            let mut fields = line.split('\t').map(decode_fixture_field);
+            let input = fields.next().unwrap();
+            assert_eq!(values(&input), fields.collect::<Vec<_>>(), "input: {:?}", input);
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(split("a 'bc"), Err(PsParseError::UnterminatedString { offset: 2 }));
+        assert_eq!(split(r#"a "b`""#), Err(PsParseError::UnterminatedString { offset: 2 }));
+        assert_eq!(split("a`"), Err(PsParseError::TrailingBacktick { offset: 1 }));
+        assert_eq!(split("a | b"), Err(PsParseError::Unsupported { offset: 2, c: '|' }));
+        assert_eq!(split("a;b"), Err(PsParseError::Unsupported { offset: 1, c: ';' }));
+        assert_eq!(split("a,b"), Err(PsParseError::Unsupported { offset: 1, c: ',' }));
+        assert_eq!(split("a\nb"), Err(PsParseError::Unsupported { offset: 1, c: '\n' }));
+        assert_eq!(split("a (1 + 2)"), Err(PsParseError::Unsupported { offset: 2, c: '(' }));
+        assert_eq!(split("a @args"), Err(PsParseError::Unsupported { offset: 2, c: '@' }));
+        assert_eq!(split("a --% b 'c"), Err(PsParseError::StopParsing { offset: 2 }));
+        assert_eq!(values("a --%b"), ["a", "--%b"]);
+    }
+}
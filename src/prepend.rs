@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+
+use crate::quote::push_quoted_bytes;
+use crate::split::split_exe_bytes;
+
+/// Prepend a program and its arguments to an existing command line.
+///
+/// This is how an Image File Execution Options `Debugger` value is applied.  Parsing the
+/// result with [`ArgsOs::parse_cmd`] produces `prefix_exe`, then `prefix_args`, then all of
+/// the arguments of `original` (starting with its executable name):
+///
+/// ```
+/// use windows_args::prepend_cmd;
+///
+/// let cmd = prepend_cmd(
+///     r"C:\Debuggers\dbg.exe".as_ref(),
+///     &["-g".as_ref(), "-o".as_ref()],
+///     r#""C:\Program Files\app.exe" /x "a b""#.as_ref(),
+/// )?;
+/// assert_eq!(cmd, r#""C:\Debuggers\dbg.exe" -g -o "C:\Program Files\app.exe" /x "a b""#);
+/// # Ok::<(), windows_args::PrependCmdError>(())
+/// ```
+///
+/// Only the executable name of `original` is requoted (because it was parsed by different
+/// rules than the arguments); the rest of it is copied exactly as written.  As with
+/// [`ArgsOs::parse_cmd`], everything in `original` from the first NUL onwards is ignored.
+///
+/// The executable name is always quoted, so it must not contain a quote.  Neither it nor the
+/// arguments may contain a NUL.
+///
+/// [`ArgsOs::parse_cmd`]: struct.ArgsOs.html#method.parse_cmd
+pub fn prepend_cmd(
+    prefix_exe: &OsStr,
+    prefix_args: &[&OsStr],
+    original: &OsStr,
+) -> Result<OsString, PrependCmdError> {
+    let args = prefix_args.iter().map(|arg| arg.as_encoded_bytes());
+    let bytes = prepend_cmd_bytes(prefix_exe.as_encoded_bytes(), args, original.as_encoded_bytes())?;
+    // SAFETY: the output is made of the input strings, split and joined only next to ASCII
+    // characters, which are valid places to split the encoding
+    Ok(unsafe { OsString::from_encoded_bytes_unchecked(bytes) })
+}
+
+/// Compose the command line in an ASCII-compatible encoding.
+fn prepend_cmd_bytes<'a>(
+    prefix_exe: &[u8],
+    prefix_args: impl Iterator<Item=&'a [u8]>,
+    original: &[u8],
+) -> Result<Vec<u8>, PrependCmdError> {
+    if prefix_exe.contains(&b'"') {
+        return Err(PrependCmdError::QuoteInExe);
+    }
+    if prefix_exe.contains(&0) {
+        return Err(PrependCmdError::NulInExe);
+    }
+
+    let mut out = Vec::with_capacity(prefix_exe.len() + original.len() + 8);
+    out.push(b'"');
+    out.extend_from_slice(prefix_exe);
+    out.push(b'"');
+    for (index, arg) in prefix_args.enumerate() {
+        if arg.contains(&0) {
+            return Err(PrependCmdError::NulInArg { index });
+        }
+        out.push(b' ');
+        push_quoted_bytes(&mut out, arg);
+    }
+
+    let (exe, rest) = split_exe_bytes(original);
+    out.push(b' ');
+    push_quoted_bytes(&mut out, &original[exe]);
+    let mut rest = &original[rest];
+    // other control characters only end an unquoted executable name, and would be part of
+    // an argument anywhere else
+    if original.first() != Some(&b'"') && rest.first().is_some_and(|&c| c < b' ' && c != b'\t') {
+        rest = &rest[1..];
+    }
+    if !rest.is_empty() {
+        if rest[0] != b' ' && rest[0] != b'\t' {
+            out.push(b' ');
+        }
+        out.extend_from_slice(rest);
+    }
+    Ok(out)
+}
+
+/// A command line could not be prepended by [`prepend_cmd`].
+///
+/// [`prepend_cmd`]: fn.prepend_cmd.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrependCmdError {
+    /// The executable name contains a quote, so it cannot be quoted.
+    QuoteInExe,
+    /// The executable name contains a NUL.
+    NulInExe,
+    /// The argument at this index (in `prefix_args`) contains a NUL.
+    NulInArg { index: usize },
+}
+
+impl fmt::Display for PrependCmdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrependCmdError::QuoteInExe => write!(f, "executable name contains a quote"),
+            PrependCmdError::NulInExe => write!(f, "executable name contains a NUL"),
+            PrependCmdError::NulInArg { index } => write!(f, "argument {} contains a NUL", index),
+        }
+    }
+}
+
+impl Error for PrependCmdError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use crate::short_strings::short_strs;
+
+    fn prepend(exe: &str, args: &[&str], original: &str) -> Result<String, PrependCmdError> {
+        let args = args.iter().map(|arg| arg.as_ref()).collect::<Vec<_>>();
+        prepend_cmd(exe.as_ref(), &args, original.as_ref()).map(|s| s.into_string().unwrap())
+    }
+
+    fn chk(exe: &str, args: &[&str], original: &str) {
+        let cmd = prepend(exe, args, original).unwrap();
+        let mut expected = vec![exe.to_string()];
+        expected.extend(args.iter().map(|arg| arg.to_string()));
+        expected.extend(Args::parse_cmd(original));
+        assert_eq!(Args::parse_cmd(&cmd).collect::<Vec<_>>(), expected, "{:?} from {:?}", cmd, original);
+        #[cfg(windows)]
+        assert_eq!(
+            crate::ArgsOs::parse_cmd(cmd.as_ref()).collect::<Vec<_>>(),
+            expected.iter().map(|arg| arg.into()).collect::<Vec<std::ffi::OsString>>(),
+        );
+    }
+
+    #[test]
+    fn edge_cases() {
+        chk(r"C:\Debuggers\dbg.exe", &[], r"app.exe");
+        chk(r"C:\Program Files\dbg.exe", &["-p", "a b", "", r#"\"x\"#], r#""C:\Program Files\app.exe" "a b""#);
+        chk(r"C:\dir\", &[r"C:\dir\"], r#""C:\dir\"\x"#);
+        chk("dbg.exe", &[], r#"C:\a"b\app.exe "c d"#);
+        chk("dbg.exe", &[], "");
+        chk("dbg.exe", &[], "  \t");
+        chk("dbg.exe", &[], "\"unterminated exe");
+        chk("dbg.exe", &[], "app.exe x\0hidden\"");
+        chk("", &[], "\x01app.exe\x01x");
+        chk("dbg.exe", &[], "\"app.exe\"\x01x");
+        assert_eq!(prepend("dbg.exe", &[], "app.exe \"a b\"  c").unwrap(), "\"dbg.exe\" app.exe \"a b\"  c");
+        assert_eq!(prepend("dbg.exe", &[], "\"a b\"c").unwrap(), "\"dbg.exe\" \"a b\" c");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(prepend("dbg\".exe", &[], "app.exe"), Err(PrependCmdError::QuoteInExe));
+        assert_eq!(prepend("dbg\0.exe", &[], "app.exe"), Err(PrependCmdError::NulInExe));
+        assert_eq!(prepend("dbg.exe", &["a", "b\0"], "app.exe"), Err(PrependCmdError::NulInArg { index: 1 }));
+    }
+
+    #[test]
+    fn exhaustive() {
+        let alphabet = ['a', '"', '\\', ' ', '\t', '\0'];
+        for original in short_strs(&alphabet, 5) {
+            chk(r"C:\dbg\", &[r"a\", ""], &original);
+        }
+    }
+}
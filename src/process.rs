@@ -0,0 +1,72 @@
+use std::ffi::{c_void, OsString};
+use std::ptr;
+use crate::{Args, ArgsOs};
+use crate::args::ArgsWtf8;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetCommandLineW() -> *const u16;
+    fn GetModuleFileNameW(hModule: *mut c_void, lpFilename: *mut u16, nSize: u32) -> u32;
+}
+
+/// The path of the current executable, as given by `GetModuleFileNameW`.
+pub(crate) fn module_file_name() -> Option<Vec<u16>> {
+    // the longest path that Windows supports is 32767 code units
+    let mut buf = vec![0u16; 260];
+    loop {
+        let len = unsafe { GetModuleFileNameW(ptr::null_mut(), buf.as_mut_ptr(), buf.len() as u32) } as usize;
+        if len == 0 {
+            return None;
+        }
+        if len < buf.len() {
+            buf.truncate(len);
+            return Some(buf);
+        }
+        // the path was truncated
+        if buf.len() > 32767 {
+            return None;
+        }
+        buf.resize(buf.len() * 2, 0);
+    }
+}
+
+/// **Windows only.**
+/// The arguments of the current process, as returned by `GetCommandLineW`.
+///
+/// Unlike `std::env::args_os`, this reads the command line anew on every call, so it reflects
+/// any modifications made to the process environment block since startup.  Nothing is leaked,
+/// and the command line is kept for [`ArgsOs::raw`], which costs one copy of it.
+///
+/// If the command line is empty, the first argument is the path of the current executable
+/// (as `CommandLineToArgvW` does), rather than the empty string that [`ArgsOs::parse_cmd`]
+/// would produce.
+///
+/// [`ArgsOs::parse_cmd`]: struct.ArgsOs.html#method.parse_cmd
+/// [`ArgsOs::raw`]: struct.ArgsOs.html#method.raw
+pub fn args_os() -> ArgsOs {
+    let ptr = unsafe { GetCommandLineW() };
+    if ptr.is_null() || unsafe { *ptr } == 0 {
+        let exe = std::env::current_exe().map(|path| path.into_os_string()).unwrap_or_default();
+        return ArgsOs { inner: ArgsWtf8::from_vec(vec![exe]) };
+    }
+    unsafe { ArgsOs::from_lpcmdline(ptr) }
+}
+
+/// **Windows only.**
+/// Like [`args_os`], but yielding `String`s.
+///
+/// # Panics
+///
+/// Like `std::env::args`, this panics if any argument is not valid Unicode.
+/// Use [`args_os`] to handle such arguments.
+///
+/// [`args_os`]: fn.args_os.html
+pub fn args() -> Args {
+    // an unpaired surrogate in the command line is always part of some argument, so the
+    // command line converts whenever the arguments do
+    let inner = args_os().inner.convert(|arg: OsString| match arg.into_string() {
+        Ok(arg) => wtf8::Wtf8Buf::from_string(arg),
+        Err(arg) => panic!("argument is not valid Unicode: {:?}", arg),
+    });
+    Args { inner }
+}
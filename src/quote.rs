@@ -0,0 +1,208 @@
+use std::fmt::{self, Write};
+#[cfg(windows)]
+use std::ffi::{OsStr, OsString};
+
+/// Display adaptor that quotes a single argument, so that it survives [`Args::parse_args`].
+///
+/// The quoting is streamed directly into the formatter, so nothing is allocated.
+/// Quotes are only added when necessary (i.e. when the argument is empty or contains
+/// whitespace), and embedded quotes are escaped with backslashes.
+///
+/// ```
+/// use windows_args::Quoted;
+///
+/// let path = r#"C:\Program Files\"#;
+/// assert_eq!(format!("run.exe {} -v", Quoted(path)), r#"run.exe "C:\Program Files\\" -v"#);
+/// assert_eq!(format!("{}", Quoted(r#"a"b"#)), r#"a\"b"#);
+/// ```
+///
+/// This is not suitable for the executable name at the beginning of a command line,
+/// which follows different rules.  Arguments that contain NUL cannot be represented.
+///
+/// [`Args::parse_args`]: struct.Args.html#method.parse_args
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quoted<'a>(pub &'a str);
+
+/// **Windows only.**
+/// Like [`Quoted`], but for an [`OsStr`].
+///
+/// Unpaired surrogates cannot be written to a formatter (which only accepts valid UTF-8),
+/// so when formatting, each one is rendered as `U+FFFD REPLACEMENT CHARACTER`, as
+/// [`ArgsOs::display_quoted`] does.  Use [`QuotedOs::to_os_string`] to keep them.
+///
+/// ```
+/// use std::ffi::OsString;
+/// use std::os::windows::ffi::OsStringExt;
+/// use windows_args::QuotedOs;
+///
+/// let arg = OsString::from_wide(&[0x61, 0x20, 0xd800]);
+/// assert_eq!(QuotedOs(&arg).to_string(), "\"a \u{FFFD}\"");
+/// assert_eq!(QuotedOs(&arg).to_os_string(), OsString::from_wide(&[0x22, 0x61, 0x20, 0xd800, 0x22]));
+/// ```
+///
+/// [`OsStr`]: https://doc.rust-lang.org/std/ffi/struct.OsStr.html
+/// [`ArgsOs::display_quoted`]: struct.ArgsOs.html#method.display_quoted
+/// [`QuotedOs::to_os_string`]: #method.to_os_string
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotedOs<'a>(pub &'a OsStr);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_quoted(f, self.0.chars())
+    }
+}
+
+#[cfg(windows)]
+impl fmt::Display for QuotedOs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::os::windows::ffi::OsStrExt;
+
+        let chars = std::char::decode_utf16(self.0.encode_wide())
+            .map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER));
+        write_quoted(f, chars)
+    }
+}
+
+#[cfg(windows)]
+impl QuotedOs<'_> {
+    /// The quoted argument, with any unpaired surrogates preserved.
+    ///
+    /// Unlike formatting, this is lossless, but it allocates.
+    pub fn to_os_string(&self) -> OsString {
+        let mut out = Vec::with_capacity(self.0.len() + 2);
+        push_quoted_bytes(&mut out, self.0.as_encoded_bytes());
+        // SAFETY: the output is the input with ASCII characters inserted only next to other
+        // ASCII characters or at either end, which are valid places to split the encoding
+        unsafe { OsString::from_encoded_bytes_unchecked(out) }
+    }
+}
+
+/// Displays a list of arguments, each quoted and separated by single spaces.
+///
+/// Each item of the iterator is the characters of one argument.
+#[derive(Clone)]
+pub(crate) struct DisplayQuoted<I>(pub(crate) I);
+
+impl<I, C> fmt::Display for DisplayQuoted<I>
+where
+    I: Iterator<Item=C> + Clone,
+    C: Iterator<Item=char> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, chars) in self.0.clone().enumerate() {
+            if i > 0 {
+                f.write_char(' ')?;
+            }
+            write_quoted(f, chars)?;
+        }
+        Ok(())
+    }
+}
+
+fn needs_quotes(mut chars: impl Iterator<Item=char>) -> bool {
+    let mut empty = true;
+    let found = chars.any(|c| {
+        empty = false;
+        c == ' ' || c == '\t'
+    });
+    empty || found
+}
+
+pub(crate) fn write_quoted<W: fmt::Write>(
+    w: &mut W,
+    chars: impl Iterator<Item=char> + Clone,
+) -> fmt::Result {
+    write_quoted_with(w, chars, false)
+}
+
+/// Like `write_quoted`, but optionally adds quotes even when they are not necessary.
+pub(crate) fn write_quoted_with<W: fmt::Write>(
+    w: &mut W,
+    chars: impl Iterator<Item=char> + Clone,
+    force_quotes: bool,
+) -> fmt::Result {
+    let quote = force_quotes || needs_quotes(chars.clone());
+    if quote {
+        w.write_char('"')?;
+    }
+
+    let mut backslashes = 0;
+    for c in chars {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // double the preceding backslashes, and escape the quote itself
+                write_backslashes(w, backslashes + 1)?;
+                backslashes = 0;
+            },
+            _ => backslashes = 0,
+        }
+        w.write_char(c)?;
+    }
+
+    if quote {
+        // backslashes before the closing quote must be doubled
+        write_backslashes(w, backslashes)?;
+        w.write_char('"')?;
+    }
+    Ok(())
+}
+
+/// Like `write_quoted`, but for an argument in an ASCII-compatible encoding (such as the
+/// encoded bytes of an `OsStr`), so that nothing is lost to lossy conversion.
+pub(crate) fn push_quoted_bytes(out: &mut Vec<u8>, arg: &[u8]) {
+    let quote = arg.is_empty() || arg.iter().any(|&b| b == b' ' || b == b'\t');
+    if quote {
+        out.push(b'"');
+    }
+
+    let mut backslashes = 0;
+    for &b in arg {
+        match b {
+            b'\\' => backslashes += 1,
+            b'"' => {
+                // double the preceding backslashes, and escape the quote itself
+                out.extend(std::iter::repeat_n(b'\\', backslashes + 1));
+                backslashes = 0;
+            },
+            _ => backslashes = 0,
+        }
+        out.push(b);
+    }
+
+    if quote {
+        // backslashes before the closing quote must be doubled
+        out.extend(std::iter::repeat_n(b'\\', backslashes));
+        out.push(b'"');
+    }
+}
+
+fn write_backslashes<W: fmt::Write>(w: &mut W, n: usize) -> fmt::Result {
+    (0..n).try_for_each(|_| w.write_char('\\'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chk(arg: &str, expected: &str) {
+        assert_eq!(Quoted(arg).to_string(), expected);
+    }
+
+    #[test]
+    fn quoting() {
+        chk("", r#""""#);
+        chk("abc", "abc");
+        chk("a b", r#""a b""#);
+        chk("a\tb", "\"a\tb\"");
+        chk(r#"a\b"#, r#"a\b"#);
+        chk(r#"a\"#, r#"a\"#);
+        chk(r#"a b\"#, r#""a b\\""#);
+        chk(r#"a b\\"#, r#""a b\\\\""#);
+        chk(r#"""#, r#"\""#);
+        chk(r#"\""#, r#"\\\""#);
+        chk(r#"a "b" c"#, r#""a \"b\" c""#);
+        chk("😅 é", "\"😅 é\"");
+    }
+}
@@ -0,0 +1,230 @@
+//! Reading the command lines of other processes.
+
+use std::convert::TryFrom;
+use std::ffi::c_void;
+use std::io;
+use std::mem;
+use std::ptr;
+use crate::ArgsOs;
+use crate::nt::parse_unicode_string;
+
+type Handle = *mut c_void;
+
+const PROCESS_VM_READ: u32 = 0x0010;
+const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+
+// values of PROCESSINFOCLASS
+const PROCESS_BASIC_INFORMATION: u32 = 0;
+const PROCESS_WOW64_INFORMATION: u32 = 26;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn OpenProcess(dwDesiredAccess: u32, bInheritHandle: i32, dwProcessId: u32) -> Handle;
+    fn CloseHandle(hObject: Handle) -> i32;
+    fn ReadProcessMemory(
+        hProcess: Handle,
+        lpBaseAddress: *const c_void,
+        lpBuffer: *mut c_void,
+        nSize: usize,
+        lpNumberOfBytesRead: *mut usize,
+    ) -> i32;
+}
+
+#[link(name = "ntdll")]
+extern "system" {
+    fn NtQueryInformationProcess(
+        ProcessHandle: Handle,
+        ProcessInformationClass: u32,
+        ProcessInformation: *mut c_void,
+        ProcessInformationLength: u32,
+        ReturnLength: *mut u32,
+    ) -> i32;
+    fn RtlNtStatusToDosError(Status: i32) -> u32;
+}
+
+/// `PROCESS_BASIC_INFORMATION`, of which only `PebBaseAddress` is used.
+#[repr(C)]
+struct BasicInformation {
+    _exit_status: i32,
+    peb_base_address: usize,
+    _rest: [usize; 4],
+}
+
+/// Where to find `RTL_USER_PROCESS_PARAMETERS.CommandLine`, for one pointer size.
+struct Layout {
+    pointer_size: usize,
+    /// The offset of `PEB.ProcessParameters`.
+    parameters: u64,
+    /// The offset of `RTL_USER_PROCESS_PARAMETERS.CommandLine`.
+    command_line: u64,
+    /// The offset of `UNICODE_STRING.Buffer`.
+    buffer: u64,
+}
+
+const LAYOUT_32: Layout = Layout { pointer_size: 4, parameters: 0x10, command_line: 0x40, buffer: 4 };
+const LAYOUT_64: Layout = Layout { pointer_size: 8, parameters: 0x20, command_line: 0x70, buffer: 8 };
+
+struct Process(Handle);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+impl Process {
+    fn open(pid: u32) -> io::Result<Process> {
+        let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION | PROCESS_VM_READ, 0, pid) };
+        match handle.is_null() {
+            true => Err(io::Error::last_os_error()),
+            false => Ok(Process(handle)),
+        }
+    }
+
+    fn query<T>(&self, class: u32, out: &mut T) -> io::Result<()> {
+        let size = mem::size_of::<T>() as u32;
+        let status = unsafe { NtQueryInformationProcess(self.0, class, out as *mut T as *mut c_void, size, ptr::null_mut()) };
+        match status {
+            0 => Ok(()),
+            _ => Err(io::Error::from_raw_os_error(unsafe { RtlNtStatusToDosError(status) } as i32)),
+        }
+    }
+
+    fn read(&self, address: u64, buf: &mut [u8]) -> io::Result<()> {
+        let address = usize::try_from(address).map_err(|_| invalid_data("address is out of range"))?;
+        let mut read = 0;
+        let ok = unsafe {
+            ReadProcessMemory(self.0, address as *const c_void, buf.as_mut_ptr() as *mut c_void, buf.len(), &mut read)
+        };
+        match (ok, read == buf.len()) {
+            (0, _) => Err(io::Error::last_os_error()),
+            (_, false) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "partial read of process memory")),
+            (_, true) => Ok(()),
+        }
+    }
+
+    fn read_pointer(&self, address: u64, layout: &Layout) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        self.read(address, &mut bytes[..layout.pointer_size])?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// The address of the PEB, and the layout of the structures that it leads to.
+    fn peb(&self) -> io::Result<(u64, &'static Layout)> {
+        // for a 32-bit process under WOW64, this is the address of its 32-bit PEB
+        let mut wow64_peb = 0usize;
+        self.query(PROCESS_WOW64_INFORMATION, &mut wow64_peb)?;
+        if wow64_peb != 0 {
+            return Ok((wow64_peb as u64, &LAYOUT_32));
+        }
+
+        let mut info: BasicInformation = unsafe { mem::zeroed() };
+        self.query(PROCESS_BASIC_INFORMATION, &mut info)?;
+        if cfg!(target_pointer_width = "32") && running_under_wow64()? {
+            // the target is 64-bit, and its PEB may lie beyond our address space
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "cannot read a 64-bit process from a 32-bit one"));
+        }
+        let layout = if cfg!(target_pointer_width = "64") { &LAYOUT_64 } else { &LAYOUT_32 };
+        Ok((info.peb_base_address as u64, layout))
+    }
+}
+
+fn running_under_wow64() -> io::Result<bool> {
+    // the pseudo-handle of the current process, as returned by `GetCurrentProcess`
+    let current = mem::ManuallyDrop::new(Process(-1isize as Handle));
+    let mut wow64_peb = 0usize;
+    current.query(PROCESS_WOW64_INFORMATION, &mut wow64_peb)?;
+    Ok(wow64_peb != 0)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl ArgsOs {
+    /// **Windows only. Requires the `process` feature.**
+    /// Read and parse the command line of another process, as found in the `CommandLine` field
+    /// of its `RTL_USER_PROCESS_PARAMETERS`.
+    ///
+    /// The process is opened with `PROCESS_QUERY_LIMITED_INFORMATION | PROCESS_VM_READ`, so
+    /// this fails with [`io::ErrorKind::PermissionDenied`] for processes that are protected
+    /// or belong to other users.  Both 64-bit and 32-bit (WOW64) processes can be read from a
+    /// 64-bit program, but a 32-bit program can only read other 32-bit processes.  The command
+    /// line is only what the process was started with (or what it has since written there),
+    /// so a process that changed its own parameters may report something else.
+    ///
+    /// The command line that was read is available from [`ArgsOs::raw`].  An empty command
+    /// line parses to a single empty argument, as it does for [`ArgsOs::parse_cmd`].
+    ///
+    /// ```no_run
+    /// use windows_args::ArgsOs;
+    ///
+    /// let args = ArgsOs::from_pid(std::process::id())?;
+    /// println!("started as {:?}", args.raw().unwrap());
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// [`ArgsOs::raw`]: struct.ArgsOs.html#method.raw
+    /// [`ArgsOs::parse_cmd`]: struct.ArgsOs.html#method.parse_cmd
+    pub fn from_pid(pid: u32) -> io::Result<ArgsOs> {
+        let process = Process::open(pid)?;
+        let (peb, layout) = process.peb()?;
+        let parameters = process.read_pointer(peb + layout.parameters, layout)?;
+        if parameters == 0 {
+            return Err(invalid_data("process has no parameters block"));
+        }
+
+        let command_line = parameters + layout.command_line;
+        let mut length = [0; 2];
+        process.read(command_line, &mut length)?;
+        let length_bytes = u16::from_le_bytes(length);
+        // an odd length is rounded down, as `parse_unicode_string` would do
+        let mut bytes = vec![0; usize::from(length_bytes) & !1];
+        if !bytes.is_empty() {
+            let buffer = process.read_pointer(command_line + layout.buffer, layout)?;
+            process.read(buffer, &mut bytes)?;
+        }
+        let wide = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect::<Vec<_>>();
+        Ok(parse_unicode_string(&wide, length_bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::os::windows::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn current_process() {
+        let args = ArgsOs::from_pid(std::process::id()).unwrap();
+        assert_eq!(args.collect::<Vec<_>>(), std::env::args_os().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn child_process() {
+        // `cmd /k` waits on its standard input, so the child stays alive until it is killed
+        let mut child = Command::new("cmd.exe")
+            .raw_arg(r#"/d /k rem "a  b"\" c"#)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let result = ArgsOs::from_pid(child.id());
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let mut args = result.unwrap();
+        assert!(args.raw().unwrap().to_str().unwrap().ends_with(r#" /d /k rem "a  b"\" c"#));
+        args.next();
+        let expected = ["/d", "/k", "rem", "a  b\"", "c"];
+        assert_eq!(args.collect::<Vec<_>>(), expected.iter().map(OsString::from).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn no_such_process() {
+        // the System Idle Process cannot be opened
+        assert!(ArgsOs::from_pid(0).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::Args;
+
+/// Options for [`Args::parse_cmd_with_response_files`].
+///
+/// [`Args::parse_cmd_with_response_files`]: struct.Args.html#method.parse_cmd_with_response_files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseFileOptions {
+    /// How deeply response files may refer to other response files.  A response file named
+    /// on the command line itself is at depth 1.  Defaults to 16.
+    pub max_depth: usize,
+    /// How to split the contents of a response file.  Defaults to [`RspSyntax::Msvc`].
+    ///
+    /// [`RspSyntax::Msvc`]: enum.RspSyntax.html#variant.Msvc
+    pub syntax: RspSyntax,
+}
+
+impl Default for ResponseFileOptions {
+    fn default() -> Self {
+        ResponseFileOptions { max_depth: 16, syntax: RspSyntax::Msvc }
+    }
+}
+
+/// The rules for splitting the contents of a response file, selected with
+/// [`ResponseFileOptions::syntax`].
+///
+/// The same file can produce different arguments under each, so choose the one of the tool
+/// that will read it.
+///
+/// [`ResponseFileOptions::syntax`]: struct.ResponseFileOptions.html#structfield.syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RspSyntax {
+    /// The rules of `cl.exe` and `link.exe`: each line is split like [`Args::parse_args`], so
+    /// backslashes only matter before a quote, and a quoted region ends with its line.
+    ///
+    /// [`Args::parse_args`]: struct.Args.html#method.parse_args
+    Msvc,
+    /// The rules of GCC (`buildargv` in libiberty), which clang also uses for response files
+    /// on Windows unless it runs as `clang-cl`:
+    ///
+    /// * Spaces, tabs, carriage returns and newlines separate arguments.  Quoted regions may
+    ///   span lines.
+    /// * Both `'` and `"` begin a quoted region, which ends at the same kind of quote.
+    /// * A backslash escapes the next character, inside or outside of quotes.  A backslash at
+    ///   the very end of the file is kept.
+    /// * `""` or `''` on its own is an empty argument.  A quote that is never closed ends
+    ///   with the file.
+    Gnu,
+}
+
+/// Response files could not be expanded by [`Args::parse_cmd_with_response_files`].
+///
+/// [`Args::parse_cmd_with_response_files`]: struct.Args.html#method.parse_cmd_with_response_files
+#[derive(Debug)]
+pub enum ResponseFileError {
+    /// The loader failed to read this file.
+    Io { path: PathBuf, error: io::Error },
+    /// This file would exceed [`ResponseFileOptions::max_depth`].
+    ///
+    /// [`ResponseFileOptions::max_depth`]: struct.ResponseFileOptions.html#structfield.max_depth
+    TooDeep { path: PathBuf },
+    /// This file refers back to itself, directly or through other response files.
+    Cycle { path: PathBuf },
+}
+
+impl fmt::Display for ResponseFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseFileError::Io { path, error } => {
+                write!(f, "could not read response file {}: {}", path.display(), error)
+            },
+            ResponseFileError::TooDeep { path } => {
+                write!(f, "response file {} is nested too deeply", path.display())
+            },
+            ResponseFileError::Cycle { path } => {
+                write!(f, "response file {} includes itself", path.display())
+            },
+        }
+    }
+}
+
+impl Error for ResponseFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResponseFileError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// State of the expansion, shared by all levels of nesting.
+struct Expander<'a, L> {
+    opts: &'a ResponseFileOptions,
+    loader: L,
+    /// The files currently being expanded, outermost first.
+    stack: Vec<PathBuf>,
+    out: Vec<String>,
+}
+
+pub(crate) fn expand_response_files(
+    mut args: impl Iterator<Item=String>,
+    opts: &ResponseFileOptions,
+    loader: impl FnMut(&Path) -> io::Result<String>,
+) -> Result<Vec<String>, ResponseFileError> {
+    let mut expander = Expander { opts, loader, stack: vec![], out: vec![] };
+    // the executable name is never a response file
+    expander.out.extend(args.next());
+    expander.expand(args)?;
+    Ok(expander.out)
+}
+
+impl<L: FnMut(&Path) -> io::Result<String>> Expander<'_, L> {
+    fn expand(&mut self, args: impl Iterator<Item=String>) -> Result<(), ResponseFileError> {
+        for arg in args {
+            match arg.strip_prefix('@') {
+                Some(path) if !path.is_empty() => self.expand_file(Path::new(path))?,
+                _ => self.out.push(arg),
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_file(&mut self, path: &Path) -> Result<(), ResponseFileError> {
+        if self.stack.iter().any(|open| open == path) {
+            return Err(ResponseFileError::Cycle { path: path.to_owned() });
+        }
+        if self.stack.len() >= self.opts.max_depth {
+            return Err(ResponseFileError::TooDeep { path: path.to_owned() });
+        }
+        let contents = (self.loader)(path).map_err(|error| ResponseFileError::Io { path: path.to_owned(), error })?;
+
+        self.stack.push(path.to_owned());
+        let contents = contents.strip_prefix('\u{FEFF}').unwrap_or(&contents);
+        match self.opts.syntax {
+            RspSyntax::Msvc => for line in contents.lines() {
+                self.expand(Args::parse_args(line))?;
+            },
+            RspSyntax::Gnu => self.expand(split_gnu(contents).into_iter())?,
+        }
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+/// Split the contents of a response file by the rules of [`RspSyntax::Gnu`].
+fn split_gnu(contents: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut chars = contents.chars().peekable();
+    loop {
+        while chars.next_if(|&c| is_gnu_space(c)).is_some() {}
+        if chars.peek().is_none() {
+            return args;
+        }
+
+        let mut arg = String::new();
+        let mut quote = None;
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', _) => arg.push(chars.next().unwrap_or('\\')),
+                (c, None) if is_gnu_space(c) => break,
+                ('\'', None) | ('"', None) => quote = Some(c),
+                (c, Some(q)) if c == q => quote = None,
+                (c, _) => arg.push(c),
+            }
+        }
+        args.push(arg);
+    }
+}
+
+fn is_gnu_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn files() -> HashMap<PathBuf, String> {
+        let mut files = HashMap::new();
+        let mut add = |path: &str, contents: &str| files.insert(PathBuf::from(path), contents.to_string());
+        add("a.rsp", "/O2 \"C:\\My Files\\x.c\"\r\n\r\n  /Fe:out.exe @b.rsp\n");
+        add("b.rsp", "\u{FEFF}/nologo \"/D A=\\\"1\\\"\"");
+        add("empty.rsp", "");
+        add("self.rsp", "/x @self.rsp");
+        add("loop1.rsp", "@loop2.rsp");
+        add("loop2.rsp", "@loop1.rsp");
+        add("deep.rsp", "@b.rsp");
+        add("deeper.rsp", "@deep.rsp");
+        add("gnu.rsp", "-I'C:\\My Headers' \"-DX=\\\"1\\\"\"\r\n-o a\\ b.exe \"multi\nline\" '' @gnu2.rsp");
+        add("gnu2.rsp", "C:\\\\dir\\\\x.c C:\\dir\\y.c \\");
+        add("gnu-open.rsp", "-x 'unterminated  ");
+        files
+    }
+
+    fn parse(input: &str, opts: &ResponseFileOptions) -> Result<Vec<String>, ResponseFileError> {
+        let files = files();
+        let loader = |path: &Path| files.get(path).cloned().ok_or_else(|| io::Error::from(io::ErrorKind::NotFound));
+        Args::parse_cmd_with_response_files(input, opts, loader).map(Iterator::collect)
+    }
+
+    #[test]
+    fn expansion() {
+        let opts = ResponseFileOptions::default();
+        assert_eq!(parse("cl.exe @a.rsp /c", &opts).unwrap(), [
+            "cl.exe", "/O2", r"C:\My Files\x.c", "/Fe:out.exe", "/nologo", r#"/D A="1""#, "/c",
+        ]);
+        // quoting does not prevent expansion, but the executable name is never expanded
+        assert_eq!(parse(r#"@b.rsp "@b.rsp""#, &opts).unwrap(), ["@b.rsp", "/nologo", r#"/D A="1""#]);
+        assert_eq!(parse("cl.exe @empty.rsp @ x@b.rsp", &opts).unwrap(), ["cl.exe", "@", "x@b.rsp"]);
+    }
+
+    #[test]
+    fn gnu_syntax() {
+        let gnu = ResponseFileOptions { syntax: RspSyntax::Gnu, ..Default::default() };
+        assert_eq!(parse("clang.exe @gnu.rsp -c", &gnu).unwrap(), [
+            "clang.exe", "-IC:My Headers", r#"-DX="1""#, "-o", "a b.exe", "multi\nline", "",
+            r"C:\dir\x.c", r"C:diry.c", "\\", "-c",
+        ]);
+        assert_eq!(parse("clang.exe @gnu-open.rsp", &gnu).unwrap(), ["clang.exe", "-x", "unterminated  "]);
+
+        // the same file under the MSVC rules
+        let msvc = ResponseFileOptions::default();
+        assert_eq!(parse("cl.exe @gnu2.rsp", &msvc).unwrap(), ["cl.exe", r"C:\\dir\\x.c", r"C:\dir\y.c", "\\"]);
+        assert_eq!(parse("cl.exe @gnu-open.rsp", &msvc).unwrap(), ["cl.exe", "-x", "'unterminated"]);
+    }
+
+    #[test]
+    fn gnu_splitting() {
+        let chk = |contents: &str, expected: &[&str]| assert_eq!(split_gnu(contents), expected, "{:?}", contents);
+        chk("", &[]);
+        chk(" \r\n\t", &[]);
+        chk(r#"a"b c"d 'e f'"#, &["ab cd", "e f"]);
+        chk(r#""it's" 'say "hi"'"#, &["it's", r#"say "hi""#]);
+        chk(r#""a\"b" 'a\'b' \"\'"#, &[r#"a"b"#, "a'b", r#""'"#]);
+        chk(r#""" '' """""#, &["", "", ""]);
+        chk("a\\\nb", &["a\nb"]);
+    }
+
+    #[test]
+    fn errors() {
+        let opts = ResponseFileOptions::default();
+        let path = |err: ResponseFileError| match err {
+            ResponseFileError::Io { path, .. } => ("io", path),
+            ResponseFileError::TooDeep { path } => ("deep", path),
+            ResponseFileError::Cycle { path } => ("cycle", path),
+        };
+        assert_eq!(path(parse("cl.exe @missing.rsp", &opts).unwrap_err()), ("io", "missing.rsp".into()));
+        assert_eq!(path(parse("cl.exe @self.rsp", &opts).unwrap_err()), ("cycle", "self.rsp".into()));
+        assert_eq!(path(parse("cl.exe @loop1.rsp", &opts).unwrap_err()), ("cycle", "loop1.rsp".into()));
+
+        // the same file may appear more than once, as long as it does not contain itself
+        assert!(parse("cl.exe @b.rsp @b.rsp @deep.rsp", &opts).is_ok());
+
+        let opts = ResponseFileOptions { max_depth: 2, ..Default::default() };
+        assert!(parse("cl.exe @deep.rsp", &opts).is_ok());
+        assert_eq!(path(parse("cl.exe @deeper.rsp", &opts).unwrap_err()), ("deep", "b.rsp".into()));
+        let opts = ResponseFileOptions { max_depth: 0, ..Default::default() };
+        assert_eq!(path(parse("cl.exe @b.rsp", &opts).unwrap_err()), ("deep", "b.rsp".into()));
+    }
+}
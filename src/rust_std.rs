@@ -0,0 +1,247 @@
+use std::ops::Range;
+
+use crate::args::{push, push_n, Alloc, CodeUnit, Mode, BACKSLASH, QUOTE, SPACE, TAB};
+use crate::config::DelimiterPolicy;
+
+/// A port of `parse_lp_cmd_line` from the current Rust standard library, which decides what
+/// `std::env::args` returns on Windows.
+///
+/// The structure follows that of std, so that future changes there are easy to carry over:
+///
+/// * In the executable name, a quote always toggles whether spaces and tabs end it, and
+///   backslashes are ordinary characters.
+/// * In the arguments, backslashes are halved before a quote (which is escaped if there were
+///   an odd number of them), and `""` inside a quoted region is a literal quote that does not
+///   end the region.
+///
+/// Otherwise, the interface is the same as that of [`parse_crt`].
+///
+/// [`parse_crt`]: crate::crt::parse_crt
+pub(crate) fn parse_rust_std<A: Alloc, U: CodeUnit>(
+    units: impl IntoIterator<Item=U>,
+    mode: Mode,
+    delimiters: DelimiterPolicy,
+    cur: &mut Vec<U>,
+    mut emit: impl FnMut(&[U], Range<usize>) -> Result<(), A::Error>,
+) -> Result<Option<usize>, A::Error> {
+    let mut units = units.into_iter()
+        .take_while(|&c| c != U::from(0))
+        .enumerate()
+        .map(|(pos, c)| (pos, c, c.into()))
+        .peekable();
+    let backslash = U::from(BACKSLASH as u8);
+    let quote = U::from(QUOTE as u8);
+
+    let mut in_quotes = false;
+    let mut quote_start = 0;
+    if mode == Mode::Cmd {
+        // there are no escapes in the executable name
+        cur.clear();
+        let mut end = 0;
+        for (pos, c, class) in units.by_ref() {
+            match class {
+                QUOTE => {
+                    in_quotes = !in_quotes;
+                    quote_start = pos;
+                },
+                _ if !in_quotes && delimiters.ends_exe(class) => break,
+                _ => push::<A, _>(cur, c)?,
+            }
+            end = pos + 1;
+        }
+        emit(&cur[..], 0..end)?;
+        if in_quotes {
+            // the input ended inside the quotes
+            return Ok(Some(quote_start));
+        }
+    }
+
+    loop {
+        while let Some((_, _, SPACE)) | Some((_, _, TAB)) = units.peek() {
+            units.next();
+        }
+        let start = match units.peek() {
+            Some(&(pos, _, _)) => pos,
+            None => return Ok(in_quotes.then_some(quote_start)),
+        };
+
+        cur.clear();
+        let mut end = start;
+        loop {
+            match units.next() {
+                None => break,
+                Some((_, _, SPACE)) | Some((_, _, TAB)) if !in_quotes => break,
+                Some((pos, _, BACKSLASH)) => {
+                    let mut backslash_count = 1;
+                    end = pos + 1;
+                    while let Some(&(pos, _, BACKSLASH)) = units.peek() {
+                        units.next();
+                        backslash_count += 1;
+                        end = pos + 1;
+                    }
+                    match units.peek() {
+                        Some(&(pos, _, QUOTE)) => {
+                            push_n::<A, U>(cur, backslash, backslash_count / 2)?;
+                            // the quote is escaped by an odd number of backslashes
+                            if backslash_count % 2 == 1 {
+                                units.next();
+                                end = pos + 1;
+                                push::<A, _>(cur, quote)?;
+                            }
+                        },
+                        _ => push_n::<A, U>(cur, backslash, backslash_count)?,
+                    }
+                },
+                Some((pos, _, QUOTE)) if in_quotes => {
+                    end = pos + 1;
+                    match units.peek() {
+                        Some(&(pos, _, QUOTE)) => {
+                            units.next();
+                            end = pos + 1;
+                            push::<A, _>(cur, quote)?;
+                        },
+                        // std keeps `in_quotes` set at the end of the input, but only to make
+                        // sure that the argument is pushed; the region was closed
+                        _ => in_quotes = false,
+                    }
+                },
+                Some((pos, _, QUOTE)) => {
+                    in_quotes = true;
+                    quote_start = pos;
+                    end = pos + 1;
+                },
+                Some((pos, c, _)) => {
+                    push::<A, _>(cur, c)?;
+                    end = pos + 1;
+                },
+            }
+        }
+        emit(&cur[..], start..end)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Compat, ParserConfig};
+    use crate::short_strings::short_strs;
+
+    fn chk(input: &str, expected: &[&str]) {
+        let config = ParserConfig::new().compat(Compat::RustStd).placeholder_exe("TEST.EXE");
+        let args = config.parse_cmd(input).unwrap().collect::<Vec<_>>();
+        assert_eq!(args, expected, "{:?}", input);
+        let wide = input.encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(config.parse_cmd(&wide).unwrap().collect::<Vec<_>>(), expected, "{:?}", input);
+    }
+
+    // The test vectors of `parse_lp_cmd_line` in the standard library.
+
+    #[test]
+    fn std_empty() {
+        chk("", &["TEST.EXE"]);
+        chk("\0", &["TEST.EXE"]);
+    }
+
+    #[test]
+    fn std_single_words() {
+        chk("EXE one_word", &["EXE", "one_word"]);
+        chk("EXE a", &["EXE", "a"]);
+        chk("EXE 😅", &["EXE", "😅"]);
+        chk("EXE 😅🤦", &["EXE", "😅🤦"]);
+    }
+
+    #[test]
+    fn std_official_examples() {
+        chk(r#"EXE "abc" d e"#, &["EXE", "abc", "d", "e"]);
+        chk(r#"EXE a\\\b d"e f"g h"#, &["EXE", r"a\\\b", "de fg", "h"]);
+        chk(r#"EXE a\\\"b c d"#, &["EXE", r#"a\"b"#, "c", "d"]);
+        chk(r#"EXE a\\\\"b c" d e"#, &["EXE", r"a\\b c", "d", "e"]);
+    }
+
+    #[test]
+    fn std_whitespace_behavior() {
+        chk(" test", &["", "test"]);
+        chk("  test", &["", "test"]);
+        chk(" test test2", &["", "test", "test2"]);
+        chk(" test  test2", &["", "test", "test2"]);
+        chk("test test2 ", &["test", "test2"]);
+        chk("test  test2 ", &["test", "test2"]);
+        chk("test ", &["test"]);
+    }
+
+    #[test]
+    fn std_genius_quotes() {
+        chk(r#"EXE "" """#, &["EXE", "", ""]);
+        chk(r#"EXE "" """"#, &["EXE", "", r#"""#]);
+        chk(r#"EXE "this is """all""" in the same argument""#, &["EXE", r#"this is "all" in the same argument"#]);
+        chk(r#"EXE "a"""#, &["EXE", r#"a""#]);
+        chk(r#"EXE "a"" a"#, &["EXE", r#"a" a"#]);
+        // quotes cannot be escaped in command names
+        chk(r#""EXE" check"#, &["EXE", "check"]);
+        chk(r#""EXE check""#, &["EXE check"]);
+        chk(r#""EXE """for""" check"#, &["EXE for check"]);
+        chk(r#""EXE \"for\" check"#, &[r"EXE \for\ check"]);
+        chk(r#""EXE \" for \" check"#, &[r"EXE \", "for", r#"""#, "check"]);
+        chk(r#"E"X"E test"#, &["EXE", "test"]);
+        chk(r#"EX""E test"#, &["EXE", "test"]);
+    }
+
+    #[test]
+    fn std_post_2008() {
+        chk("EXE CallMeIshmael", &["EXE", "CallMeIshmael"]);
+        chk(r#"EXE "Call Me Ishmael""#, &["EXE", "Call Me Ishmael"]);
+        chk(r#"EXE Cal"l Me I"shmael"#, &["EXE", "Call Me Ishmael"]);
+        chk(r#"EXE CallMe\"Ishmael"#, &["EXE", r#"CallMe"Ishmael"#]);
+        chk(r#"EXE "CallMe\"Ishmael""#, &["EXE", r#"CallMe"Ishmael"#]);
+        chk(r#"EXE "Call Me Ishmael\\""#, &["EXE", r"Call Me Ishmael\"]);
+        chk(r#"EXE "CallMe\\\"Ishmael""#, &["EXE", r#"CallMe\"Ishmael"#]);
+        chk(r#"EXE a\\\b"#, &["EXE", r"a\\\b"]);
+        chk(r#"EXE "a\\\b""#, &["EXE", r"a\\\b"]);
+        chk(r#"EXE "\"Call Me Ishmael\"""#, &["EXE", r#""Call Me Ishmael""#]);
+        chk(r#"EXE "C:\TEST A\\""#, &["EXE", r"C:\TEST A\"]);
+        chk(r#"EXE "\"C:\TEST A\\\"""#, &["EXE", r#""C:\TEST A\""#]);
+        chk(r#"EXE "a b c"  d  e"#, &["EXE", "a b c", "d", "e"]);
+        chk(r#"EXE "ab\"c"  "\\"  d"#, &["EXE", r#"ab"c"#, r"\", "d"]);
+        chk(r#"EXE a\\\b d"e f"g h"#, &["EXE", r"a\\\b", "de fg", "h"]);
+        chk(r#"EXE a\\\"b c d"#, &["EXE", r#"a\"b"#, "c", "d"]);
+        chk(r#"EXE a\\\\"b c" d e"#, &["EXE", r"a\\b c", "d", "e"]);
+        // double double quotes
+        chk(r#"EXE "a b c"""#, &["EXE", r#"a b c""#]);
+        chk(r#"EXE """CallMeIshmael"""  b  c"#, &["EXE", r#""CallMeIshmael""#, "b", "c"]);
+        chk(r#"EXE """Call Me Ishmael""""#, &["EXE", r#""Call Me Ishmael""#]);
+        chk(r#"EXE """"Call Me Ishmael"" b c"#, &["EXE", r#""Call"#, "Me", "Ishmael", "b", "c"]);
+    }
+
+    /// Inputs on which std disagrees with `CommandLineToArgvW`.
+    #[test]
+    fn divergences_from_shell32() {
+        let table: &[(&str, &[&str], &[&str])] = &[
+            // (input, Shell32, RustStd)
+            (r#"EXE "a"" b""#, &["EXE", r#"a""#, "b"], &["EXE", r#"a" b"#]),
+            (r#"EXE """" x"#, &["EXE", r#"" x"#], &["EXE", r#"""#, "x"]),
+            (r#""C:\a b"c d"#, &[r"C:\a b", "c", "d"], &[r"C:\a bc", "d"]),
+            ("\x01EXE x", &["", "EXE", "x"], &["\x01EXE", "x"]),
+        ];
+        let shell32 = ParserConfig::new();
+        for &(input, expected_shell32, expected_std) in table {
+            assert_eq!(shell32.parse_cmd(input).unwrap().collect::<Vec<_>>(), expected_shell32, "{:?}", input);
+            chk(input, expected_std);
+        }
+    }
+
+    /// Std was rewritten to follow the modern C runtime, and so far it agrees on every input.
+    #[test]
+    fn agrees_with_crt_modern() {
+        let alphabet = ['a', '"', '\\', ' ', '\t', '\u{1}', '\0'];
+        let std = ParserConfig::new().compat(Compat::RustStd);
+        let crt = ParserConfig::new().compat(Compat::CrtModern);
+        for input in short_strs(&alphabet, 6) {
+            let cmd = crt.parse_cmd(&input).unwrap().collect::<Vec<_>>();
+            assert_eq!(std.parse_cmd(&input).unwrap().collect::<Vec<_>>(), cmd, "{:?}", input);
+            let args = crt.parse_args(&input).unwrap().collect::<Vec<_>>();
+            assert_eq!(std.parse_args(&input).unwrap().collect::<Vec<_>>(), args, "{:?}", input);
+
+            let strict = |config: &ParserConfig| config.clone().strict_quotes(true).parse_cmd(&input).is_ok();
+            assert_eq!(strict(&std), strict(&crt), "{:?}", input);
+        }
+    }
+}
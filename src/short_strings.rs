@@ -0,0 +1,20 @@
+//! Exhaustive enumeration of short inputs, for tests.  This file is also included by the
+//! integration tests, so it must not depend on the rest of the crate.
+
+/// Every sequence of at most `max_len` items of `alphabet`, shortest first.
+pub(crate) fn short_strings<T: Copy>(alphabet: &[T], max_len: u32) -> impl Iterator<Item=Vec<T>> + '_ {
+    (0..=max_len).flat_map(move |len| {
+        (0..alphabet.len().pow(len)).map(move |mut n| {
+            (0..len).map(|_| {
+                let item = alphabet[n % alphabet.len()];
+                n /= alphabet.len();
+                item
+            }).collect()
+        })
+    })
+}
+
+/// Like [`short_strings`], but for characters.
+pub(crate) fn short_strs(alphabet: &[char], max_len: u32) -> impl Iterator<Item=String> + '_ {
+    short_strings(alphabet, max_len).map(|chars| chars.into_iter().collect())
+}
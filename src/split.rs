@@ -0,0 +1,218 @@
+#[cfg(windows)]
+use std::ffi::{OsStr, OsString};
+use std::ops::Range;
+
+/// Split a command line into the executable name and the raw remainder.
+///
+/// The executable name is parsed by the same rules as in [`Args::parse_cmd`]: if it begins
+/// with a quote, it ends at the next quote, and otherwise it ends at the next whitespace.
+/// The remainder is returned exactly as written, so that it can be forwarded to another
+/// process without being requoted.
+///
+/// ```
+/// use windows_args::split_exe;
+///
+/// assert_eq!(split_exe(r#""C:\Program Files\app.exe"  -x "a b""#), (r#"C:\Program Files\app.exe"#.to_string(), r#"  -x "a b""#));
+/// assert_eq!(split_exe(r#"app.exe  -x "a b""#), ("app.exe".to_string(), r#"  -x "a b""#));
+/// ```
+///
+/// The remainder is everything after the executable name (and its closing quote, if any), so
+/// it keeps the whitespace that ended an unquoted name.  Parsing it with [`Args::parse_args`]
+/// produces the same arguments as [`Args::parse_cmd`] does after the executable name, except
+/// when that whitespace is a control character other than space or tab, which only ends the
+/// executable name and would otherwise be part of the next argument.  If the executable name
+/// has no closing quote, the remainder is empty.  As with [`Args::parse_cmd`], everything from
+/// the first NUL onwards is ignored.
+///
+/// [`Args::parse_cmd`]: struct.Args.html#method.parse_cmd
+/// [`Args::parse_args`]: struct.Args.html#method.parse_args
+pub fn split_exe(input: &str) -> (String, &str) {
+    let (exe, rest) = split_exe_bytes(input.as_bytes());
+    // every boundary found is next to an ASCII character
+    (input[exe].to_string(), &input[rest])
+}
+
+/// **Windows only.**
+/// Like [`split_exe`], but for an [`OsStr`].
+///
+/// [`OsStr`]: https://doc.rust-lang.org/std/ffi/struct.OsStr.html
+#[cfg(windows)]
+pub fn split_exe_os(input: &OsStr) -> (OsString, &OsStr) {
+    let bytes = input.as_encoded_bytes();
+    let (exe, rest) = split_exe_bytes(bytes);
+    // SAFETY: every boundary found is next to an ASCII character, which is a valid place
+    // to split the encoding
+    unsafe {
+        (
+            OsStr::from_encoded_bytes_unchecked(&bytes[exe]).to_os_string(),
+            OsStr::from_encoded_bytes_unchecked(&bytes[rest]),
+        )
+    }
+}
+
+/// The executable name of a command line, both exactly as written and as parsed.
+///
+/// The first element is the text of the executable name in `input`, including any quotes
+/// around it; the second is the argument that [`Args::parse_cmd`] produces from it.  This
+/// shows how the executable was named, which the parsed name alone does not:
+///
+/// ```
+/// use windows_args::exe_token;
+///
+/// assert_eq!(exe_token(r#""C:\Program Files\x.exe" -v"#), (r#""C:\Program Files\x.exe""#, r"C:\Program Files\x.exe".to_string()));
+/// assert_eq!(exe_token(r"C:\PROGRA~1\x.exe -v"), (r"C:\PROGRA~1\x.exe", r"C:\PROGRA~1\x.exe".to_string()));
+/// ```
+///
+/// If the executable name has no closing quote, the raw token runs to the end of the input
+/// (or to the first NUL).  If the input begins with whitespace, the executable name is empty,
+/// and so is the raw token.
+///
+/// [`Args::parse_cmd`]: struct.Args.html#method.parse_cmd
+pub fn exe_token(input: &str) -> (&str, String) {
+    let (raw, exe) = exe_token_bytes(input.as_bytes());
+    // every boundary found is next to an ASCII character
+    (&input[raw], input[exe].to_string())
+}
+
+/// **Windows only.**
+/// Like [`exe_token`], but for an [`OsStr`].
+///
+/// [`OsStr`]: https://doc.rust-lang.org/std/ffi/struct.OsStr.html
+#[cfg(windows)]
+pub fn exe_token_os(input: &OsStr) -> (&OsStr, OsString) {
+    let bytes = input.as_encoded_bytes();
+    let (raw, exe) = exe_token_bytes(bytes);
+    // SAFETY: every boundary found is next to an ASCII character, which is a valid place
+    // to split the encoding
+    unsafe {
+        (
+            OsStr::from_encoded_bytes_unchecked(&bytes[raw]),
+            OsStr::from_encoded_bytes_unchecked(&bytes[exe]).to_os_string(),
+        )
+    }
+}
+
+/// Find the raw executable token and the executable name within it.
+fn exe_token_bytes(input: &[u8]) -> (Range<usize>, Range<usize>) {
+    let (exe, _) = split_exe_bytes(input);
+    let raw_end = match input.first() {
+        // include the closing quote, if there is one
+        Some(b'"') => match input.get(exe.end) {
+            Some(b'"') => exe.end + 1,
+            _ => exe.end,
+        },
+        _ => exe.end,
+    };
+    (0..raw_end, exe)
+}
+
+/// Find the executable name and the remainder in an ASCII-compatible encoding.
+pub(crate) fn split_exe_bytes(input: &[u8]) -> (Range<usize>, Range<usize>) {
+    let end = input.iter().position(|&b| b == 0).unwrap_or(input.len());
+    let content = &input[..end];
+    match content.first() {
+        None => (0..0, 0..0),
+        Some(b'"') => match content[1..].iter().position(|&b| b == b'"') {
+            Some(len) => (1..1 + len, 2 + len..end),
+            None => (1..end, end..end),
+        },
+        // the same notion of whitespace as parse_lp_cmd_line, i.e. any ASCII control character
+        Some(&first) if first <= b' ' => (0..0, 0..end),
+        Some(_) => match content.iter().position(|&b| b <= b' ') {
+            Some(len) => (0..len, len..end),
+            None => (0..end, end..end),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use crate::short_strings::short_strs;
+
+    #[test]
+    fn edge_cases() {
+        let chk = |input: &str, exe: &str, rest: &str| {
+            assert_eq!(split_exe(input), (exe.to_string(), rest));
+        };
+        chk("", "", "");
+        chk("a.exe", "a.exe", "");
+        chk("a.exe   x  ", "a.exe", "   x  ");
+        chk("app.exe  -x", "app.exe", "  -x");
+        chk("\"a b.exe\"   x", "a b.exe", "   x");
+        chk("\"a b.exe\"x y", "a b.exe", "x y");
+        // no closing quote
+        chk("\"a b.exe x", "a b.exe x", "");
+        chk("\"", "", "");
+        // leading whitespace
+        chk("  a.exe x", "", "  a.exe x");
+        chk("\ta", "", "\ta");
+        // quoting in the remainder is untouched
+        chk(r#"a.exe "x \"y\"" \\"#, "a.exe", r#" "x \"y\"" \\"#);
+        chk("a.exe x\0y", "a.exe", " x");
+        chk("😅.exe\t😅", "😅.exe", "\t😅");
+    }
+
+    #[test]
+    fn agrees_with_parser() {
+        let alphabet = ['a', '"', '\\', ' ', '\t', '\u{1}', '\0', 'é'];
+        for input in short_strs(&alphabet, 5) {
+            let (exe, rest) = split_exe(&input);
+            // a control character that ends an unquoted executable name is not whitespace
+            // anywhere else
+            let rest = match input.starts_with('"') {
+                true => rest,
+                false => rest.strip_prefix(|c: char| c < ' ' && c != '\t').unwrap_or(rest),
+            };
+            let mut expected = Args::parse_cmd(&input);
+            assert_eq!(expected.next(), Some(exe), "{:?}", input);
+            assert_eq!(Args::parse_args(rest).collect::<Vec<_>>(), expected.collect::<Vec<_>>(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn exe_tokens() {
+        let chk = |input: &str, raw: &str, exe: &str| {
+            assert_eq!(exe_token(input), (raw, exe.to_string()), "{:?}", input);
+        };
+        chk("", "", "");
+        chk("a.exe x", "a.exe", "a.exe");
+        chk(r#""a b.exe" x"#, r#""a b.exe""#, "a b.exe");
+        chk(r#""a b.exe"x y"#, r#""a b.exe""#, "a b.exe");
+        chk(r#"a"b c"#, r#"a"b"#, r#"a"b"#);
+        // no closing quote
+        chk(r#""a b.exe x"#, r#""a b.exe x"#, "a b.exe x");
+        chk("\"", "\"", "");
+        chk("\"a\0\"", "\"a", "a");
+        // leading whitespace
+        chk("  a.exe", "", "");
+        chk("\ta.exe", "", "");
+    }
+
+    #[test]
+    fn exe_token_agrees_with_parser() {
+        let alphabet = ['a', '"', '\\', ' ', '\t', '\u{1}', '\0', 'é'];
+        for input in short_strs(&alphabet, 5) {
+            let (raw, exe) = exe_token(&input);
+            assert_eq!(Args::parse_cmd(&input).next(), Some(exe.clone()), "{:?}", input);
+            assert!(input.starts_with(raw), "{:?}", input);
+            // the raw token parses to the same executable name, with nothing after it
+            assert_eq!(Args::parse_cmd(raw).collect::<Vec<_>>(), [exe], "{:?}", input);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn os() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let input = OsString::from_wide(&[0xd800, b' ' as u16, b' ' as u16, 0xdc00]);
+        let (exe, rest) = split_exe_os(&input);
+        assert_eq!(exe, OsString::from_wide(&[0xd800]));
+        assert_eq!(rest, OsString::from_wide(&[b' ' as u16, b' ' as u16, 0xdc00]));
+        assert_eq!(split_exe_os(OsStr::new("\"a b\" c")), (OsString::from("a b"), OsStr::new(" c")));
+        assert_eq!(exe_token_os(OsStr::new("\"a b\" c")), (OsStr::new("\"a b\""), OsString::from("a b")));
+        assert_eq!(exe_token_os(&input), (OsString::from_wide(&[0xd800]).as_os_str(), OsString::from_wide(&[0xd800])));
+    }
+}
@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+
+use crate::args::{unwrap_infallible, Infallible, Mode};
+
+/// What to do with an argument that is not valid Unicode, such as one containing an
+/// unpaired UTF-16 surrogate.
+///
+/// Used by [`Args::parse_cmd_os`] and [`ArgsOs::parse_cmd_surrogates`].
+///
+/// [`Args::parse_cmd_os`]: struct.Args.html#method.parse_cmd_os
+/// [`ArgsOs::parse_cmd_surrogates`]: struct.ArgsOs.html#method.parse_cmd_surrogates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SurrogatePolicy {
+    /// Fail with a [`NonUnicodeArg`] naming the first such argument.
+    ///
+    /// [`NonUnicodeArg`]: struct.NonUnicodeArg.html
+    Error,
+    /// Replace each invalid sequence with U+FFFD REPLACEMENT CHARACTER, as by
+    /// `OsStr::to_string_lossy`.
+    ReplaceWithFffd,
+    /// Leave the argument as it is.
+    ///
+    /// Only an `OsString` can hold such an argument, so wherever the output is a `String`,
+    /// this behaves like [`SurrogatePolicy::Error`].
+    ///
+    /// [`SurrogatePolicy::Error`]: #variant.Error
+    Keep,
+}
+
+/// An argument was not valid Unicode, under [`SurrogatePolicy::Error`].
+///
+/// [`SurrogatePolicy::Error`]: enum.SurrogatePolicy.html#variant.Error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonUnicodeArg {
+    index: usize,
+    arg: OsString,
+}
+
+impl NonUnicodeArg {
+    /// The index of the argument, counting the executable name (if any) as 0.
+    pub fn index(&self) -> usize { self.index }
+
+    /// The argument itself.
+    pub fn arg(&self) -> &OsStr { &self.arg }
+}
+
+impl fmt::Display for NonUnicodeArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "argument {} is not valid Unicode: {:?}", self.index, self.arg)
+    }
+}
+
+impl Error for NonUnicodeArg {}
+
+/// Split an `OsStr` into arguments, and convert each of them to a `String` according to
+/// `policy`.
+///
+/// The parser runs directly over the encoded bytes, which works because it only ever looks
+/// at ASCII characters.
+pub(crate) fn parse_os_to_strings(input: &OsStr, mode: Mode, policy: SurrogatePolicy) -> Result<Vec<String>, NonUnicodeArg> {
+    let mut args = Vec::new();
+    let mut error = None;
+    unwrap_infallible(mode.parse::<Infallible, u8>(input.as_encoded_bytes().iter().cloned(), &mut Vec::new(), |arg, _| {
+        // SAFETY: the argument is made of pieces of the input split next to ASCII characters,
+        // and of ASCII characters, so it is valid in the encoding of the input
+        let arg = unsafe { OsStr::from_encoded_bytes_unchecked(arg) };
+        match (arg.to_str(), policy) {
+            (Some(arg), _) => args.push(arg.to_string()),
+            (None, SurrogatePolicy::ReplaceWithFffd) => args.push(arg.to_string_lossy().into_owned()),
+            (None, SurrogatePolicy::Error) | (None, SurrogatePolicy::Keep) => {
+                if error.is_none() {
+                    error = Some(NonUnicodeArg { index: args.len(), arg: arg.to_os_string() });
+                }
+                args.push(String::new());
+            },
+        }
+        Ok(())
+    }));
+    match error {
+        Some(error) => Err(error),
+        None => Ok(args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+
+    /// `"C:\a b\app.exe" x<bad> "y z"<bad>`, where `<bad>` is an unpaired surrogate on Windows
+    /// and an invalid byte elsewhere.
+    fn bad_input() -> OsString {
+        #[cfg(windows)]
+        {
+            use std::os::windows::ffi::OsStringExt;
+            let mut wide = r#""C:\a b\app.exe" x"#.encode_utf16().collect::<Vec<u16>>();
+            wide.push(0xD800);
+            wide.extend(r#" "y z""#.encode_utf16());
+            wide.push(0xDC00);
+            OsString::from_wide(&wide)
+        }
+        #[cfg(not(windows))]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            OsString::from_vec(b"\"C:\\a b\\app.exe\" x\xff \"y z\"\xfe".to_vec())
+        }
+    }
+
+    fn collect(args: Result<Args, NonUnicodeArg>) -> Result<Vec<String>, usize> {
+        args.map(Iterator::collect).map_err(|e| e.index())
+    }
+
+    #[test]
+    fn policies() {
+        let input = bad_input();
+        assert_eq!(collect(Args::parse_cmd_os(&input, SurrogatePolicy::Error)), Err(1));
+        assert_eq!(collect(Args::parse_cmd_os(&input, SurrogatePolicy::Keep)), Err(1));
+        assert_eq!(collect(Args::parse_cmd_os(&input, SurrogatePolicy::ReplaceWithFffd)), Ok(vec![
+            r"C:\a b\app.exe".to_string(),
+            "x\u{FFFD}".to_string(),
+            "y z\u{FFFD}".to_string(),
+        ]));
+        assert_eq!(collect(Args::parse_args_os(&input, SurrogatePolicy::Error)), Err(1));
+
+        let err = Args::parse_cmd_os(&input, SurrogatePolicy::Error).unwrap_err();
+        assert_eq!(Args::parse_cmd_os(err.arg(), SurrogatePolicy::ReplaceWithFffd).unwrap().collect::<Vec<_>>(), ["x\u{FFFD}"]);
+    }
+
+    #[test]
+    fn valid_input_matches_parse_cmd() {
+        for &input in &["", "app.exe", r#""C:\a b\app.exe" x "y \" z" \\"#, "é 😅\0hidden", "\x01a\x01b"] {
+            for policy in [SurrogatePolicy::Error, SurrogatePolicy::ReplaceWithFffd, SurrogatePolicy::Keep] {
+                assert_eq!(
+                    collect(Args::parse_cmd_os(input.as_ref(), policy)),
+                    Ok(Args::parse_cmd(input).collect::<Vec<_>>()),
+                );
+                assert_eq!(
+                    collect(Args::parse_args_os(input.as_ref(), policy)),
+                    Ok(Args::parse_args(input).collect::<Vec<_>>()),
+                );
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn args_os() {
+        use crate::ArgsOs;
+
+        let input = bad_input();
+        let kept = ArgsOs::parse_cmd_surrogates(&input, SurrogatePolicy::Keep).unwrap();
+        assert_eq!(kept.collect::<Vec<_>>(), ArgsOs::parse_cmd(&input).collect::<Vec<_>>());
+        assert_eq!(ArgsOs::parse_cmd_surrogates(&input, SurrogatePolicy::Error).unwrap_err().index(), 1);
+        let replaced = ArgsOs::parse_cmd_surrogates(&input, SurrogatePolicy::ReplaceWithFffd).unwrap();
+        assert_eq!(replaced.collect::<Vec<_>>(), [r"C:\a b\app.exe", "x\u{FFFD}", "y z\u{FFFD}"]);
+    }
+}
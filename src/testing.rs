@@ -0,0 +1,67 @@
+//! **Requires the `proptest` feature.**
+//! Tools for testing code that produces or consumes Windows command lines.
+
+/// [`proptest`] strategies biased toward inputs that exercise the parser's interesting states.
+///
+/// Uniformly random strings almost never contain things like a long run of backslashes
+/// followed by a quote, so these generate command lines by concatenating weighted fragments:
+///
+/// * runs of backslashes, often followed by a quote
+/// * clusters of `"` characters
+/// * quoted runs immediately adjacent to unquoted runs
+/// * runs of spaces and tabs
+/// * ASCII control characters, including the occasional NUL
+/// * ordinary words, including non-ASCII ones
+/// * lone surrogates (only in [`wide_cmd_line`])
+///
+/// Failures shrink by removing fragments and by shrinking the fragments themselves.
+///
+/// ```
+/// use proptest::prelude::*;
+/// use windows_args::testing::adversarial;
+///
+/// proptest!(|(input in adversarial::cmd_line(16))| {
+///     let _ = windows_args::Args::parse_cmd(&input).collect::<Vec<_>>();
+/// });
+/// ```
+///
+/// [`proptest`]: https://docs.rs/proptest
+pub mod adversarial {
+    use proptest::prelude::*;
+    use proptest::collection::vec;
+
+    /// Command lines made of up to `size` fragments.
+    pub fn cmd_line(size: usize) -> impl Strategy<Value=String> {
+        vec(fragment(), 0..=size).prop_map(|fragments| fragments.concat())
+    }
+
+    /// Like [`cmd_line`], but as UTF-16, and also including lone surrogates.
+    pub fn wide_cmd_line(size: usize) -> impl Strategy<Value=Vec<u16>> {
+        let wide_fragment = prop_oneof![
+            9 => fragment().prop_map(|s| s.encode_utf16().collect::<Vec<_>>()),
+            1 => (0xd800u16..=0xdfff).prop_map(|unit| vec![unit]),
+        ];
+        vec(wide_fragment, 0..=size).prop_map(|fragments| fragments.concat())
+    }
+
+    /// A single fragment of a command line.
+    pub fn fragment() -> impl Strategy<Value=String> {
+        prop_oneof![
+            3 => (0..=64usize, any::<bool>()).prop_map(|(n, quote)| {
+                format!("{}{}", "\\".repeat(n), if quote { "\"" } else { "" })
+            }),
+            3 => (1..=8usize).prop_map(|n| "\"".repeat(n)),
+            2 => (word(), word(), any::<bool>()).prop_map(|(a, b, quoted_first)| match quoted_first {
+                true => format!("\"{}\"{}", a, b),
+                false => format!("{}\"{}\"", a, b),
+            }),
+            3 => vec(prop_oneof![Just(' '), Just('\t')], 1..=4).prop_map(|ws| ws.into_iter().collect()),
+            1 => prop_oneof![7 => proptest::char::range('\u{1}', '\u{1f}'), 1 => Just('\0')].prop_map(String::from),
+            3 => word(),
+        ]
+    }
+
+    fn word() -> impl Strategy<Value=String> {
+        "[a-zA-Z0-9:./é😅-]{1,8}"
+    }
+}
@@ -0,0 +1,85 @@
+#[cfg(windows)]
+use std::ffi::OsStr;
+
+/// Options for [`Args::parse_cmd_trimmed`] and [`ArgsOs::parse_cmd_trimmed`].
+///
+/// Trailing NULs, carriage returns and line feeds are always removed.  Tabs and spaces are
+/// left alone, and are parsed as usual.
+///
+/// [`Args::parse_cmd_trimmed`]: struct.Args.html#method.parse_cmd_trimmed
+/// [`ArgsOs::parse_cmd_trimmed`]: struct.ArgsOs.html#method.parse_cmd_trimmed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrimOptions {
+    /// Whether to ignore everything from the first carriage return or line feed onwards.
+    ///
+    /// A command line stored in the registry is a single line, so anything after a line
+    /// break is usually junk that was pasted along with it.
+    pub stop_at_newline: bool,
+}
+
+pub(crate) fn trim_str<'a>(input: &'a str, opts: &TrimOptions) -> &'a str {
+    // every boundary found is next to an ASCII character
+    &input[..trimmed_len(input.as_bytes(), opts)]
+}
+
+#[cfg(windows)]
+pub(crate) fn trim_os<'a>(input: &'a OsStr, opts: &TrimOptions) -> &'a OsStr {
+    let bytes = input.as_encoded_bytes();
+    let len = trimmed_len(bytes, opts);
+    // SAFETY: the boundary found is next to an ASCII character, which is a valid place
+    // to split the encoding
+    unsafe { OsStr::from_encoded_bytes_unchecked(&bytes[..len]) }
+}
+
+/// Find the length of the trimmed input in an ASCII-compatible encoding.
+fn trimmed_len(input: &[u8], opts: &TrimOptions) -> usize {
+    let is_junk = |b: &u8| matches!(b, b'\0' | b'\r' | b'\n');
+    let mut input = input;
+    if opts.stop_at_newline {
+        if let Some(end) = input.iter().position(|&b| b == b'\r' || b == b'\n') {
+            input = &input[..end];
+        }
+    }
+    input.iter().rposition(|b| !is_junk(b)).map_or(0, |last| last + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+
+    fn chk(input: &str, stop_at_newline: bool, expected: &[&str]) {
+        let opts = TrimOptions { stop_at_newline };
+        assert_eq!(Args::parse_cmd_trimmed(input, &opts).collect::<Vec<_>>(), expected, "{:?}", input);
+        #[cfg(windows)]
+        assert_eq!(
+            crate::ArgsOs::parse_cmd_trimmed(input.as_ref(), &opts).collect::<Vec<_>>(), expected,
+            "{:?}", input,
+        );
+    }
+
+    #[test]
+    fn trailing_nuls() {
+        chk("app.exe /s\0\0", false, &["app.exe", "/s"]);
+        chk("\"C:\\a b\\app.exe\" \"x\r\n\0\0", false, &["C:\\a b\\app.exe", "x"]);
+        chk("\0\0", false, &[""]);
+        chk("", false, &[""]);
+    }
+
+    #[test]
+    fn newlines() {
+        chk("app.exe /s\r\n", false, &["app.exe", "/s"]);
+        chk("app.exe /s\r\nrem junk", true, &["app.exe", "/s"]);
+        chk("app.exe /s\nrem junk\r\n", true, &["app.exe", "/s"]);
+        chk("\r\nrem junk", true, &[""]);
+
+        // without stop_at_newline, interior line breaks are ordinary characters
+        chk("app.exe /s\r\nrem junk", false, &["app.exe", "/s\r\nrem", "junk"]);
+    }
+
+    #[test]
+    fn whitespace_is_untouched() {
+        chk("app.exe \t\"a \t\" \t\r\n", true, &["app.exe", "a \t"]);
+        chk("\"app.exe \" \t\0", true, &["app.exe "]);
+    }
+}
@@ -0,0 +1,34 @@
+//! Interop with the `widestring` crate, behind the `widestring` feature.
+
+use std::os::windows::ffi::OsStrExt;
+use widestring::{U16CStr, U16Str, U16String};
+use crate::ArgsOs;
+
+impl ArgsOs {
+    /// **Requires the `widestring` feature.**
+    /// Parse a NUL-terminated wide string containing the complete command line.
+    ///
+    /// The input is parsed in place, as by [`ArgsOs::parse_cmd_wide`].  Otherwise, this
+    /// behaves like [`ArgsOs::parse_cmd`].
+    pub fn parse_cmd_u16cstr(input: &U16CStr) -> Self {
+        ArgsOs::parse_cmd_wide(input.as_slice())
+    }
+
+    /// **Requires the `widestring` feature.**
+    /// Parse a wide string containing the complete command line.
+    ///
+    /// This is equivalent to [`ArgsOs::parse_cmd_wide`].
+    pub fn parse_cmd_u16str(input: &U16Str) -> Self {
+        ArgsOs::parse_cmd_wide(input.as_slice())
+    }
+}
+
+/// **Requires the `widestring` feature.**
+/// Collects the remaining arguments.
+impl From<ArgsOs> for Vec<U16String> {
+    fn from(args: ArgsOs) -> Vec<U16String> {
+        args.inner.into_vec().into_iter()
+            .map(|arg| U16String::from_vec(arg.encode_wide().collect::<Vec<_>>()))
+            .collect()
+    }
+}
@@ -0,0 +1,94 @@
+/// Lists the names of the entries in a directory, for wildcard expansion.
+///
+/// The directory is given as it was written in the argument (for instance `src\` or `C:`),
+/// or as the empty string for the current directory.
+pub(crate) type DirLister<'a> = dyn FnMut(&str) -> Vec<String> + 'a;
+
+/// List a directory of the real filesystem.  Errors produce an empty listing, just as a
+/// failed `FindFirstFile` produces no matches.
+pub(crate) fn read_dir(dir: &str) -> Vec<String> {
+    let dir = if dir.is_empty() { "." } else { dir };
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()?.file_name().into_string().ok()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Expand an argument containing `*` or `?`, in the way of the CRT's `setargv.obj`.
+///
+/// Returns `None` if the argument should be kept as it is: because it has no wildcards in its
+/// final component, because there are wildcards in the directory part (which `FindFirstFile`
+/// does not support), or because nothing matched.
+pub(crate) fn expand(arg: &str, dirs: &mut DirLister<'_>) -> Option<Vec<String>> {
+    let is_wild = |c| c == '*' || c == '?';
+    let name_start = arg.rfind(['\\', '/', ':']).map_or(0, |pos| pos + 1);
+    let (dir, pattern) = arg.split_at(name_start);
+    if !pattern.contains(is_wild) || dir.contains(is_wild) {
+        return None;
+    }
+
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let matches = dirs(dir).into_iter()
+        .filter(|name| name != "." && name != "..")
+        .filter(|name| matches(&pattern, &name.chars().collect::<Vec<_>>()))
+        .map(|name| format!("{}{}", dir, name))
+        .collect::<Vec<_>>();
+    match matches.is_empty() {
+        true => None,
+        false => Some(matches),
+    }
+}
+
+/// Case-insensitive matching of `*` (any run of characters) and `?` (any one character).
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    let same = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+
+    // on a mismatch, retry the most recent `*` with one more character
+    let (mut p, mut n) = (0, 0);
+    let mut retry = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                retry = Some((p, n));
+                p += 1;
+            },
+            Some(&c) if c == '?' || same(c, name[n]) => {
+                p += 1;
+                n += 1;
+            },
+            _ => match retry {
+                Some((star, start)) => {
+                    retry = Some((star, start + 1));
+                    p = star + 1;
+                    n = start + 1;
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        let chk = |pattern: &str, name: &str, expected: bool| {
+            let chars = |s: &str| s.chars().collect::<Vec<_>>();
+            assert_eq!(matches(&chars(pattern), &chars(name)), expected, "{:?} {:?}", pattern, name);
+        };
+        chk("*", "", true);
+        chk("*", "a.txt", true);
+        chk("*.txt", "a.txt", true);
+        chk("*.TXT", "a.txt", true);
+        chk("*.txt", "a.txt.bak", false);
+        chk("a?c", "abc", true);
+        chk("a?c", "ac", false);
+        chk("a*b*c", "aXbYbZc", true);
+        chk("a*b*c", "aXbYbZ", false);
+        chk("**x", "x", true);
+        chk("É*", "été", true);
+    }
+}
@@ -0,0 +1,40 @@
+//! Interop with the string types of the `windows` crate, behind the `windows-strings` feature.
+//!
+//! These come from the `windows-strings` crate, which `windows::core` re-exports.
+
+use windows_strings::{HSTRING, PCWSTR};
+use crate::ArgsOs;
+
+impl ArgsOs {
+    /// **Requires the `windows-strings` feature.**
+    /// Parse a NUL-terminated wide string containing the complete command line.
+    ///
+    /// This is equivalent to [`ArgsOs::from_lpcmdline`].  It does not copy the input, and a
+    /// null pointer is treated like an empty string.
+    ///
+    /// ```no_run
+    /// use windows::Win32::System::Environment::GetCommandLineW;
+    ///
+    /// let args = unsafe { windows_args::ArgsOs::parse_cmd_pcwstr(GetCommandLineW()) };
+    /// for arg in args {
+    ///     println!("{:?}", arg);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Unless null, the pointer must point to a NUL-terminated string that remains valid
+    /// and unmodified for the duration of the call.
+    pub unsafe fn parse_cmd_pcwstr(input: PCWSTR) -> Self {
+        ArgsOs::from_lpcmdline(input.0)
+    }
+
+    /// **Requires the `windows-strings` feature.**
+    /// Parse an `HSTRING` containing the complete command line.
+    ///
+    /// This is equivalent to [`ArgsOs::parse_cmd_wide`], so unpaired surrogates are preserved
+    /// and parsing stops at the first NUL, if any.
+    pub fn parse_cmd_hstring(input: &HSTRING) -> Self {
+        ArgsOs::parse_cmd_wide(input)
+    }
+}
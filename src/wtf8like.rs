@@ -1,12 +1,39 @@
+use std::collections::TryReserveError;
 use wtf8::{Wtf8, Wtf8Buf};
 
 pub(crate) trait IsWtf8Slice {
-    fn encode_wide(&self) -> Vec<u16>;
+    /// An upper bound on the number of UTF-16 code units produced by `extend_wide`.
+    fn wide_len_bound(&self) -> usize;
+    fn extend_wide(&self, out: &mut Vec<u16>);
 }
 
 pub(crate) trait IsWtf8Buf: Sized {
     fn from_wide(wide: &[u16]) -> Self;
-    fn from_str(str: &str) -> Self;
+
+    /// Like `from_wide`, but reports allocation failure instead of aborting.
+    ///
+    /// Lone surrogates may still incur a small temporary allocation through the
+    /// infallible APIs, as neither `OsString` nor `Wtf8Buf` exposes a way to push them
+    /// into an existing buffer.
+    fn try_from_wide(wide: &[u16]) -> Result<Self, TryReserveError>;
+
+    /// Replace the contents with `wide`, reusing the existing allocation where possible.
+    fn assign_wide(&mut self, wide: &[u16]) {
+        *self = Self::from_wide(wide);
+    }
+
+    /// Length of the WTF-8 encoding.
+    fn wtf8_byte_len(&self) -> usize;
+
+    /// The byte at `i` of the WTF-8 encoding if it is ASCII, else 0xFF.
+    fn ascii_byte_at(&self, i: usize) -> u8;
+}
+
+/// Exact length of the WTF-8 encoding of a UTF-16 string.
+pub(crate) fn wtf8_len(wide: &[u16]) -> usize {
+    std::char::decode_utf16(wide.iter().cloned())
+        .map(|c| c.map_or(3, char::len_utf8))
+        .sum()
 }
 
 #[cfg(windows)]
@@ -16,8 +43,13 @@ mod windows_impls {
     use std::os::windows::ffi::{OsStrExt, OsStringExt};
 
     impl IsWtf8Slice for OsStr {
-        fn encode_wide(&self) -> Vec<u16> {
-            <OsStr as OsStrExt>::encode_wide(self).collect()
+        fn wide_len_bound(&self) -> usize {
+            // every code unit takes at least one byte in WTF-8
+            self.len()
+        }
+
+        fn extend_wide(&self, out: &mut Vec<u16>) {
+            out.extend(<OsStr as OsStrExt>::encode_wide(self))
         }
     }
 
@@ -26,15 +58,56 @@ mod windows_impls {
             <OsString as OsStringExt>::from_wide(wide)
         }
 
-        fn from_str(s: &str) -> Self {
-            s.into()
+        fn try_from_wide(wide: &[u16]) -> Result<Self, TryReserveError> {
+            let mut out = OsString::new();
+            out.try_reserve_exact(wtf8_len(wide))?;
+            push_wide(&mut out, wide);
+            Ok(out)
+        }
+
+        fn assign_wide(&mut self, wide: &[u16]) {
+            self.clear();
+            self.reserve(wtf8_len(wide));
+            push_wide(self, wide);
+        }
+
+        fn wtf8_byte_len(&self) -> usize {
+            self.len()
+        }
+
+        fn ascii_byte_at(&self, i: usize) -> u8 {
+            ascii_or_ff(self.as_encoded_bytes()[i])
+        }
+    }
+
+    /// Append without reallocating, provided that enough capacity has been reserved.
+    fn push_wide(out: &mut OsString, wide: &[u16]) {
+        for c in std::char::decode_utf16(wide.iter().cloned()) {
+            match c {
+                Ok(c) => out.push(c.encode_utf8(&mut [0; 4])),
+                Err(e) => out.push(<OsString as OsStringExt>::from_wide(&[e.unpaired_surrogate()])),
+            }
         }
     }
 }
 
+impl IsWtf8Slice for [u16] {
+    fn wide_len_bound(&self) -> usize {
+        self.len()
+    }
+
+    fn extend_wide(&self, out: &mut Vec<u16>) {
+        out.extend_from_slice(self)
+    }
+}
+
 impl IsWtf8Slice for Wtf8 {
-    fn encode_wide(&self) -> Vec<u16> {
-        self.to_ill_formed_utf16().collect()
+    fn wide_len_bound(&self) -> usize {
+        self.len()
+    }
+
+    fn extend_wide(&self, out: &mut Vec<u16>) {
+        out.extend(self.to_ill_formed_utf16())
     }
 }
 
@@ -43,7 +116,66 @@ impl IsWtf8Buf for Wtf8Buf {
         Wtf8Buf::from_ill_formed_utf16(wide)
     }
 
-    fn from_str(s: &str) -> Self {
-        Wtf8Buf::from_str(s)
+    fn try_from_wide(wide: &[u16]) -> Result<Self, TryReserveError> {
+        let mut out = String::new();
+        out.try_reserve_exact(wtf8_len(wide))?;
+        for c in std::char::decode_utf16(wide.iter().cloned()) {
+            match c {
+                Ok(c) => out.push(c),
+                // Wtf8Buf has no fallible API, so give up on reusing the buffer
+                Err(_) => return Ok(Wtf8Buf::from_ill_formed_utf16(wide)),
+            }
+        }
+        Ok(Wtf8Buf::from_string(out))
+    }
+
+    fn wtf8_byte_len(&self) -> usize {
+        self.len()
+    }
+
+    fn ascii_byte_at(&self, i: usize) -> u8 {
+        Wtf8::ascii_byte_at(self, i)
+    }
+}
+
+/// Only for parsing input that was valid UTF-8 to begin with.
+impl IsWtf8Buf for String {
+    fn from_wide(wide: &[u16]) -> Self {
+        let mut out = String::new();
+        out.assign_wide(wide);
+        out
+    }
+
+    fn try_from_wide(wide: &[u16]) -> Result<Self, TryReserveError> {
+        let mut out = String::new();
+        out.try_reserve_exact(wtf8_len(wide))?;
+        push_still_utf8(&mut out, wide);
+        Ok(out)
+    }
+
+    fn assign_wide(&mut self, wide: &[u16]) {
+        self.clear();
+        self.reserve(wtf8_len(wide));
+        push_still_utf8(self, wide);
+    }
+
+    fn wtf8_byte_len(&self) -> usize {
+        self.len()
+    }
+
+    fn ascii_byte_at(&self, i: usize) -> u8 {
+        ascii_or_ff(self.as_bytes()[i])
+    }
+}
+
+fn ascii_or_ff(byte: u8) -> u8 {
+    if byte.is_ascii() { byte } else { 0xFF }
+}
+
+fn push_still_utf8(out: &mut String, wide: &[u16]) {
+    for c in std::char::decode_utf16(wide.iter().cloned()) {
+        out.push(c.unwrap_or_else(|e| {
+            panic!("valid UTF-8 became invalid after arg splitting?! ({:?})", e)
+        }));
     }
 }
@@ -0,0 +1,63 @@
+#![cfg(feature = "proptest")]
+
+// Properties that must hold for all inputs, checked against the adversarial generator.
+
+use proptest::prelude::*;
+use windows_args::testing::adversarial;
+use windows_args::{Args, ParseScratch, ParserConfig};
+
+proptest! {
+    #[test]
+    fn parse_never_panics(input in adversarial::cmd_line(32)) {
+        let cmd = Args::parse_cmd(&input).collect::<Vec<_>>();
+        prop_assert!(!cmd.is_empty());
+        let _ = Args::parse_args(&input).collect::<Vec<_>>();
+    }
+
+    #[test]
+    fn parse_args_is_parse_cmd_without_exe(input in adversarial::cmd_line(32)) {
+        let args = Args::parse_args(&input).collect::<Vec<_>>();
+        let cmd = Args::parse_cmd(&format!("a {}", input)).skip(1).collect::<Vec<_>>();
+        prop_assert_eq!(args, cmd);
+    }
+
+    #[test]
+    fn entry_points_agree(input in adversarial::cmd_line(32)) {
+        let expected = Args::parse_cmd(&input).collect::<Vec<_>>();
+
+        let reserving = Args::try_parse_cmd_reserving(&input).unwrap().collect::<Vec<_>>();
+        prop_assert_eq!(&reserving, &expected);
+
+        let mut out = vec!["leftover".to_string(); 3];
+        Args::parse_cmd_into(&input, &mut out, &mut ParseScratch::new());
+        prop_assert_eq!(&out, &expected);
+
+        let config = ParserConfig::default();
+        prop_assert_eq!(config.parse_cmd(&input).unwrap().collect::<Vec<_>>(), expected);
+        let args = Args::parse_args(&input).collect::<Vec<_>>();
+        prop_assert_eq!(config.parse_args(&input).unwrap().collect::<Vec<_>>(), args);
+    }
+
+    #[test]
+    fn utf8_agrees_with_utf16(input in adversarial::cmd_line(32)) {
+        let wide = input.encode_utf16().collect::<Vec<_>>();
+        let cmd = Args::parse_cmd(&input).collect::<Vec<_>>();
+        prop_assert_eq!(cmd, Args::parse_cmd_wide(&wide).unwrap().collect::<Vec<_>>());
+        let args = Args::parse_args(&input).collect::<Vec<_>>();
+        prop_assert_eq!(args, Args::parse_args_wide(&wide).unwrap().collect::<Vec<_>>());
+    }
+}
+
+#[cfg(windows)]
+proptest! {
+    #[test]
+    fn wide_parse_never_panics(input in adversarial::wide_cmd_line(32)) {
+        use std::ffi::OsString;
+        use std::os::windows::ffi::OsStringExt;
+
+        let input = OsString::from_wide(&input);
+        let cmd = windows_args::ArgsOs::parse_cmd(&input).collect::<Vec<_>>();
+        prop_assert!(!cmd.is_empty());
+        let _ = windows_args::ArgsOs::parse_args(&input).collect::<Vec<_>>();
+    }
+}
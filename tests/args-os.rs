@@ -0,0 +1,31 @@
+#![cfg(windows)]
+
+// Tests that the arguments of the current process agree with std.
+
+#[test]
+fn matches_std() {
+    let ours = windows_args::args_os().collect::<Vec<_>>();
+    let std = std::env::args_os().collect::<Vec<_>>();
+    assert_eq!(ours, std);
+
+    assert_eq!(windows_args::args().collect::<Vec<_>>(), std::env::args().collect::<Vec<_>>());
+}
+
+#[test]
+fn repeated_calls() {
+    let first = windows_args::args_os().collect::<Vec<_>>();
+    for _ in 0..1000 {
+        assert_eq!(windows_args::args_os().collect::<Vec<_>>(), first);
+    }
+}
+
+#[test]
+fn keeps_raw() {
+    let args_os = windows_args::args_os();
+    let mut args = windows_args::args();
+    assert_eq!(args.raw(), args_os.raw().map(|raw| raw.to_str().unwrap()));
+
+    args.next();
+    let rest = args_os.raw().unwrap().to_str().unwrap();
+    assert!(rest.ends_with(args.rest_raw().unwrap()));
+}
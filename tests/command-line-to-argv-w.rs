@@ -1,41 +1,23 @@
 #![cfg(windows)]
 
-// Tests that ArgsOs::parse is equivalent to CommandLineToArgvW, except in the case
-// of the empty string.
+// Tests that ArgsOs::parse is equivalent to CommandLineToArgvW, when configured to produce
+// the current exe for the empty string.
+
+mod common;
 
 use std::collections::VecDeque;
 use std::ffi::OsString;
-use std::os::windows::ffi::OsStringExt;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::fmt::Write;
 use std::slice;
 use std::iter;
-use std::ptr;
+use std::time::SystemTime;
 
 // function that behaves identical to CommandLineToArgvW, implemented in terms of
 // the windows_args crate
 fn new_parser(lp_cmd_line: &[u16]) -> VecDeque<OsString> {
-    let out: VecDeque<OsString> = {
-        windows_args::ArgsOs::parse_cmd(&OsString::from_wide(lp_cmd_line)).collect()
-    };
-
-    match lp_cmd_line[0] {
-        0 => {
-            // CommandLineToArgvW is defined to return the current exe on empty strings;
-            // that doesn't make sense for us, so we treat it like a pure whitespace input.
-            assert_eq!(out, VecDeque::from(vec!["".into()]));
-            VecDeque::from(vec![unsafe { current_exe() }])
-        },
-        _ => out,
-    }
-}
-
-unsafe fn current_exe() -> OsString {
-    let mut exe_name: [u16; 4096] = [0; 4096];
-    let ch = GetModuleFileNameW(ptr::null_mut(), &mut exe_name as *mut [u16; 4096] as *mut u16, 4096);
-    if ch == 0 {
-        OsString::new()
-    } else {
-        OsString::from_wide(&exe_name[0..ch as usize])
-    }
+    let config = windows_args::ParserConfig::new().current_exe_for_empty(true);
+    config.parse_cmd_os(&OsString::from_wide(lp_cmd_line)).unwrap().collect()
 }
 
 unsafe fn old_parser(lp_cmd_line: &[u16]) -> VecDeque<OsString> {
@@ -64,7 +46,6 @@ extern "system" {
 #[link(name="Kernel32")]
 extern "system" {
     fn LocalFree(pNumArgs: *mut *mut u16);
-    fn GetModuleFileNameW(hModule: *mut u32, lpFilename: *mut u16, nSize: u32) -> u32;
 }
 
 fn test_chars() -> impl Iterator<Item=u16> {
@@ -80,6 +61,50 @@ fn test_chars() -> impl Iterator<Item=u16> {
         .chain(iter::once(0xdeee)) // a low surrogate
 }
 
+/// Short strings made of the characters that matter around the end of the executable name.
+fn exe_boundary_inputs() -> impl Iterator<Item=Vec<u16>> {
+    const CHARS: &[u16] = &[b'a' as u16, b'"' as u16, b' ' as u16, b'\t' as u16, b'\\' as u16];
+    common::short_strings::short_strings(CHARS, 6).filter(|input| !input.is_empty())
+}
+
+#[test]
+fn exe_token_boundaries() {
+    let shapes = [r#"a"b c"d e"#, r#""unterminated exe"#, r#"" a b"#, r#""" a"#, r#"a"" b"#, "\"a\"\tb"];
+    let inputs = shapes.iter().map(|s| s.encode_utf16().collect()).chain(exe_boundary_inputs());
+    for mut input in inputs {
+        input.push(0);
+        unsafe {
+            assert_eq!(old_parser(&input), new_parser(&input), "ucs_2={:?}", input);
+        }
+    }
+}
+
+#[test]
+fn trailing_empty_default() {
+    use windows_args::{ParserConfig, TrailingEmpty};
+
+    for input in [r#"foo """#, r#"foo ""#, r#"foo "bar"#] {
+        let mut input = input.encode_utf16().collect::<Vec<u16>>();
+        input.push(0);
+        let old_result = unsafe { old_parser(&input) };
+        assert_eq!(old_result, new_parser(&input), "{:?}", input);
+
+        let config = ParserConfig::new().trailing_empty(TrailingEmpty::Keep);
+        let keep = config.parse_cmd_os(&OsString::from_wide(&input)).unwrap().collect::<VecDeque<_>>();
+        assert_eq!(old_result, keep, "{:?}", input);
+    }
+}
+
+/// Where the parser matches the local shell32, detection should find the default.
+#[test]
+fn detected_quirks() {
+    use windows_args::{Compat, DelimiterPolicy, DetectedQuirks, ParserConfig};
+
+    let expected = DetectedQuirks::Matched { compat: Compat::Shell32, delimiters: DelimiterPolicy::ControlPlane };
+    assert_eq!(ParserConfig::current_windows_quirks(), expected);
+    assert_eq!(ParserConfig::for_current_windows(), ParserConfig::new().delimiters(DelimiterPolicy::ControlPlane));
+}
+
 #[test]
 fn command_line_to_argv_w_near_equivalence() {
     // Test with no executable at the beginning
@@ -121,3 +146,89 @@ fn command_line_to_argv_w_near_equivalence() {
         }
     }
 }
+
+#[test]
+fn units_match_slices() {
+    for a in test_chars() {
+        for b in test_chars() {
+            for c in test_chars() {
+                for d in test_chars() {
+                    for input in &[[a, b, c, d, 0, 0], ['a' as u16, ' ' as u16, a, b, c, d]] {
+                        let expected = windows_args::ArgsOs::parse_cmd_wide(input).collect::<Vec<_>>();
+                        let units = windows_args::ArgsOs::parse_cmd_units(input.iter().cloned());
+                        assert_eq!(units.collect::<Vec<_>>(), expected, "ucs_2={:?}", input);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Regenerates `tests/fixture/shell32.txt`, which lets the parser be checked against
+/// `CommandLineToArgvW` on other platforms.
+#[test]
+#[ignore]
+fn record_fixture() {
+    let official_examples = [
+        r#"EXE "abc" d e"#,
+        r#"EXE a\\\b d"e f"g h"#,
+        r#"EXE a\\\"b c d"#,
+        r#"EXE a\\\\"b c" d e"#,
+        r#""C:\Program Files\a.exe" b"#,
+    ];
+    let mut inputs = official_examples.iter().map(|s| s.encode_utf16().collect()).collect::<Vec<Vec<u16>>>();
+    inputs.extend(exe_boundary_inputs());
+    for a in test_chars() {
+        for b in test_chars() {
+            inputs.push(vec!['a' as u16, ' ' as u16, a, b]);
+            for c in test_chars() {
+                inputs.push(vec![a, b, c]);
+            }
+        }
+    }
+
+    let mut out = String::from("\
+# Expected output of CommandLineToArgvW, checked on every platform by the unit tests.
+# The `source` line says where the cases came from.
+#
+# Each case is a line of tab-separated fields: the input (without its NUL terminator),
+# followed by each argument.  Fields are UTF-16 code units, as 4 hex digits each.
+#
+# Regenerate on Windows with:
+#     cargo test --test command-line-to-argv-w -- --ignored record_fixture
+");
+    writeln!(out, "version: 1").unwrap();
+    writeln!(out, "source: recorded from CommandLineToArgvW on {}", today()).unwrap();
+    for mut input in inputs {
+        if input[0] == 0 {
+            // CommandLineToArgvW returns the current exe, which is not reproducible
+            continue;
+        }
+        input.push(0);
+        let output = unsafe { old_parser(&input) };
+
+        let fields = iter::once(&input[..input.len() - 1])
+            .map(<[u16]>::to_vec)
+            .chain(output.iter().map(|arg| arg.encode_wide().collect()));
+        let line = fields.map(|field| field.iter().map(|u| format!("{:04x}", u)).collect::<String>());
+        writeln!(out, "{}", line.collect::<Vec<_>>().join("\t")).unwrap();
+    }
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixture/shell32.txt");
+    std::fs::write(path, out).unwrap();
+}
+
+/// The current UTC date, as YYYY-MM-DD.
+fn today() -> String {
+    let secs = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
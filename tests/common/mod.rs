@@ -0,0 +1,83 @@
+// Helpers shared by the integration tests, included with `mod common;`.
+
+#![allow(dead_code)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ptr;
+
+#[cfg(windows)]
+pub mod process;
+
+#[path = "../../src/short_strings.rs"]
+pub mod short_strings;
+
+/// A global allocator that counts the allocations made by each thread, and that can be made
+/// to fail with [`with_budget`].  Install it in a test crate with:
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: common::TestAlloc = common::TestAlloc;
+/// ```
+pub struct TestAlloc;
+
+thread_local! {
+    // (number of allocations, total bytes allocated)
+    static COUNTS: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+    // Bytes that may still be allocated by this thread, or None for no limit.
+    static BUDGET: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+unsafe impl GlobalAlloc for TestAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allowed = BUDGET.try_with(|budget| match budget.get() {
+            None => true,
+            Some(remaining) if layout.size() <= remaining => {
+                budget.set(Some(remaining - layout.size()));
+                true
+            },
+            Some(_) => false,
+        }).unwrap_or(true);
+        if !allowed {
+            return ptr::null_mut();
+        }
+
+        let _ = COUNTS.try_with(|counts| {
+            let (count, bytes) = counts.get();
+            counts.set((count + 1, bytes + layout.size()));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// What was allocated while running a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocations {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Run `func`, and count what this thread allocated meanwhile.
+pub fn allocations<T>(func: impl FnOnce() -> T) -> (T, Allocations) {
+    let (count_before, bytes_before) = COUNTS.with(Cell::get);
+    let out = func();
+    let (count, bytes) = COUNTS.with(Cell::get);
+    (out, Allocations { count: count - count_before, bytes: bytes - bytes_before })
+}
+
+/// The number of allocations made by `func`.
+pub fn count_allocs(func: impl FnOnce()) -> usize {
+    allocations(func).1.count
+}
+
+/// Run `func` with allocations on this thread failing once they exceed `bytes` in total.
+pub fn with_budget<T>(bytes: usize, func: impl FnOnce() -> T) -> T {
+    BUDGET.with(|budget| budget.set(Some(bytes)));
+    let out = func();
+    BUDGET.with(|budget| budget.set(None));
+    out
+}
@@ -0,0 +1,88 @@
+// Starting a child process with an exact command line, through CreateProcessW.
+
+use std::ffi::c_void;
+use std::mem;
+use std::ptr;
+
+type Handle = *mut c_void;
+
+#[repr(C)]
+struct StartupInfoW {
+    cb: u32,
+    reserved: *mut u16,
+    desktop: *mut u16,
+    title: *mut u16,
+    x: u32,
+    y: u32,
+    x_size: u32,
+    y_size: u32,
+    x_count_chars: u32,
+    y_count_chars: u32,
+    fill_attribute: u32,
+    flags: u32,
+    show_window: u16,
+    cb_reserved2: u16,
+    reserved2: *mut u8,
+    std_input: Handle,
+    std_output: Handle,
+    std_error: Handle,
+}
+
+#[repr(C)]
+struct ProcessInformation {
+    process: Handle,
+    thread: Handle,
+    process_id: u32,
+    thread_id: u32,
+}
+
+const INFINITE: u32 = 0xffff_ffff;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn CreateProcessW(
+        lpApplicationName: *const u16,
+        lpCommandLine: *mut u16,
+        lpProcessAttributes: *mut c_void,
+        lpThreadAttributes: *mut c_void,
+        bInheritHandles: i32,
+        dwCreationFlags: u32,
+        lpEnvironment: *mut c_void,
+        lpCurrentDirectory: *const u16,
+        lpStartupInfo: *mut StartupInfoW,
+        lpProcessInformation: *mut ProcessInformation,
+    ) -> i32;
+    fn WaitForSingleObject(hHandle: Handle, dwMilliseconds: u32) -> u32;
+    fn GetExitCodeProcess(hProcess: Handle, lpExitCode: *mut u32) -> i32;
+    fn CloseHandle(hObject: Handle) -> i32;
+}
+
+/// Run a process to completion and return its exit code.
+///
+/// `application` is the NUL-terminated path of the executable, or null to let Windows find
+/// it from the command line.  `cmd_line` must be NUL-terminated, and may be modified by
+/// `CreateProcessW` while it runs.
+///
+/// # Safety
+///
+/// Both pointers must be null or point to NUL-terminated wide strings.
+pub unsafe fn run(application: *const u16, cmd_line: *mut u16) -> u32 {
+    let mut startup_info: StartupInfoW = mem::zeroed();
+    startup_info.cb = mem::size_of::<StartupInfoW>() as u32;
+    let mut process_info: ProcessInformation = mem::zeroed();
+
+    let ok = CreateProcessW(
+        application, cmd_line,
+        ptr::null_mut(), ptr::null_mut(), 0, 0,
+        ptr::null_mut(), ptr::null(),
+        &mut startup_info, &mut process_info,
+    );
+    assert_ne!(ok, 0, "CreateProcessW failed: {}", std::io::Error::last_os_error());
+
+    WaitForSingleObject(process_info.process, INFINITE);
+    let mut code = 0;
+    assert_ne!(GetExitCodeProcess(process_info.process, &mut code), 0);
+    CloseHandle(process_info.thread);
+    CloseHandle(process_info.process);
+    code
+}
@@ -0,0 +1,18 @@
+#![cfg(windows)]
+
+// Tests that a CmdLineBuffer can be given to CreateProcessW.
+
+mod common;
+
+use std::ffi::OsStr;
+use windows_args::CmdLineBuffer;
+
+#[test]
+fn create_process() {
+    let mut buf = CmdLineBuffer::new(OsStr::new(r#"cmd.exe /d /c "exit 7""#)).unwrap();
+
+    let code = unsafe { common::process::run(std::ptr::null(), buf.as_mut_ptr()) };
+    assert_eq!(code, 7);
+
+    assert_eq!(buf.reparse().collect::<Vec<_>>(), ["cmd.exe", "/d", "/c", "exit 7"]);
+}
@@ -0,0 +1,73 @@
+// Tests that parse_cmd_into agrees with parse_cmd, and that it stops allocating once
+// its buffers have grown large enough.
+
+mod common;
+
+use common::count_allocs;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use windows_args::{Args, ParseScratch};
+
+#[global_allocator]
+static ALLOC: common::TestAlloc = common::TestAlloc;
+
+// Deliberately share the buffers between a sequence of inputs, so that leftover state from
+// one parse would have a chance to leak into the next.
+fn check_sequence(inputs: &[String]) -> Result<(), TestCaseError> {
+    let mut scratch = ParseScratch::new();
+    let mut out = vec![];
+    for input in inputs {
+        for input in [input.clone(), format!("EXE {}", input)] {
+            Args::parse_cmd_into(&input, &mut out, &mut scratch);
+            prop_assert_eq!(&out, &Args::parse_cmd(&input).collect::<Vec<_>>(), "{:?}", input);
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn equivalent_to_parse_cmd(inputs in vec(r#"[a \t\\"\x02é😅]{0,12}"#, 1..8)) {
+        check_sequence(&inputs)?;
+    }
+}
+
+#[cfg(feature = "proptest")]
+proptest! {
+    #[test]
+    fn equivalent_to_parse_cmd_adversarial(inputs in vec(windows_args::testing::adversarial::cmd_line(16), 1..8)) {
+        check_sequence(&inputs)?;
+    }
+}
+
+#[test]
+fn steady_state_does_not_allocate() {
+    let lines = [
+        r#""C:\Program Files\thing.exe" --flag "some value" C:\path\to\file.txt"#,
+        r#"C:\Windows\system32\cmd.exe /c "echo hi""#,
+        r#"a.exe"#,
+        r#"b.exe a\\\"b "" """#,
+    ];
+
+    let mut scratch = ParseScratch::new();
+    let mut out = vec![];
+    // grow the scratch buffers beyond the size of any single line
+    Args::parse_cmd_into(&lines.join(" "), &mut out, &mut scratch);
+
+    for line in &lines {
+        Args::parse_cmd_into(line, &mut out, &mut scratch);
+        let allocs = count_allocs(|| Args::parse_cmd_into(line, &mut out, &mut scratch));
+        assert_eq!(allocs, 0, "{:?}", line);
+    }
+}
+
+#[test]
+fn reuses_element_capacity() {
+    let mut scratch = ParseScratch::new();
+    let mut out = vec![];
+    Args::parse_cmd_into("a_long_exe_name.exe another_long_argument", &mut out, &mut scratch);
+
+    let allocs = count_allocs(|| Args::parse_cmd_into("short.exe arg", &mut out, &mut scratch));
+    assert_eq!(allocs, 0);
+    assert_eq!(out, vec!["short.exe".to_string(), "arg".to_string()]);
+}
@@ -0,0 +1,118 @@
+// Stress tests for inputs that are designed to hit the parser's worst cases.
+//
+// The crate promises linear time, and memory proportional to the input and output.
+// Allocations are counted with a global allocator.  Time is checked deterministically, by
+// counting the code units that the parser reads and comparing the allocations for inputs of
+// two different sizes; a check with a clock is also here, but ignored by default, since it
+// is at the mercy of the machine it runs on.
+
+mod common;
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use windows_args::{Args, ArgsWtf8};
+
+#[global_allocator]
+static ALLOC: common::TestAlloc = common::TestAlloc;
+
+const BIG: usize = 1 << 20;
+
+fn backslashes_then_quote(n: usize) -> String {
+    format!("EXE {}\"", "\\".repeat(n))
+}
+
+fn alternating_quotes(n: usize) -> String {
+    format!("EXE {}", "\"".repeat(n))
+}
+
+fn many_quote_pairs(n: usize) -> String {
+    format!("EXE{}", " \"\"".repeat(n / 3))
+}
+
+/// Parses the input, returning the number of args along with the number of
+/// allocations and bytes allocated by the parser.
+fn measure(input: &str) -> (usize, usize, usize) {
+    let (args, allocs) = common::allocations(|| Args::parse_cmd(input));
+    (args.len(), allocs.count, allocs.bytes)
+}
+
+fn check_memory(input: &str, bytes: usize, len: usize) {
+    // The wide buffer, the buffer for the current arg, and the args themselves are each at
+    // most a couple of times the size of the input, and the output vector costs a couple
+    // of `Wtf8Buf`s per arg due to amortized growth.
+    assert!(bytes < 8 * input.len() + 64 * len, "{} bytes for {} args", bytes, len);
+}
+
+fn time(input: &str) -> Duration {
+    (0..3).map(|_| {
+        let start = Instant::now();
+        drop(Args::parse_cmd(input));
+        start.elapsed()
+    }).min().unwrap()
+}
+
+fn check_scaling(make_input: impl Fn(usize) -> String) {
+    let small = make_input(BIG / 8);
+    let big = make_input(BIG);
+    for input in &[&small, &big] {
+        // every code unit is read exactly once
+        let reads = Cell::new(0);
+        drop(ArgsWtf8::parse_cmd_units(input.encode_utf16().inspect(|_| reads.set(reads.get() + 1))));
+        assert_eq!(reads.get(), input.len());
+    }
+    let (_, _, small_bytes) = measure(&small);
+    let (_, _, big_bytes) = measure(&big);
+    // linear should be ~8x; quadratic would be ~64x
+    assert!(big_bytes < small_bytes * 32, "{} vs {} bytes", small_bytes, big_bytes);
+}
+
+fn check_wall_clock_scaling(make_input: impl Fn(usize) -> String) {
+    let small = time(&make_input(BIG / 8));
+    let big = time(&make_input(BIG));
+    // linear should be ~8x; quadratic would be ~64x
+    assert!(big < small * 32 + Duration::from_millis(50), "{:?} vs {:?}", small, big);
+}
+
+#[test]
+fn long_backslash_run() {
+    let input = backslashes_then_quote(BIG);
+    let (len, count, bytes) = measure(&input);
+    assert_eq!(len, 2);
+    assert!(count < 64, "{}", count);
+    check_memory(&input, bytes, len);
+
+    assert_eq!(Args::parse_cmd(&input).nth(1).unwrap().len(), BIG / 2);
+    check_scaling(backslashes_then_quote);
+}
+
+#[test]
+fn alternating_quote_chars() {
+    let input = alternating_quotes(BIG);
+    let (len, count, bytes) = measure(&input);
+    assert_eq!(len, 2);
+    assert!(count < 64, "{}", count);
+    check_memory(&input, bytes, len);
+
+    check_scaling(alternating_quotes);
+}
+
+#[test]
+fn thousands_of_empty_args() {
+    let input = many_quote_pairs(BIG);
+    let (len, count, bytes) = measure(&input);
+    assert_eq!(len, BIG / 3 + 1);
+    // empty args must not allocate individually
+    assert!(count < 64, "{}", count);
+    check_memory(&input, bytes, len);
+
+    check_scaling(many_quote_pairs);
+}
+
+#[test]
+#[ignore = "depends on the speed and load of the machine; run with --ignored"]
+fn wall_clock_scaling() {
+    check_wall_clock_scaling(backslashes_then_quote);
+    check_wall_clock_scaling(alternating_quotes);
+    check_wall_clock_scaling(many_quote_pairs);
+}
@@ -0,0 +1,92 @@
+#![cfg(windows)]
+
+// Tests that powershell::split agrees with pwsh on the cases in tests/fixture/pwsh.txt.
+
+use std::fmt::Write;
+use std::io;
+use std::process::Command;
+use windows_args::powershell;
+
+const FIXTURE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixture/pwsh.txt");
+
+/// A script block that prints each of its arguments as hex UTF-16, followed by `;`.
+const PRINT_ARGS: &str = r#"{ [Console]::Out.Write(-join ($args | ForEach-Object { -join ([int[]][char[]]"$_" | ForEach-Object { '{0:x4}' -f $_ }) + ';' })) }"#;
+
+fn encode(s: &str) -> String {
+    s.encode_utf16().map(|u| format!("{:04x}", u)).collect()
+}
+
+fn decode(field: &str) -> String {
+    let units = (0..field.len()).step_by(4)
+        .map(|i| u16::from_str_radix(&field[i..i + 4], 16).unwrap())
+        .collect::<Vec<_>>();
+    String::from_utf16(&units).unwrap()
+}
+
+/// The inputs of the fixture, and the arguments expected for each.
+fn fixture_cases() -> Vec<(String, Vec<String>)> {
+    let fixture = std::fs::read_to_string(FIXTURE_PATH).unwrap();
+    let lines = fixture.lines().filter(|line| !line.starts_with('#')).skip(2);
+    lines.map(|line| {
+        let mut fields = line.split('\t').map(decode);
+        (fields.next().unwrap(), fields.collect())
+    }).collect()
+}
+
+/// Run a script with `pwsh -c`, or return `None` if pwsh is not installed.
+fn run_pwsh(script: &str) -> Option<String> {
+    let output = Command::new("pwsh").args(["-NoProfile", "-NonInteractive", "-Command", script]).output();
+    let output = match output {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => panic!("could not run pwsh: {}", e),
+    };
+    assert!(output.status.success(), "pwsh failed on {:?}: {}", script, String::from_utf8_lossy(&output.stderr));
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+/// The arguments that pwsh gives to a command written with `input` after it.
+fn pwsh_split(input: &str) -> Option<Vec<String>> {
+    let output = run_pwsh(&format!("& {} {}", PRINT_ARGS, input))?;
+    let output = output.trim_end();
+    Some(output.split_terminator(';').map(decode).collect())
+}
+
+#[test]
+fn agrees_with_pwsh() {
+    if run_pwsh("exit").is_none() {
+        eprintln!("pwsh is not installed; skipping");
+        return;
+    }
+    for (input, expected) in fixture_cases() {
+        let values = powershell::split(&input).unwrap().into_iter().map(|arg| arg.value).collect::<Vec<_>>();
+        let actual = pwsh_split(&input).unwrap();
+        assert_eq!(values, actual, "input: {:?}", input);
+        assert_eq!(expected, actual, "the fixture is out of date; input: {:?}", input);
+    }
+}
+
+/// Regenerates `tests/fixture/pwsh.txt` from its own inputs, which lets the powershell module
+/// be checked against pwsh on other platforms.
+#[test]
+#[ignore]
+fn record_fixture() {
+    let version = run_pwsh("[Console]::Out.Write($PSVersionTable.PSVersion)").expect("pwsh is not installed");
+    let mut out = String::from("\
+# Expected output of PowerShell's argument mode, checked on every platform by the unit tests
+# of the powershell module.  The `source` line says where the cases came from.
+#
+# Each case is a line of tab-separated fields: the arguments as written after a command,
+# followed by the value of each argument.  Fields are UTF-16 code units, as 4 hex digits each.
+#
+# Regenerate on Windows, with pwsh on the PATH, with:
+#     cargo test --test powershell -- --ignored record_fixture
+");
+    writeln!(out, "version: 1").unwrap();
+    writeln!(out, "source: recorded from pwsh {}", version.trim()).unwrap();
+    for (input, _) in fixture_cases() {
+        let fields = std::iter::once(input.clone()).chain(pwsh_split(&input).unwrap());
+        writeln!(out, "{}", fields.map(|field| encode(&field)).collect::<Vec<_>>().join("\t")).unwrap();
+    }
+    std::fs::write(FIXTURE_PATH, out).unwrap();
+}
@@ -0,0 +1,165 @@
+// Tests that Quoted round-trips through the parser, that it agrees with a straightforward
+// implementation of the quoting rules, and that it doesn't allocate.
+
+mod common;
+
+use std::fmt::{self, Write};
+
+use common::count_allocs;
+use proptest::prelude::*;
+use windows_args::{Args, Quoted};
+
+#[global_allocator]
+static ALLOC: common::TestAlloc = common::TestAlloc;
+
+/// A fixed-size buffer that can be written to without allocating.
+struct StackBuf {
+    buf: [u8; 256],
+    len: usize,
+}
+
+impl Write for StackBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buf.get_mut(self.len..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+fn roundtrip(arg: &str) -> Vec<String> {
+    Args::parse_args(&Quoted(arg).to_string()).collect()
+}
+
+/// Quote an argument by the rules that `Quoted` documents, one run of backslashes at a time.
+fn reference_quote(arg: &str) -> String {
+    let quote = arg.is_empty() || arg.contains([' ', '\t']);
+    let mut out = String::new();
+    if quote {
+        out.push('"');
+    }
+    let mut chars = arg.chars().peekable();
+    loop {
+        let mut backslashes = 0;
+        while chars.next_if_eq(&'\\').is_some() {
+            backslashes += 1;
+        }
+        match chars.next() {
+            None => {
+                // backslashes are only special before a quote, including the closing one
+                out.push_str(&"\\".repeat(if quote { 2 * backslashes } else { backslashes }));
+                break;
+            },
+            Some('"') => {
+                out.push_str(&"\\".repeat(2 * backslashes + 1));
+                out.push('"');
+            },
+            Some(c) => {
+                out.push_str(&"\\".repeat(backslashes));
+                out.push(c);
+            },
+        }
+    }
+    if quote {
+        out.push('"');
+    }
+    out
+}
+
+#[test]
+fn ascii_does_not_allocate() {
+    let exe = r#"C:\Program Files\app.exe"#;
+    let path = r#"C:\dir with spaces\"#;
+    let mut buf = StackBuf { buf: [0; 256], len: 0 };
+
+    let allocs = count_allocs(|| {
+        write!(buf, "running {} with {} {}", Quoted(exe), Quoted(path), Quoted(r#"a"b"#)).unwrap();
+    });
+    assert_eq!(allocs, 0);
+
+    assert_eq!(
+        std::str::from_utf8(&buf.buf[..buf.len]).unwrap(),
+        r#"running "C:\Program Files\app.exe" with "C:\dir with spaces\\" a\"b"#,
+    );
+}
+
+#[test]
+fn display_quoted_does_not_allocate() {
+    let mut args = Args::parse_cmd(r#"app.exe --mode fast -o "C:\out dir\\" --include=src\*.rs "" x"#);
+    args.next();
+    let mut buf = StackBuf { buf: [0; 256], len: 0 };
+
+    let allocs = count_allocs(|| write!(buf, "{}", args.display_quoted()).unwrap());
+    assert_eq!(allocs, 0);
+
+    assert_eq!(
+        std::str::from_utf8(&buf.buf[..buf.len]).unwrap(),
+        r#"--mode fast -o "C:\out dir\\" --include=src\*.rs "" x"#,
+    );
+}
+
+proptest! {
+    #[test]
+    fn display_quoted_roundtrips(list in proptest::collection::vec("[^\0]*", 0..6), skip in 0..3usize) {
+        let line = list.iter().map(|arg| Quoted(arg).to_string()).collect::<Vec<_>>().join(" ");
+        let mut args = Args::parse_args(&line);
+        for _ in 0..skip {
+            args.next();
+        }
+        let expected = args.display_quoted().to_string();
+        let remaining = list.iter().skip(skip).cloned().collect::<Vec<_>>();
+        prop_assert_eq!(Args::parse_args(&expected).collect::<Vec<_>>(), remaining);
+    }
+
+    #[test]
+    fn roundtrips_any_string(arg in "[^\0]*") {
+        prop_assert_eq!(roundtrip(&arg), vec![arg]);
+    }
+
+    #[test]
+    fn roundtrips_metacharacters(arg in r#"[ \t\\"a]{0,12}"#) {
+        prop_assert_eq!(roundtrip(&arg), vec![arg]);
+    }
+
+    #[test]
+    fn matches_reference(arg in prop_oneof![r#"[ \t\\"a]{0,12}"#, "[^\0]*"]) {
+        prop_assert_eq!(Quoted(&arg).to_string(), reference_quote(&arg));
+    }
+}
+
+#[cfg(feature = "proptest")]
+proptest! {
+    #[test]
+    fn roundtrips_adversarial(arg in windows_args::testing::adversarial::cmd_line(16)) {
+        let arg = arg.replace('\0', "");
+        prop_assert_eq!(roundtrip(&arg), vec![arg]);
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn os_matches_str() {
+    use std::ffi::OsStr;
+    use windows_args::QuotedOs;
+
+    for arg in &["", "a b", r#"a\"b"#, r#"x y\\"#, "😅"] {
+        assert_eq!(QuotedOs(OsStr::new(arg)).to_string(), Quoted(arg).to_string());
+        assert_eq!(QuotedOs(OsStr::new(arg)).to_os_string(), OsStr::new(&Quoted(arg).to_string()));
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn os_unpaired_surrogates() {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use windows_args::{ArgsOs, QuotedOs};
+
+    for wide in &[&[0xd800][..], &[0x20, 0xdc00, 0x5c], &[0x22, 0xd800, 0x5c, 0x22]] {
+        let arg = OsString::from_wide(wide);
+        let lossy = arg.to_string_lossy();
+        assert_eq!(QuotedOs(&arg).to_string(), Quoted(&lossy).to_string());
+        let quoted = QuotedOs(&arg).to_os_string();
+        assert_eq!(ArgsOs::parse_args(&quoted).collect::<Vec<_>>(), [arg]);
+    }
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "rayon")]
+
+// Tests that parallel iteration agrees with sequential iteration.
+
+use rayon::prelude::*;
+use windows_args::Args;
+
+fn many_args() -> Args {
+    let line = (0..10_000).map(|i| format!(r#""C:\dir {}\file.txt" -{}"#, i, i)).collect::<Vec<_>>();
+    Args::parse_args(&line.join(" "))
+}
+
+#[test]
+fn order_is_preserved() {
+    let sequential = many_args().map(|arg| arg.len()).collect::<Vec<_>>();
+
+    let args = many_args();
+    let borrowed = args.par_iter().map(str::len).collect::<Vec<_>>();
+    assert_eq!(borrowed, sequential);
+
+    let owned = args.into_par_iter().map(|arg| arg.len()).collect::<Vec<_>>();
+    assert_eq!(owned, sequential);
+}
+
+#[test]
+fn remaining_args_only() {
+    let mut args = Args::parse_args("a b c d");
+    args.next();
+    args.next_back();
+    assert_eq!(args.par_iter().collect::<Vec<_>>(), ["b", "c"]);
+    assert_eq!(args.into_par_iter().collect::<Vec<_>>(), ["b", "c"]);
+}
+
+#[test]
+fn empty() {
+    let args = Args::parse_args("");
+    assert_eq!(args.par_iter().count(), 0);
+    assert_eq!(args.into_par_iter().collect::<Vec<String>>(), Vec::<String>::new());
+}
+
+#[cfg(windows)]
+#[test]
+fn os_order_is_preserved() {
+    use std::ffi::OsString;
+    use windows_args::ArgsOs;
+
+    let line = OsString::from((0..1000).map(|i| format!("a{} ", i)).collect::<String>());
+    let sequential = ArgsOs::parse_args(&line).collect::<Vec<_>>();
+
+    let args = ArgsOs::parse_args(&line);
+    assert_eq!(args.par_iter().map(|s| s.to_owned()).collect::<Vec<_>>(), sequential);
+    assert_eq!(args.into_par_iter().collect::<Vec<_>>(), sequential);
+    assert_eq!(ArgsOs::parse_args(&OsString::new()).into_par_iter().count(), 0);
+}
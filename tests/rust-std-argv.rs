@@ -0,0 +1,76 @@
+// Tests that Compat::RustStd agrees with what std::env::args_os sees in a real Rust process.
+//
+// This is not a libtest test: the test binary starts copies of itself with chosen command
+// lines, and each copy reports its arguments back through a file.
+
+#[cfg(windows)]
+mod common;
+
+fn main() {
+    #[cfg(windows)]
+    windows::main();
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::{OsStr, OsString};
+    use std::fs;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use crate::common;
+    use windows_args::{Compat, ParserConfig};
+
+    const OUTPUT_VAR: &str = "WINDOWS_ARGS_RUST_STD_ARGV_OUTPUT";
+
+    pub fn main() {
+        match std::env::var_os(OUTPUT_VAR) {
+            Some(path) => write_args(&path),
+            None => compare_all(),
+        }
+    }
+
+    /// In the child: write each argument as a line of UTF-16 code units.
+    fn write_args(path: &OsStr) {
+        let mut out = String::new();
+        for arg in std::env::args_os() {
+            let units = arg.encode_wide().map(|c| c.to_string()).collect::<Vec<_>>();
+            out.push_str(&units.join(","));
+            out.push('\n');
+        }
+        fs::write(path, out).unwrap();
+    }
+
+    /// Run a copy of this executable with the given command line, and read back its arguments.
+    fn child_args(exe: &[u16], cmd_line: &str, output: &std::path::Path) -> Vec<OsString> {
+        let mut cmd_line = cmd_line.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+        unsafe { common::process::run(exe.as_ptr(), cmd_line.as_mut_ptr()) };
+
+        let contents = fs::read_to_string(output).unwrap();
+        contents.lines().map(|line| {
+            let units = line.split(',').filter(|s| !s.is_empty()).map(|s| s.parse().unwrap()).collect::<Vec<u16>>();
+            OsString::from_wide(&units)
+        }).collect()
+    }
+
+    /// Short strings made of the characters that matter to the parser.
+    fn inputs() -> impl Iterator<Item=String> {
+        const CHARS: &[char] = &['a', '"', '\\', ' ', '\t', '\u{1}'];
+        common::short_strings::short_strs(CHARS, 4)
+    }
+
+    fn compare_all() {
+        let output = std::env::temp_dir().join(format!("windows-args-rust-std-{}.txt", std::process::id()));
+        std::env::set_var(OUTPUT_VAR, &output);
+        let exe = std::env::current_exe().unwrap().into_os_string().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+
+        let config = ParserConfig::new().compat(Compat::RustStd).current_exe_for_empty(true);
+        let shapes = [r#"EXE "a"" b""#, r#""C:\a b"c d"#, r#"EX""E test"#, r#"EXE """"Call Me Ishmael"" b c"#];
+        let mut count = 0;
+        for input in shapes.iter().map(|s| s.to_string()).chain(inputs()) {
+            let expected = config.parse_cmd_os(&input).unwrap().collect::<Vec<OsString>>();
+            assert_eq!(child_args(&exe, &input, &output), expected, "{:?}", input);
+            count += 1;
+        }
+        let _ = fs::remove_file(&output);
+        println!("rust-std-argv: {} command lines agree with std::env::args_os", count);
+    }
+}
@@ -0,0 +1,73 @@
+// Tests that the `try_*_reserving` functions report allocation failure instead of
+// aborting, using a global allocator that starts failing after a fixed number of bytes.
+
+mod common;
+
+use common::with_budget;
+use windows_args::Args;
+
+#[global_allocator]
+static ALLOC: common::TestAlloc = common::TestAlloc;
+
+const CMD: &str = r#"EXE a\\\b d"e f"g h "" "this is """all""" in the same argument" 😅"#;
+
+#[test]
+fn cmd_fails_gracefully_at_every_budget() {
+    let expected = Args::parse_cmd(CMD).collect::<Vec<_>>();
+
+    let mut saw_failure = false;
+    for budget in 0.. {
+        match with_budget(budget, || Args::try_parse_cmd_reserving(CMD)) {
+            Err(_) => saw_failure = true,
+            Ok(args) => {
+                assert_eq!(args.collect::<Vec<_>>(), expected);
+                break;
+            },
+        }
+    }
+    assert!(saw_failure);
+}
+
+#[test]
+fn args_fails_gracefully_at_every_budget() {
+    let expected = Args::parse_args(CMD).collect::<Vec<_>>();
+
+    let mut saw_failure = false;
+    for budget in 0.. {
+        match with_budget(budget, || Args::try_parse_args_reserving(CMD)) {
+            Err(_) => saw_failure = true,
+            Ok(args) => {
+                assert_eq!(args.collect::<Vec<_>>(), expected);
+                break;
+            },
+        }
+    }
+    assert!(saw_failure);
+}
+
+#[test]
+fn huge_input_fails_gracefully() {
+    let input = "a ".repeat(1000);
+    assert!(with_budget(1000, || Args::try_parse_cmd_reserving(&input)).is_err());
+}
+
+#[cfg(windows)]
+#[test]
+fn os_fails_gracefully_at_every_budget() {
+    use std::ffi::OsStr;
+    use windows_args::ArgsOs;
+
+    let expected = ArgsOs::parse_cmd(OsStr::new(CMD)).collect::<Vec<_>>();
+
+    let mut saw_failure = false;
+    for budget in 0.. {
+        match with_budget(budget, || ArgsOs::try_parse_cmd_reserving(OsStr::new(CMD))) {
+            Err(_) => saw_failure = true,
+            Ok(args) => {
+                assert_eq!(args.collect::<Vec<_>>(), expected);
+                break;
+            },
+        }
+    }
+    assert!(saw_failure);
+}
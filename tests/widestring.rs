@@ -0,0 +1,42 @@
+#![cfg(all(windows, feature = "widestring"))]
+
+// Tests that the widestring inputs agree with the OsStr-based ones.
+
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use widestring::{U16CString, U16String};
+use windows_args::ArgsOs;
+
+fn inputs() -> Vec<Vec<u16>> {
+    let w = |s: &str| s.encode_utf16().collect::<Vec<_>>();
+    vec![
+        vec![],
+        w(r#"EXE "a b" c\\\"d"#),
+        [&w("EXE \"")[..], &[0xd800], &w(" b\" ")].concat(),
+        [&[0xdc00][..], &w(" "), &[0xd83d, 0xde05], &w(" \"")].concat(),
+    ]
+}
+
+#[test]
+fn matches_os_str() {
+    for wide in inputs() {
+        let expected = ArgsOs::parse_cmd(&OsString::from_wide(&wide)).collect::<Vec<_>>();
+
+        let cstr = U16CString::from_vec(wide.clone()).unwrap();
+        assert_eq!(ArgsOs::parse_cmd_u16cstr(&cstr).collect::<Vec<_>>(), expected);
+
+        let string = U16String::from_vec(wide);
+        assert_eq!(ArgsOs::parse_cmd_u16str(&string).collect::<Vec<_>>(), expected);
+    }
+}
+
+#[test]
+fn into_u16_strings() {
+    for wide in inputs() {
+        let string = U16String::from_vec(wide);
+        let expected = ArgsOs::parse_cmd_u16str(&string).map(|arg| U16String::from_os_str(&arg)).collect::<Vec<_>>();
+        let mut args = ArgsOs::parse_cmd_u16str(&string);
+        args.next();
+        assert_eq!(Vec::<U16String>::from(args), expected[1..]);
+    }
+}
@@ -0,0 +1,46 @@
+#![cfg(all(windows, feature = "windows-strings"))]
+
+// Tests that the windows-strings inputs agree with the OsStr-based ones.
+
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use windows::core::{HSTRING, PCWSTR};
+use windows_args::ArgsOs;
+
+fn inputs() -> Vec<Vec<u16>> {
+    let w = |s: &str| s.encode_utf16().collect::<Vec<_>>();
+    vec![
+        vec![],
+        w(r#"EXE "a b" c\\\"d"#),
+        [&w("EXE \"")[..], &[0xd800], &w(" b\" ")].concat(),
+        [&[0xdc00][..], &w(" "), &[0xd83d, 0xde05], &w(" \"")].concat(),
+    ]
+}
+
+#[test]
+fn matches_os_str() {
+    for wide in inputs() {
+        let expected = ArgsOs::parse_cmd(&OsString::from_wide(&wide)).collect::<Vec<_>>();
+
+        assert_eq!(ArgsOs::parse_cmd_hstring(&HSTRING::from_wide(&wide)).collect::<Vec<_>>(), expected);
+
+        let mut terminated = wide.clone();
+        terminated.push(0);
+        let args = unsafe { ArgsOs::parse_cmd_pcwstr(PCWSTR(terminated.as_ptr())) };
+        assert_eq!(args.collect::<Vec<_>>(), expected);
+    }
+}
+
+#[test]
+fn null_pcwstr() {
+    let args = unsafe { ArgsOs::parse_cmd_pcwstr(PCWSTR::null()) };
+    assert_eq!(args.collect::<Vec<_>>(), [OsString::new()]);
+}
+
+#[test]
+fn get_command_line() {
+    use windows::Win32::System::Environment::GetCommandLineW;
+
+    let args = unsafe { ArgsOs::parse_cmd_pcwstr(GetCommandLineW()) };
+    assert_eq!(args.collect::<Vec<_>>(), windows_args::args_os().collect::<Vec<_>>());
+}